@@ -1,12 +1,10 @@
-use std::collections::HashMap;
-
 use rusths::ths::{THS, Adjust, Interval};
 use chrono::Local;
 
 fn main() {
     // 初始化日志
     // 创建 THS 实例
-    let mut ths = THS::new(None).expect("Failed to create THS instance");
+    let ths = THS::new(None).expect("Failed to create THS instance");
 
     // 连接到服务器
     ths.connect().expect("Failed to connect to server");
@@ -23,8 +21,8 @@ fn main() {
         "USHA600000",  // 浦发银行
         Some(start_time),
         Some(end_time),
-        Adjust::NONE,
-        Interval::DAY,
+        Adjust::None,
+        Interval::Day,
         100,
     ).expect("Failed to get klines");
     println!("K线数据: {:?}", klines);
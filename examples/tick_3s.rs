@@ -1,13 +1,14 @@
 
-use rusths::ths::{THS, Adjust, Interval, ThsOption};
+use rusths::ths::{THS, ThsOption};
 
 fn main() {
 
     // 创建 THS 实例
-    let mut ths = THS::new(Some(ThsOption { 
+    let ths = THS::new(Some(ThsOption { 
         username: String::new(),
         password: String::new(),
-        lib_ver: "116".parse().unwrap() 
+        lib_ver: "116".parse().unwrap(),
+        ..Default::default()
     })).expect("Failed to create THS instance");
 
     // 连接到服务器
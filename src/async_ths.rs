@@ -0,0 +1,503 @@
+//! 基于 tokio 的异步客户端，需要开启 `async` feature
+//!
+//! 所有动态库调用都通过 `spawn_blocking` 放到阻塞线程池中执行，不会占用异步运行时的工作线程。
+//! 重试由异步代码驱动：每次尝试只在阻塞线程中执行一遍，需要等待时返回，等待使用 `tokio::time::sleep`，
+//! 不占用阻塞线程。
+//!
+//! 取消：丢弃返回的 future（例如配合 `tokio::time::timeout` 或 `select!`）即可取消等待，
+//! 已经进入动态库的单次尝试会在阻塞线程中执行完毕，其结果被丢弃；之后不会再重试。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tracing::warn;
+
+use crate::batch::{BatchOptions, BatchQuotes};
+use crate::code::{IntoSecurityCode, SecurityCode};
+use crate::error::THSError;
+use crate::factor::AdjustFactors;
+use crate::kline::RangeOptions;
+use crate::query::QueryRequest;
+use crate::subscription::{DataClass, PushUpdate};
+use crate::ths::{Adjust, Interval, Response, THS};
+use crate::retry;
+use crate::timeout::{self, Deadline};
+use crate::types::{KLineData, Quote};
+
+/// 异步客户端，克隆后共享同一个 `THS` 实例
+#[derive(Debug, Clone)]
+pub struct AsyncThs {
+    inner: Arc<THS>,
+}
+
+macro_rules! block_lists {
+    ($($name:ident),* $(,)?) => {
+        $(
+            pub async fn $name(&self) -> Result<Response, THSError> {
+                self.run(|ths| ths.$name()).await
+            }
+        )*
+    };
+}
+
+impl AsyncThs {
+    pub fn new(ths: THS) -> Self {
+        Self { inner: Arc::new(ths) }
+    }
+
+    /// 与同步代码共享的底层客户端
+    pub fn blocking(&self) -> &Arc<THS> {
+        &self.inner
+    }
+
+    /// 在阻塞线程池中执行 `f`，失败时按重试策略等待后重新执行
+    ///
+    /// 重试会重新执行整个 `f`，全部尝试共用 `ThsOption::timeouts.query`。
+    pub async fn run<R, F>(&self, f: F) -> Result<R, THSError>
+    where
+        R: Send + 'static,
+        F: Fn(&THS) -> Result<R, THSError> + Send + Sync + 'static,
+    {
+        self.run_until(self.inner.query_deadline(), f).await
+    }
+
+    /// 在阻塞线程池中执行一次 `f`，不重试
+    pub async fn run_once<R, F>(&self, f: F) -> Result<R, THSError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&THS) -> Result<R, THSError> + Send + 'static,
+    {
+        self.attempt(None, f).await
+    }
+
+    async fn run_until<R, F>(&self, deadline: Option<Deadline>, f: F) -> Result<R, THSError>
+    where
+        R: Send + 'static,
+        F: Fn(&THS) -> Result<R, THSError> + Send + Sync + 'static,
+    {
+        let policy = self.inner.retry_policy().clone();
+        let f = Arc::new(f);
+        let mut attempt = 0;
+        loop {
+            let f = Arc::clone(&f);
+            let err = match self.attempt(deadline, move |ths| f(ths)).await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            attempt += 1;
+            if attempt >= policy.max_attempts() || !self.inner.backs_off(&err) || deadline.is_some_and(|d| d.expired()) {
+                return Err(err);
+            }
+            let delay = THS::backoff(policy.delay(attempt - 1), deadline);
+            warn!(attempt, error = %err, delay = ?delay, "调用失败，等待后重试");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// 在阻塞线程中执行一遍 `f`，需要等待才能重试的错误直接返回
+    async fn attempt<R, F>(&self, deadline: Option<Deadline>, f: F) -> Result<R, THSError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&THS) -> Result<R, THSError> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || {
+            retry::defer_backoff(|| match deadline {
+                Some(deadline) => timeout::with_scoped(deadline, || f(&inner)),
+                None => f(&inner),
+            })
+        })
+        .await
+        .map_err(|e| THSError::ApiError(format!("阻塞任务执行失败: {}", e)))?
+    }
+
+    /// 连接服务器，按重试策略重试，仍然失败时换用更旧的动态库再次连接
+    ///
+    /// 整个过程不超过 `ThsOption::timeouts.connect`。
+    pub async fn connect(&self) -> Result<Response, THSError> {
+        let deadline = self.inner.connect_deadline();
+        loop {
+            let e = match self.connect_with_retry(deadline).await {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+            if deadline.is_some_and(|d| d.expired()) {
+                return Err(e);
+            }
+            let (fell_back, e) = self.run_once(move |ths| Ok((ths.fall_back(&e), e))).await?;
+            if !fell_back {
+                return Err(e);
+            }
+        }
+    }
+
+    async fn connect_with_retry(&self, deadline: Option<Deadline>) -> Result<Response, THSError> {
+        let policy = self.inner.retry_policy().clone();
+        let mut attempt = 0;
+        loop {
+            match self.run_once(move |ths| ths.connect_once(deadline)).await {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    warn!(method = "connect", attempt = attempt + 1, error = %e, "连接尝试失败");
+                    attempt += 1;
+                    if attempt >= policy.max_attempts() || !policy.is_retryable(&e) || deadline.is_some_and(|d| d.expired()) {
+                        return Err(e);
+                    }
+                }
+            }
+            tokio::time::sleep(THS::backoff(policy.delay(attempt - 1), deadline)).await;
+        }
+    }
+
+    /// 在 `timeout` 内执行 `f`，包括重试的等待，见 [`THS::with_timeout`]
+    pub async fn with_timeout<R, F>(&self, timeout: Duration, f: F) -> Result<R, THSError>
+    where
+        R: Send + 'static,
+        F: Fn(&THS) -> Result<R, THSError> + Send + Sync + 'static,
+    {
+        self.run_until(Some(Deadline::after(timeout)), f).await
+    }
+
+    pub async fn disconnect(&self) -> Result<(), THSError> {
+        self.run_once(|ths| ths.disconnect()).await
+    }
+
+    pub async fn help(&self, req: &str) -> Result<String, THSError> {
+        let req = req.to_string();
+        self.run(move |ths| ths.help(&req)).await
+    }
+
+    /// 订阅推送数据，返回异步接收端，同样需要设置 [`ThsOption::experimental_push`](crate::ths::ThsOption::experimental_push)
+    pub async fn subscribe(&self, data_class: DataClass, codes: &[&str]) -> Result<UnboundedReceiver<PushUpdate>, THSError> {
+        let codes = owned(codes);
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.run_once(move |ths| {
+            ths.subscribe_with(data_class, &borrowed(&codes), move |update| {
+                let _ = tx.send(update);
+            })
+        }).await?;
+        Ok(rx)
+    }
+
+    pub async fn unsubscribe(&self, data_class: DataClass, codes: &[&str]) -> Result<(), THSError> {
+        let codes = owned(codes);
+        self.run(move |ths| ths.unsubscribe(data_class, &borrowed(&codes))).await
+    }
+
+    pub async fn klines(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        adjust: Adjust,
+        interval: Interval,
+        count: i32,
+    ) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.klines(&code, start_time, end_time, adjust, interval, count)).await
+    }
+
+    pub async fn klines_typed(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        adjust: Adjust,
+        interval: Interval,
+        count: i32,
+    ) -> Result<Vec<KLineData>, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.klines_typed(&code, start_time, end_time, adjust, interval, count)).await
+    }
+
+    pub async fn klines_range(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        adjust: Adjust,
+        interval: Interval,
+    ) -> Result<Vec<KLineData>, THSError> {
+        self.klines_range_with(ths_code, start_time, end_time, adjust, interval, &RangeOptions::default()).await
+    }
+
+    pub async fn klines_range_with(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        adjust: Adjust,
+        interval: Interval,
+        options: &RangeOptions,
+    ) -> Result<Vec<KLineData>, THSError> {
+        let (code, options) = (ths_code.into_security_code()?, options.clone());
+        self.run(move |ths| ths.klines_range_with(&code, start_time, end_time, adjust, interval, &options)).await
+    }
+
+    pub async fn adjust_factors(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+    ) -> Result<AdjustFactors, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.adjust_factors(&code, start_time, end_time)).await
+    }
+
+    pub async fn stock_market_data(&self, ths_code: &str) -> Result<Response, THSError> {
+        let ths_code = ths_code.to_string();
+        self.run(move |ths| ths.stock_market_data(&ths_code)).await
+    }
+
+    pub async fn stock_market_data_with<T: Copy + Into<i32>>(&self, ths_code: &str, data_types: &[T]) -> Result<Response, THSError> {
+        let (ths_code, data_types) = (ths_code.to_string(), data_types.iter().map(|&t| t.into()).collect::<Vec<i32>>());
+        self.run(move |ths| ths.stock_market_data_with(&ths_code, &data_types)).await
+    }
+
+    pub async fn quotes(&self, ths_code: &str, data_types: Option<&[i32]>) -> Result<Vec<Quote>, THSError> {
+        let (ths_code, data_types) = (ths_code.to_string(), data_types.map(<[i32]>::to_vec));
+        self.run(move |ths| ths.quotes(&ths_code, data_types.as_deref())).await
+    }
+
+    /// 批量获取行情快照，见 [`THS::batch_quotes_with`]
+    pub async fn batch_quotes<I>(&self, codes: I) -> Result<BatchQuotes, THSError>
+    where I: IntoIterator, I::Item: IntoSecurityCode {
+        self.batch_quotes_with(codes, &BatchOptions::default()).await
+    }
+
+    /// 批量获取行情快照，每轮只重新请求上一轮因可重试的错误而失败的代码
+    pub async fn batch_quotes_with<I>(&self, codes: I, options: &BatchOptions) -> Result<BatchQuotes, THSError>
+    where I: IntoIterator, I::Item: IntoSecurityCode {
+        let mut results = Vec::new();
+        let mut pending: Vec<(usize, SecurityCode)> = Vec::new();
+        for (i, code) in codes.into_iter().enumerate() {
+            match code.into_security_code() {
+                Ok(code) => {
+                    results.push(None);
+                    pending.push((i, code));
+                }
+                Err(e) => results.push(Some(Err(Arc::new(e)))),
+            }
+        }
+
+        let policy = self.inner.retry_policy().clone();
+        let deadline = self.inner.query_deadline();
+        let mut attempt = 0;
+        while !pending.is_empty() {
+            let (codes, options) = (pending.iter().map(|(_, code)| code.clone()).collect::<Vec<_>>(), options.clone());
+            let batch = self.attempt(deadline, move |ths| Ok(ths.batch_quotes_with(codes, &options))).await?;
+
+            attempt += 1;
+            let retry = attempt < policy.max_attempts() && !deadline.is_some_and(|d| d.expired());
+            let mut failed = Vec::new();
+            for ((i, code), result) in pending.into_iter().zip(batch.results) {
+                match result {
+                    Err(e) if retry && self.inner.backs_off(&e) => {
+                        results[i] = Some(Err(e));
+                        failed.push((i, code));
+                    }
+                    result => results[i] = Some(result),
+                }
+            }
+            pending = failed;
+            if !pending.is_empty() {
+                let delay = THS::backoff(policy.delay(attempt - 1), deadline);
+                warn!(attempt, count = pending.len(), delay = ?delay, "批量行情部分失败，等待后重试");
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        let results = results.into_iter().map(|r| r.unwrap_or_else(|| Err(Arc::new(THSError::NoData("没有结果".into()))))).collect();
+        Ok(BatchQuotes { results })
+    }
+
+    pub async fn get_block_data(&self, block_id: i32) -> Result<Response, THSError> {
+        self.run(move |ths| ths.get_block_data(block_id)).await
+    }
+
+    pub async fn get_block_components(&self, link_code: &str) -> Result<Response, THSError> {
+        let link_code = link_code.to_string();
+        self.run(move |ths| ths.get_block_components(&link_code)).await
+    }
+
+    pub async fn block_market_data(&self, block_code: &str) -> Result<Response, THSError> {
+        let block_code = block_code.to_string();
+        self.run(move |ths| ths.block_market_data(&block_code)).await
+    }
+
+    block_lists!(
+        query_ths_industry,
+        query_ths_concept,
+        query_ths_index,
+        stock_zh_lists,
+        stock_us_lists,
+        stock_hk_lists,
+        stock_zh_b_lists,
+        cbond_lists,
+        fund_etf_lists,
+        fund_etf_t0_lists,
+        ipo_today,
+        ipo_wait,
+    );
+
+    pub async fn get_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.get_transaction_data(&code, start, end)).await
+    }
+
+    pub async fn get_super_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.get_super_transaction_data(&code, start, end)).await
+    }
+
+    pub async fn get_l2_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.get_l2_transaction_data(&code, start, end)).await
+    }
+
+    pub async fn wencai_base(&self, condition: &str) -> Result<Response, THSError> {
+        let condition = condition.to_string();
+        self.run(move |ths| ths.wencai_base(&condition)).await
+    }
+
+    pub async fn wencai_nlp(&self, condition: &str) -> Result<Response, THSError> {
+        let condition = condition.to_string();
+        self.run(move |ths| ths.wencai_nlp(&condition)).await
+    }
+
+    pub async fn order_book_ask(&self, ths_code: impl IntoSecurityCode) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.order_book_ask(&code)).await
+    }
+
+    pub async fn order_book_bid(&self, ths_code: impl IntoSecurityCode) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+        self.run(move |ths| ths.order_book_bid(&code)).await
+    }
+
+    pub async fn query_raw(&self, req: &QueryRequest) -> Result<Response, THSError> {
+        let req = req.clone();
+        self.run(move |ths| ths.query_raw(&req)).await
+    }
+
+    pub async fn history_minute_time_data(&self, ths_code: impl IntoSecurityCode, date: &str, fields: Option<Vec<&str>>) -> Result<Response, THSError> {
+        let (code, date) = (ths_code.into_security_code()?, date.to_string());
+        let fields = fields.map(|f| owned(&f));
+        self.run(move |ths| ths.history_minute_time_data(&code, &date, fields.as_deref().map(borrowed))).await
+    }
+}
+
+impl From<THS> for AsyncThs {
+    fn from(ths: THS) -> Self {
+        Self::new(ths)
+    }
+}
+
+impl From<Arc<THS>> for AsyncThs {
+    fn from(inner: Arc<THS>) -> Self {
+        Self { inner }
+    }
+}
+
+fn owned(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn borrowed(items: &[String]) -> Vec<&str> {
+    items.iter().map(String::as_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::retry::RetryPolicy;
+    use crate::ths::ThsOption;
+    use crate::timeout::TimeoutPolicy;
+    use crate::transport::{CallStatus, MockTransport};
+
+    const OK: &str = r#"{"err_info":"","payload":{"result":"ok"}}"#;
+    const BUSY: &str = r#"{"err_info":"服务器繁忙","payload":{}}"#;
+
+    fn client(mock: &MockTransport, delay: Duration) -> AsyncThs {
+        let ops = ThsOption {
+            zip_version: Some(2),
+            timeouts: TimeoutPolicy::none(),
+            retry: RetryPolicy {
+                max_attempts: 10,
+                base_delay: delay,
+                max_delay: delay,
+                retry_err_info: vec!["繁忙".into()],
+                ..RetryPolicy::default()
+            },
+            ..ThsOption::default()
+        };
+        AsyncThs::new(THS::with_transport(Some(ops), mock.clone().on_ok("connect", OK)))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_sleep_on_the_runtime() {
+        let mock = MockTransport::new().on_ok("help", BUSY).on_ok("help", BUSY).on_ok("help", OK);
+        let ths = client(&mock, Duration::from_secs(60));
+        ths.connect().await.unwrap();
+
+        // 两次 60 秒的等待由暂停的时钟推进，阻塞线程中等待会真的花 2 分钟
+        let started = Instant::now();
+        assert_eq!(ths.help("x").await.unwrap(), "ok");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(mock.calls_to("help").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_future_stops_retrying() {
+        let mock = MockTransport::new().on_ok("help", BUSY);
+        let ths = client(&mock, Duration::from_millis(200));
+        ths.connect().await.unwrap();
+
+        assert!(tokio::time::timeout(Duration::from_millis(300), ths.help("x")).await.is_err());
+        let calls = mock.calls_to("help").len();
+        assert_eq!(calls, 2);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(mock.calls_to("help").len(), calls);
+    }
+
+    #[tokio::test]
+    async fn run_once_does_not_retry() {
+        let mock = MockTransport::new().on_ok("help", BUSY);
+        let ths = client(&mock, Duration::from_millis(10));
+        ths.connect().await.unwrap();
+
+        let err = ths.run_once(|ths| ths.help("x")).await.unwrap_err();
+        assert!(matches!(err, THSError::Server { .. }));
+        assert_eq!(mock.calls_to("help").len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batch_retries_only_failed_codes() {
+        // 第一个请求（USHA）失败一次，USZA 一直成功
+        let busy = Arc::new(AtomicUsize::new(1));
+        let mock = MockTransport::new().with_handler(move |method, params, _| {
+            if !method.starts_with("cmd.query_data.") {
+                return CallStatus::Ok(OK.into());
+            }
+            let params = params.unwrap();
+            if params.contains("market=USHA") && busy.fetch_sub(1, Ordering::SeqCst) > 0 {
+                return CallStatus::Ok(BUSY.into());
+            }
+            let code = if params.contains("market=USHA") { "600000" } else { "000001" };
+            CallStatus::Ok(format!(r#"{{"err_info":"","payload":{{"result":[{{"代码":"{}","价格":1.0}}]}}}}"#, code))
+        });
+        let ths = client(&mock, Duration::from_secs(60));
+        ths.connect().await.unwrap();
+
+        let batch = ths.batch_quotes(["USHA600000", "USZA000001"]).await.unwrap();
+        assert!(batch.is_complete(), "{:?}", batch);
+        let requests: Vec<_> = mock.calls().into_iter().filter(|c| c.method.starts_with("cmd.query_data.")).collect();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].params.as_deref().unwrap().contains("market=USHA"));
+    }
+}
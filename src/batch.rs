@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::STOCK_QUOTE_DATA_TYPES;
+use crate::error::THSError;
+use crate::types::Quote;
+
+/// 批量行情查询的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchOptions {
+    /// 单次请求的最大代码数量
+    pub chunk_size: usize,
+    /// 请求的 datatype 列表，含义见 [`Field`](crate::fields::Field)，可以用 [`fields::ids`](crate::fields::ids) 转换
+    pub data_types: Vec<i32>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 200,
+            data_types: STOCK_QUOTE_DATA_TYPES.to_vec(),
+        }
+    }
+}
+
+/// 批量行情查询的结果，顺序与传入的代码一致
+///
+/// 单个代码的失败不影响其他代码：无法解析的代码、所在请求失败的代码和服务器没有返回数据的代码
+/// 都以 `Err` 的形式放在对应位置上。同一个请求中的代码共享同一个错误，因此错误用 `Arc` 包装。
+#[derive(Debug, Default)]
+pub struct BatchQuotes {
+    pub results: Vec<Result<Quote, Arc<THSError>>>,
+}
+
+impl BatchQuotes {
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// 成功获取的行情
+    pub fn quotes(&self) -> impl Iterator<Item = &Quote> {
+        self.results.iter().filter_map(|r| r.as_ref().ok())
+    }
+
+    /// 失败的代码位置和错误
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &THSError)> {
+        self.results.iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.as_ref().err().map(|e| (i, e.as_ref())))
+    }
+
+    /// 是否全部成功
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+
+    /// 丢弃失败的代码，返回成功获取的行情
+    pub fn into_quotes(self) -> Vec<Quote> {
+        self.results.into_iter().filter_map(Result::ok).collect()
+    }
+}
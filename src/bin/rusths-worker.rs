@@ -0,0 +1,23 @@
+//! 进程外调用的子进程，用法: `rusths-worker <动态库路径> [版本号]`
+//!
+//! 由 `WorkerTransport` 启动，通过标准输入输出与父进程通信，见 `rusths::worker`。
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1);
+    let Some(lib_path) = args.next().map(PathBuf::from) else {
+        eprintln!("用法: rusths-worker <动态库路径> [版本号]");
+        return ExitCode::from(2);
+    };
+    let version = args.next().map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+
+    match rusths::worker::serve(&lib_path, &version) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rusths-worker: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,423 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    MARKETS, MARKET_USHA, MARKET_USHB, MARKET_USHD, MARKET_USHI, MARKET_USHJ, MARKET_USHP, MARKET_USHT,
+    MARKET_USOO, MARKET_USTM, MARKET_USZA, MARKET_USZB, MARKET_USZD, MARKET_USZI, MARKET_USZJ, MARKET_USZP,
+    MARKET_UZOO,
+};
+use crate::error::THSError;
+
+/// 交易所，用于从 "600000.SH"、"sh600000" 这类写法推断市场
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exchange {
+    Shanghai,
+    Shenzhen,
+    Beijing,
+}
+
+impl Exchange {
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag.to_ascii_uppercase().as_str() {
+            "SH" | "SS" | "SSE" => Some(Exchange::Shanghai),
+            "SZ" | "SZSE" => Some(Exchange::Shenzhen),
+            "BJ" | "BSE" => Some(Exchange::Beijing),
+            _ => None,
+        }
+    }
+
+    /// 在交易所内按代码前缀推断市场
+    fn market_of(self, code: &str) -> Option<&'static str> {
+        let market = match self {
+            Exchange::Shanghai => match &code[..2] {
+                "60" | "68" => MARKET_USHA,
+                "50" | "51" | "56" | "58" => MARKET_USHJ,
+                "11" => MARKET_USHD,
+                "90" => MARKET_USHB,
+                "00" => MARKET_USHI,
+                _ => return None,
+            },
+            Exchange::Shenzhen => match &code[..2] {
+                "00" | "30" => MARKET_USZA,
+                "15" | "16" => MARKET_USZJ,
+                "12" => MARKET_USZD,
+                "20" => MARKET_USZB,
+                "39" => MARKET_USZI,
+                _ => return None,
+            },
+            Exchange::Beijing => match &code[..1] {
+                "4" | "8" | "9" => MARKET_USTM,
+                _ => return None,
+            },
+        };
+        Some(market)
+    }
+}
+
+/// 证券类型，由市场代码决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityType {
+    /// A 股、B 股，包括风险警示板和北交所
+    Stock,
+    Index,
+    Fund,
+    Bond,
+    Option,
+    /// 退市整理板
+    Delisting,
+}
+
+impl SecurityType {
+    /// 市场代码对应的证券类型，未知的市场返回 `None`
+    pub fn of_market(market: &str) -> Option<Self> {
+        let kind = match market {
+            MARKET_USHA | MARKET_USZA | MARKET_USHB | MARKET_USZB | MARKET_USHT | MARKET_USTM => SecurityType::Stock,
+            MARKET_USHI | MARKET_USZI => SecurityType::Index,
+            MARKET_USHJ | MARKET_USZJ => SecurityType::Fund,
+            MARKET_USHD | MARKET_USZD => SecurityType::Bond,
+            MARKET_USOO | MARKET_UZOO => SecurityType::Option,
+            MARKET_USHP | MARKET_USZP => SecurityType::Delisting,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+impl fmt::Display for SecurityType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SecurityType::Stock => "股票",
+            SecurityType::Index => "指数",
+            SecurityType::Fund => "基金",
+            SecurityType::Bond => "债券",
+            SecurityType::Option => "期权",
+            SecurityType::Delisting => "退市整理",
+        })
+    }
+}
+
+/// 证券代码，由同花顺市场代码（如 "USHA"）和 6 位代码组成
+///
+/// 支持以下写法：
+/// - 同花顺格式 "USHA600000"
+/// - 后缀格式 "600000.SH"、"000001.SZ"、"830799.BJ"
+/// - 前缀格式 "sh600000"、"sz000001"、"bj830799"
+/// - 纯数字 "600000"，按代码前缀推断市场：60/68 沪A，00/30 深A，8x/4x/92 北交所，51/15 基金，11/12 债券
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SecurityCode {
+    market: String,
+    code: String,
+}
+
+impl SecurityCode {
+    /// 由市场代码和 6 位代码构造
+    pub fn new(market: &str, code: &str) -> Result<Self, THSError> {
+        let market = market.to_ascii_uppercase();
+        if !MARKETS.contains(&market.as_str()) {
+            return Err(THSError::InvalidCode(format!("未知的市场代码: {}", market)));
+        }
+        if code.len() != 6 || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(THSError::InvalidCode(format!("证券代码必须为6位: {}", code)));
+        }
+        Ok(Self { market, code: code.to_ascii_uppercase() })
+    }
+
+    /// 解析各种写法的证券代码
+    pub fn parse(s: &str) -> Result<Self, THSError> {
+        let s = s.trim();
+        let invalid = || THSError::InvalidCode(format!("无法识别的证券代码: {}", s));
+
+        // USHA600000
+        if s.len() == 10 && s.is_ascii() {
+            let market = s[..4].to_ascii_uppercase();
+            if MARKETS.contains(&market.as_str()) {
+                return Self::new(&market, &s[4..]);
+            }
+        }
+
+        // 600000.SH
+        if let Some((code, tag)) = s.split_once('.') {
+            let exchange = Exchange::from_tag(tag).ok_or_else(invalid)?;
+            return Self::infer_in(exchange, code).ok_or_else(invalid);
+        }
+
+        // sh600000
+        if s.len() == 8 && s.is_ascii()
+            && let Some(exchange) = Exchange::from_tag(&s[..2]) {
+            return Self::infer_in(exchange, &s[2..]).ok_or_else(invalid);
+        }
+
+        // 600000
+        let market = Self::infer_market(s).ok_or_else(invalid)?;
+        Self::new(market, s)
+    }
+
+    /// 按纯数字代码的前缀推断市场，无法推断时返回 `None`
+    pub fn infer_market(code: &str) -> Option<&'static str> {
+        if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let market = match &code[..2] {
+            "60" | "68" => MARKET_USHA,
+            "00" | "30" => MARKET_USZA,
+            "50" | "51" | "56" | "58" => MARKET_USHJ,
+            "15" | "16" => MARKET_USZJ,
+            "11" => MARKET_USHD,
+            "12" => MARKET_USZD,
+            "90" => MARKET_USHB,
+            "20" => MARKET_USZB,
+            "92" => MARKET_USTM,
+            p if p.starts_with('8') || p.starts_with('4') => MARKET_USTM,
+            _ => return None,
+        };
+        Some(market)
+    }
+
+    fn infer_in(exchange: Exchange, code: &str) -> Option<Self> {
+        if code.len() != 6 || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let market = exchange.market_of(code)?;
+        Self::new(market, code).ok()
+    }
+
+    /// 同花顺市场代码，如 "USHA"
+    pub fn market(&self) -> &str {
+        &self.market
+    }
+
+    /// 6 位代码，如 "600000"
+    pub fn short_code(&self) -> &str {
+        &self.code
+    }
+
+    /// 同花顺格式的完整代码，如 "USHA600000"
+    pub fn ths_code(&self) -> String {
+        format!("{}{}", self.market, self.code)
+    }
+
+    /// 返回数据中的代码是否表示该证券
+    ///
+    /// 带市场前缀时比较市场和代码，只有代码时只比较代码，此时调用方需保证请求中只有一个市场。
+    pub fn matches(&self, returned: &str) -> bool {
+        let returned = returned.trim();
+        returned == self.code || returned.eq_ignore_ascii_case(&self.ths_code())
+    }
+
+    /// 证券类型
+    pub fn security_type(&self) -> SecurityType {
+        // `new` 保证了市场代码是已知的
+        SecurityType::of_market(&self.market).unwrap_or(SecurityType::Stock)
+    }
+
+    /// 是否为北交所证券
+    pub fn is_beijing(&self) -> bool {
+        self.market == MARKET_USTM
+    }
+
+    /// 涨跌幅限制比例，没有涨跌幅限制（指数、债券等）时返回 `None`
+    ///
+    /// 北交所 30%，科创板和创业板 20%，风险警示板 5%，其余 A 股、B 股和基金 10%。
+    /// 只按市场和代码判断，无法识别 ST 等特殊情况，以服务器返回的涨跌停价为准。
+    pub fn price_limit_ratio(&self) -> Option<f64> {
+        let ratio = match self.market.as_str() {
+            MARKET_USTM => 0.30,
+            MARKET_USHT => 0.05,
+            MARKET_USHA if self.code.starts_with("688") => 0.20,
+            MARKET_USZA if self.code.starts_with("30") => 0.20,
+            MARKET_USHA | MARKET_USZA | MARKET_USHB | MARKET_USZB | MARKET_USHJ | MARKET_USZJ => 0.10,
+            _ => return None,
+        };
+        Some(ratio)
+    }
+
+    /// 按昨收价计算涨停价和跌停价，四舍五入到分
+    pub fn price_limits(&self, pre_close: f64) -> Option<(f64, f64)> {
+        let ratio = self.price_limit_ratio()?;
+        let round = |v: f64| (v * 100.0).round() / 100.0;
+        Some((round(pre_close * (1.0 + ratio)), round(pre_close * (1.0 - ratio))))
+    }
+}
+
+impl fmt::Display for SecurityCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.market, self.code)
+    }
+}
+
+impl FromStr for SecurityCode {
+    type Err = THSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for SecurityCode {
+    type Error = THSError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for SecurityCode {
+    type Error = THSError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s)
+    }
+}
+
+impl TryFrom<&String> for SecurityCode {
+    type Error = THSError;
+
+    fn try_from(s: &String) -> Result<Self, Self::Error> {
+        Self::parse(s)
+    }
+}
+
+impl From<&SecurityCode> for SecurityCode {
+    fn from(code: &SecurityCode) -> Self {
+        code.clone()
+    }
+}
+
+impl From<SecurityCode> for String {
+    fn from(code: SecurityCode) -> Self {
+        code.ths_code()
+    }
+}
+
+/// 可以转换为 [`SecurityCode`] 的类型，查询接口的证券代码参数都接受该 trait
+///
+/// `&str`、`String` 按 [`SecurityCode::parse`] 解析，`SecurityCode` 原样使用。
+pub trait IntoSecurityCode {
+    fn into_security_code(self) -> Result<SecurityCode, THSError>;
+}
+
+impl<T> IntoSecurityCode for T
+where
+    T: TryInto<SecurityCode>,
+    THSError: From<T::Error>,
+{
+    fn into_security_code(self) -> Result<SecurityCode, THSError> {
+        Ok(self.try_into()?)
+    }
+}
+
+impl IntoSecurityCode for Result<SecurityCode, THSError> {
+    fn into_security_code(self) -> Result<SecurityCode, THSError> {
+        self
+    }
+}
+
+/// 解析逗号分隔的多个代码
+pub(crate) fn parse_list(codes: &str) -> Result<Vec<SecurityCode>, THSError> {
+    codes.split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(SecurityCode::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        let cases = [
+            ("USHA600000", "USHA600000"),
+            ("usha600000", "USHA600000"),
+            (" USZA000001 ", "USZA000001"),
+            ("USHI1A0001", "USHI1A0001"),
+            ("600000.SH", "USHA600000"),
+            ("600000.ss", "USHA600000"),
+            ("000001.SZ", "USZA000001"),
+            ("000001.SH", "USHI000001"),
+            ("399001.SZ", "USZI399001"),
+            ("510300.SH", "USHJ510300"),
+            ("159915.SZ", "USZJ159915"),
+            ("113050.SH", "USHD113050"),
+            ("123001.SZ", "USZD123001"),
+            ("900901.SH", "USHB900901"),
+            ("200002.SZ", "USZB200002"),
+            ("830799.BJ", "USTM830799"),
+            ("sh600000", "USHA600000"),
+            ("SZ300750", "USZA300750"),
+            ("bj430047", "USTM430047"),
+            ("600000", "USHA600000"),
+            ("688981", "USHA688981"),
+            ("000001", "USZA000001"),
+            ("300750", "USZA300750"),
+            ("830799", "USTM830799"),
+            ("430047", "USTM430047"),
+            ("920001", "USTM920001"),
+            ("510300", "USHJ510300"),
+            ("159915", "USZJ159915"),
+            ("113050", "USHD113050"),
+            ("123001", "USZD123001"),
+        ];
+        for (input, expected) in cases {
+            let code = SecurityCode::parse(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+            assert_eq!(code.ths_code(), expected, "{}", input);
+            assert_eq!(code.market(), &expected[..4], "{}", input);
+            assert_eq!(code.short_code(), &expected[4..], "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid() {
+        let cases = ["", "60000", "6000000", "700000", "600000.HK", "600000.", "hk600000", "USXX600000", "60000a.SH", "300750.SH", "sh60000"];
+        for input in cases {
+            assert!(SecurityCode::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn infer_market_by_prefix() {
+        let cases = [
+            ("600000", Some(MARKET_USHA)),
+            ("688001", Some(MARKET_USHA)),
+            ("000001", Some(MARKET_USZA)),
+            ("300001", Some(MARKET_USZA)),
+            ("830001", Some(MARKET_USTM)),
+            ("870001", Some(MARKET_USTM)),
+            ("430001", Some(MARKET_USTM)),
+            ("920001", Some(MARKET_USTM)),
+            ("510001", Some(MARKET_USHJ)),
+            ("150001", Some(MARKET_USZJ)),
+            ("110001", Some(MARKET_USHD)),
+            ("120001", Some(MARKET_USZD)),
+            ("700001", None),
+            ("1A0001", None),
+            ("60000", None),
+        ];
+        for (code, market) in cases {
+            assert_eq!(SecurityCode::infer_market(code), market, "{}", code);
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let code: SecurityCode = "600000.SH".parse().unwrap();
+        assert_eq!(code.to_string(), "USHA600000");
+        assert_eq!(String::from(code.clone()), "USHA600000");
+        assert_eq!((&code).into_security_code().unwrap(), code);
+        assert_eq!("sh600000".into_security_code().unwrap(), code);
+        assert_eq!(serde_json::to_string(&code).unwrap(), "\"USHA600000\"");
+        assert_eq!(serde_json::from_str::<SecurityCode>("\"600000\"").unwrap(), code);
+        assert_eq!(code.security_type(), SecurityType::Stock);
+
+        assert!(code.matches("USHA600000") && code.matches("600000") && code.matches("usha600000"));
+        assert!(!code.matches("USZA600000") && !code.matches("1600000") && !code.matches("00000"));
+
+        let codes = parse_list("600000, 000001.SZ,,USHI1A0001").unwrap();
+        assert_eq!(codes.iter().map(|c| c.ths_code()).collect::<Vec<_>>(), ["USHA600000", "USZA000001", "USHI1A0001"]);
+    }
+}
@@ -25,421 +25,38 @@ pub const MARKET_USZD: &str = "USZD"; // Shenzhen Bonds
 pub const MARKET_USZJ: &str = "USZJ"; // Shenzhen Funds
 pub const MARKET_USZP: &str = "USZP"; // Shenzhen Delisting
 pub const MARKET_USTM: &str = "USTM"; // Beijing Exchange
+pub const MARKET_USOO: &str = "USOO"; // Shanghai Options
+pub const MARKET_UZOO: &str = "UZOO"; // Shenzhen Options
 
 // Market code lists
-pub const MARKETS: [&str; 15] = [
+pub const MARKETS: [&str; 16] = [
     "USHI", "USHA", "USHB", "USHD", "USHJ", "USHP", "USHT",
-    "USZI", "USZA", "USZB", "USZD", "USZJ", "USZP", "USOO", "UZOO"
+    "USZI", "USZA", "USZB", "USZD", "USZJ", "USZP", "USTM", "USOO", "UZOO"
 ];
 
 pub const BLOCK_MARKETS: [&str; 1] = ["URFI"];
 
-// Field name mappings
+// Environment variable naming the directory that holds the hq library
+pub const LIB_DIR_ENV: &str = "RUSTHS_LIB_DIR";
+
+// zipversion sent with cmd.query_data requests unless configured otherwise
+pub const DEFAULT_ZIP_VERSION: i32 = 2;
+
+// zipversion values tried in order when negotiating after connect, newest first
+pub const ZIP_VERSIONS: [i32; 3] = [2, 1, 0];
+
+// Default datatype ids requested by stock_market_data
+pub const STOCK_QUOTE_DATA_TYPES: [i32; 31] = [
+    5, 6, 7, 8, 9, 10, 12, 13, 402, 19, 407, 24, 30, 48, 49, 69, 70, 3250, 920371, 55,
+    199112, 264648, 1968584, 461256, 1771976, 3475914, 3541450, 526792, 3153, 592888, 592890,
+];
+
+// Field name mappings, kept for compatibility; see `fields::Field` for lookup by name, alias and category
 use std::collections::HashMap;
 use lazy_static::lazy_static;
 
+use crate::fields::Field;
+
 lazy_static! {
-    pub static ref FIELD_NAME_MAP: HashMap<i32, &'static str> = {
-        let mut map = HashMap::new();
-        map.insert(1, "时间");
-        map.insert(5, "代码");
-        map.insert(6, "昨收价");
-        map.insert(7, "开盘价");
-        map.insert(8, "最高价");
-        map.insert(9, "最低价");
-        map.insert(10, "价格");
-        map.insert(11, "收盘价");
-        map.insert(12, "成交方向");
-        map.insert(13, "成交量");
-        map.insert(14, "外盘成交量");
-        map.insert(15, "内盘成交量");
-        map.insert(16, "对倒成交量");
-        map.insert(17, "开盘成交量");
-        map.insert(18, "交易笔数");
-        map.insert(19, "总金额");
-        map.insert(20, "委托买入价");
-        map.insert(21, "委托卖出价");
-        map.insert(22, "委买总量");
-        map.insert(23, "委卖总量");
-        map.insert(24, "买1价");
-        map.insert(25, "买1量");
-        map.insert(26, "买2价");
-        map.insert(27, "买2量");
-        map.insert(28, "买3价");
-        map.insert(29, "买3量");
-        map.insert(30, "卖1价");
-        map.insert(31, "卖1量");
-        map.insert(32, "卖2价");
-        map.insert(33, "卖2量");
-        map.insert(34, "卖3价");
-        map.insert(35, "卖3量");
-        map.insert(36, "指数种类");
-        map.insert(37, "股票总数");
-        map.insert(38, "上涨家数");
-        map.insert(39, "下跌家数");
-        map.insert(40, "领先指标");
-        map.insert(41, "上涨趋势");
-        map.insert(42, "下跌趋势");
-        map.insert(43, "最近成交金额");
-        map.insert(44, "证券名称(繁体)");
-        map.insert(45, "五日成交总量");
-        map.insert(46, "证券名称(英文)");
-        map.insert(47, "证券名称(Unicode)");
-        map.insert(48, "涨速");
-        map.insert(49, "当前量(手)");
-        map.insert(50, "代码(港)");
-        map.insert(53, "委比");
-        map.insert(54, "均价(中金所)");
-        map.insert(55, "名称");
-        map.insert(56, "挂单时间");
-        map.insert(60, "H股昨收");
-        map.insert(61, "异动类型1");
-        map.insert(64, "状态");
-        map.insert(65, "持仓");
-        map.insert(66, "昨结");
-        map.insert(69, "涨停价");
-        map.insert(70, "跌停价");
-        map.insert(71, "现增仓");
-        map.insert(72, "今结");
-        map.insert(73, "昨持仓");
-        map.insert(74, "买单ID");
-        map.insert(75, "卖单ID");
-        map.insert(80, "利息");
-        map.insert(82, "撤单时间");
-        map.insert(84, "所属行业");
-        map.insert(85, "盈利情况");
-        map.insert(89, "转让状态参数");
-        map.insert(90, "板块流通市值");
-        map.insert(91, "市盈率");
-        map.insert(92, "板块总市值");
-        map.insert(93, "交易单位");
-        map.insert(95, "52周最高");
-        map.insert(96, "52周最低");
-        map.insert(100, "现价(港)");
-        map.insert(102, "买6价");
-        map.insert(103, "买6量");
-        map.insert(104, "卖6价");
-        map.insert(105, "卖6量");
-        map.insert(106, "买7价");
-        map.insert(107, "买7量");
-        map.insert(108, "卖7价");
-        map.insert(109, "卖7量");
-        map.insert(110, "买8价");
-        map.insert(111, "买8量");
-        map.insert(112, "卖8价");
-        map.insert(113, "卖8量");
-        map.insert(114, "买9价");
-        map.insert(115, "买9量");
-        map.insert(116, "卖9价");
-        map.insert(117, "卖9量");
-        map.insert(118, "买10价");
-        map.insert(119, "买10量");
-        map.insert(120, "卖10价");
-        map.insert(121, "卖10量");
-        map.insert(122, "加权平均买价");
-        map.insert(123, "总委买量");
-        map.insert(124, "加权平均卖价");
-        map.insert(125, "总委卖量");
-        map.insert(130, "总手(港)");
-        map.insert(141, "波动性中断参考价");
-        map.insert(142, "波动性中断虚拟匹配量");
-        map.insert(143, "合约代码");
-        map.insert(144, "标的证券代码");
-        map.insert(145, "标的证券名称");
-        map.insert(146, "标的证券类型");
-        map.insert(147, "期权类型");
-        map.insert(148, "认购认沽");
-        map.insert(149, "合约单位");
-        map.insert(150, "买4价");
-        map.insert(151, "买4量");
-        map.insert(152, "卖4价");
-        map.insert(153, "卖4量");
-        map.insert(154, "买5价");
-        map.insert(155, "买5量");
-        map.insert(156, "卖5价");
-        map.insert(157, "卖5量");
-        map.insert(160, "市场分层");
-        map.insert(191, "买差价");
-        map.insert(192, "卖差价");
-        map.insert(201, "主动买入特大单量");
-        map.insert(202, "主动卖出特大单量");
-        map.insert(203, "主动买入大单量");
-        map.insert(204, "主动卖出大单量");
-        map.insert(205, "主动买入中单量");
-        map.insert(206, "主动卖出中单量");
-        map.insert(207, "被动买入特大单量");
-        map.insert(208, "被动卖出特大单量");
-        map.insert(209, "被动买入大单量");
-        map.insert(210, "被动卖出大单量");
-        map.insert(211, "被动买入中单量");
-        map.insert(212, "被动卖出中单量");
-        map.insert(213, "主动买入小单量");
-        map.insert(214, "主动卖出小单量");
-        map.insert(215, "主动买入特大单笔数");
-        map.insert(216, "主动卖出特大单笔数");
-        map.insert(217, "主动买入大单笔数");
-        map.insert(218, "主动卖出大单笔数");
-        map.insert(219, "被动买入特大单笔数");
-        map.insert(220, "被动卖出特大单笔数");
-        map.insert(221, "被动买入大单笔数");
-        map.insert(222, "被动卖出大单笔数");
-        map.insert(223, "主动买入特大单金额");
-        map.insert(224, "主动卖出特大单金额");
-        map.insert(225, "主动买入大单金额");
-        map.insert(226, "主动卖出大单金额");
-        map.insert(227, "被动买入特大单金额");
-        map.insert(228, "被动卖出特大单金额");
-        map.insert(229, "被动买入大单金额");
-        map.insert(230, "被动卖出大单金额");
-        map.insert(231, "买入单数量");
-        map.insert(232, "卖出单数量");
-        map.insert(233, "资金流入");
-        map.insert(234, "资金流出");
-        map.insert(235, "大单净量正");
-        map.insert(236, "大单净量负");
-        map.insert(237, "主动买入小单金额");
-        map.insert(238, "主动卖出小单金额");
-        map.insert(239, "成交笔数");
-        map.insert(240, "昨日收盘收益率");
-        map.insert(241, "昨日加权平均收益率");
-        map.insert(242, "开盘收益率");
-        map.insert(243, "最高收益率");
-        map.insert(244, "最低收益率");
-        map.insert(245, "最新收益率");
-        map.insert(246, "当日加权平均收益率");
-        map.insert(250, "委托买入前五档金额");
-        map.insert(251, "委托卖出前五档金额");
-        map.insert(252, "委托买入前十档金额");
-        map.insert(253, "委托卖出前十档金额");
-        map.insert(255, "主动买入中单笔数");
-        map.insert(256, "主动卖出中单笔数");
-        map.insert(257, "被动买入中单笔数");
-        map.insert(258, "被动卖出中单笔数");
-        map.insert(259, "主动买入中单金额");
-        map.insert(260, "主动卖出中单金额");
-        map.insert(261, "被动买入中单金额");
-        map.insert(262, "被动卖出中单金额");
-        map.insert(271, "52周最高日期");
-        map.insert(272, "52周最低日期");
-        map.insert(273, "年度最高日期");
-        map.insert(274, "年度最低日期");
-        map.insert(275, "领涨股");
-        map.insert(276, "涨停家数");
-        map.insert(277, "跌停家数");
-        map.insert(278, "盘后最新价");
-        map.insert(279, "港股指数人民币成交金额");
-        map.insert(280, "期权行权价");
-        map.insert(281, "首个交易日");
-        map.insert(282, "最后交易日1");
-        map.insert(283, "期权行权日");
-        map.insert(284, "期权到期日");
-        map.insert(285, "合约版本号");
-        map.insert(286, "行权交割日");
-        map.insert(288, "标的证券前收盘");
-        map.insert(289, "涨跌幅限制类型");
-        map.insert(290, "保证金比例参数1");
-        map.insert(291, "保证金比例参数2");
-        map.insert(292, "单位保证金");
-        map.insert(294, "整手数");
-        map.insert(295, "单笔限价申报下限");
-        map.insert(296, "单笔限价申报上限");
-        map.insert(297, "单笔市价申报下限");
-        map.insert(298, "单笔市价申报上限");
-        map.insert(299, "期权合约状态标签");
-        map.insert(402, "总股本");
-        map.insert(407, "流通股本");
-        map.insert(410, "流通B股");
-        map.insert(471, "权息资料");
-        map.insert(497, "转股价");
-        map.insert(499, "债券余额");
-        map.insert(520, "流动资产");
-        map.insert(543, "资产总计");
-        map.insert(593, "公积金");
-        map.insert(602, "主营收入");
-        map.insert(605, "营业利润");
-        map.insert(615, "利润总额");
-        map.insert(619, "净利润1");
-        map.insert(672, "申购限额(万股)");
-        map.insert(675, "实际涨幅");
-        map.insert(676, "首日振幅");
-        map.insert(873, "引伸波幅");
-        map.insert(874, "对冲值");
-        map.insert(875, "街货占比");
-        map.insert(876, "街货量");
-        map.insert(877, "最后交易日2");
-        map.insert(879, "回收价");
-        map.insert(880, "牛熊证种类");
-        map.insert(881, "标的证券");
-        map.insert(882, "权证类型");
-        map.insert(887, "行使价");
-        map.insert(888, "换股比率1");
-        map.insert(890, "到期日");
-        map.insert(899, "财务数据项");
-        map.insert(900, "流通股变动量");
-        map.insert(981, "港元人民币汇率");
-        map.insert(1002, "每股收益");
-        map.insert(1005, "每股净资产");
-        map.insert(1015, "净资产收益率");
-        map.insert(1024, "债券规模");
-        map.insert(1047, "转股起始日");
-        map.insert(1110, "星级");
-        map.insert(1121, "标记");
-        map.insert(1322, "利率");
-        map.insert(1384, "融资余额");
-        map.insert(1385, "融券余额");
-        map.insert(1386, "融资买入");
-        map.insert(1387, "融券卖出");
-        map.insert(1566, "净利润2");
-        map.insert(1606, "发行价");
-        map.insert(1612, "中签率");
-        map.insert(1670, "股东总数");
-        map.insert(1674, "流通A股");
-        map.insert(2026, "评级");
-        map.insert(2039, "纯债价值");
-        map.insert(2041, "期权价值");
-        map.insert(2570, "卖出信号");
-        map.insert(2579, "机构持股比例");
-        map.insert(2719, "人均持股数");
-        map.insert(2942, "市盈率(动态)1");
-        map.insert(2946, "市盈率(静态)");
-        map.insert(2947, "市净率1");
-        map.insert(3153, "市盈率TTM");
-        map.insert(3250, "5日涨幅");
-        map.insert(3251, "10日涨幅");
-        map.insert(3252, "20日涨幅");
-        map.insert(3397, "净值");
-        map.insert(9810, "溢价率");
-        map.insert(32772, "时间戳");
-        map.insert(68107, "板块主力净量1");
-        map.insert(68166, "板块主力流入");
-        map.insert(68167, "板块主力流出");
-        map.insert(68213, "板块主力净流入");
-        map.insert(68285, "板块主力净量2");
-        map.insert(68759, "板块开盘价");
-        map.insert(133702, "细分行业");
-        map.insert(133778, "基差");
-        map.insert(133964, "日增仓1");
-        map.insert(134071, "市销率TTM");
-        map.insert(134072, "净资产收益率TTM");
-        map.insert(134141, "净利润增长率");
-        map.insert(134143, "营业收入增长率");
-        map.insert(134152, "市盈率(静态)2");
-        map.insert(134160, "换股比率2");
-        map.insert(134162, "折溢率");
-        map.insert(134237, "杠杆比率");
-        map.insert(134238, "溢价");
-        map.insert(199112, "涨幅");
-        map.insert(199643, "大单净量");
-        map.insert(264648, "涨跌");
-        map.insert(330321, "异动类型2");
-        map.insert(330322, "竞价评级1");
-        map.insert(330325, "涨停类型");
-        map.insert(330329, "涨停状态");
-        map.insert(331070, "今日主力增仓占比");
-        map.insert(331077, "2日主力增仓占比");
-        map.insert(331078, "3日主力增仓占比");
-        map.insert(331079, "5日主力增仓占比");
-        map.insert(331080, "10日主力增仓占比");
-        map.insert(331124, "2日主力增仓排名");
-        map.insert(331125, "3日主力增仓排名");
-        map.insert(331126, "5日主力增仓排名");
-        map.insert(331127, "10日主力增仓排名");
-        map.insert(331128, "今日主力增仓排名");
-        map.insert(395720, "委差");
-        map.insert(461256, "委比");
-        map.insert(461346, "年初至今涨幅");
-        map.insert(461438, "涨速(10分钟)");
-        map.insert(461439, "涨速(15分钟)");
-        map.insert(462057, "散户数量");
-        map.insert(526792, "振幅");
-        map.insert(527198, "未知字段527198");
-        map.insert(527526, "涨速(3分钟)");
-        map.insert(527527, "涨速(1分钟)");
-        map.insert(592544, "贡献度");
-        map.insert(592920, "市净率2");
-        map.insert(592741, "机构动向");
-        map.insert(592888, "主力净量");
-        map.insert(592890, "主力净流入");
-        map.insert(592893, "5日大单净量");
-        map.insert(592894, "10日大单净量");
-        map.insert(592946, "多空比");
-        map.insert(625362, "每股公积金");
-        map.insert(625295, "成交笔数2");
-        map.insert(658784, "金叉个数");
-        map.insert(658785, "利好");
-        map.insert(658786, "利空");
-        map.insert(920371, "开盘涨幅");
-        map.insert(920372, "实体涨幅");
-        map.insert(920428, "股票分类标记");
-        map.insert(1149395, "市净率3");
-        map.insert(1378761, "均价");
-        map.insert(1509847, "户均持股数");
-        map.insert(1640904, "手每笔");
-        map.insert(1771976, "量比");
-        map.insert(1968584, "换手率");
-        map.insert(1991120, "涨幅(港)");
-        map.insert(2034120, "市盈率(动态)2");
-        map.insert(2034121, "资产负债率");
-        map.insert(2097453, "流通股");
-        map.insert(2263506, "流通比例");
-        map.insert(2427336, "均笔额");
-        map.insert(2646480, "H股涨跌");
-        map.insert(2820564, "内盘");
-        map.insert(3082712, "涨幅(结算)");
-        map.insert(3475914, "流通市值");
-        map.insert(3541450, "总市值");
-        map.insert(3934664, "板块涨速");
-        map.insert(4065737, "买价");
-        map.insert(4099083, "日增仓2");
-        map.insert(4131273, "卖价");
-        map.insert(4525375, "小单流入");
-        map.insert(4525376, "中单流入");
-        map.insert(4525377, "大单流入");
-        map.insert(7000001, "占基金规模1");
-        map.insert(7000002, "持股变动1");
-        map.insert(7000003, "基金规模1");
-        map.insert(7000004, "代码2");
-        map.insert(7000005, "涨幅百分比");
-        map.insert(7000006, "持股变动2");
-        map.insert(7000007, "占基金规模2");
-        map.insert(7000008, "业绩表现");
-        map.insert(7000009, "近一年收益");
-        map.insert(7000010, "近一周收益");
-        map.insert(7000011, "近一月收益");
-        map.insert(7000012, "近三月收益");
-        map.insert(7000013, "今年以来收益");
-        map.insert(7000014, "成立以来收益");
-        map.insert(7000015, "产品类型");
-        map.insert(7000016, "基金规模2");
-        map.insert(7000017, "基金公司");
-        map.insert(7000018, "投资类型");
-        map.insert(7000019, "基金经理");
-        map.insert(7000020, "资产占比");
-        map.insert(7000021, "较上期");
-        map.insert(8311855, "类别");
-        map.insert(8719679, "小单流出");
-        map.insert(8719680, "中单流出");
-        map.insert(8719681, "大单流出");
-        map.insert(12345671, "A股关联主题");
-        map.insert(12913983, "小单净额");
-        map.insert(12913984, "中单净额");
-        map.insert(12913985, "大单净额");
-        map.insert(17108287, "小单净额占比");
-        map.insert(17108288, "中单净额占比");
-        map.insert(17108289, "大单净额占比");
-        map.insert(18550831, "成分股数");
-        map.insert(20190901, "标签");
-        map.insert(21302591, "小单总额");
-        map.insert(21302592, "中单总额");
-        map.insert(21302593, "大单总额");
-        map.insert(25496895, "小单总额占比");
-        map.insert(25496896, "中单总额占比");
-        map.insert(25496897, "大单总额占比");
-        map.insert(189546735, "计算数据项");
-        map.insert(2018090319, "竞价评级2");
-        map.insert(2018090320, "竞价异动说明");
-        map.insert(2018090410, "异动说明");
-        map.insert(2018090411, "竞价涨幅");
-        map
-    };
-} 
\ No newline at end of file
+    pub static ref FIELD_NAME_MAP: HashMap<i32, &'static str> = Field::ALL.iter().map(|f| (f.id(), f.name())).collect();
+}
\ No newline at end of file
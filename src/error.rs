@@ -1,5 +1,12 @@
+use std::convert::Infallible;
 use std::fmt;
 use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// 解析错误的底层原因
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Debug)]
 pub enum THSError {
@@ -7,9 +14,31 @@ pub enum THSError {
     LibraryError(String),
     InvalidCode(String),
     InvalidDate(String),
+    /// 字段字典中没有该字段
+    UnknownField(String),
     NoData(String),
     UnsupportedPlatform(String),
     ApiError(String),
+    /// 输出缓冲区大小不足，`size` 为本次调用使用的缓冲区大小
+    BufferTooSmall { size: usize },
+    /// 动态库返回了未知的错误码，一般表示方法不存在
+    MethodNotFound { code: i32, method: String },
+    NotLoggedIn,
+    /// 服务器在 `err_info` 中返回了错误信息
+    Server { err_info: String },
+    /// 返回数据无法解析，`context` 说明解析的内容
+    Decode { context: String, source: BoxError },
+    /// 调用超过了限定时间
+    Timeout { method: String, timeout: Duration },
+    /// 动态库中有超时后仍未返回的调用，`method` 为该调用的方法
+    LibraryBusy { method: String },
+}
+
+impl THSError {
+    /// 构造解析错误，`source` 可以是任意错误或错误描述
+    pub fn decode(context: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        THSError::Decode { context: context.into(), source: source.into() }
+    }
 }
 
 impl fmt::Display for THSError {
@@ -19,17 +48,83 @@ impl fmt::Display for THSError {
             THSError::LibraryError(e) => write!(f, "动态库错误: {}", e),
             THSError::InvalidCode(e) => write!(f, "无效的证券代码: {}", e),
             THSError::InvalidDate(e) => write!(f, "无效的日期: {}", e),
+            THSError::UnknownField(e) => write!(f, "未知的字段: {}", e),
             THSError::NoData(e) => write!(f, "无数据: {}", e),
             THSError::UnsupportedPlatform(e) => write!(f, "不支持的平台: {}", e),
             THSError::ApiError(e) => write!(f, "API错误: {}", e),
+            THSError::BufferTooSmall { size } => write!(
+                f,
+                "缓冲区大小不足,当前大小: {:.2} MB",
+                *size as f64 / (1024.0 * 1024.0)
+            ),
+            THSError::MethodNotFound { code, method } => write!(f, "错误代码: {}, 未找到方法: {}", code, method),
+            THSError::NotLoggedIn => write!(f, "未登录"),
+            THSError::Server { err_info } => write!(f, "服务器返回错误: {}", err_info),
+            THSError::Decode { context, source } => write!(f, "{}: {}", context, source),
+            THSError::Timeout { method, timeout } => write!(f, "调用超时: {}, 限定时间: {:?}", method, timeout),
+            THSError::LibraryBusy { method } => write!(f, "动态库仍在执行超时的调用: {}", method),
         }
     }
 }
 
-impl std::error::Error for THSError {}
+impl std::error::Error for THSError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            THSError::IoError(e) => Some(e),
+            THSError::Decode { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// 错误类别，用于判断错误是否可以重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorKind {
+    Io,
+    Library,
+    InvalidInput,
+    NoData,
+    UnsupportedPlatform,
+    /// 输出缓冲区大小不足
+    BufferTooSmall,
+    /// 动态库中没有对应的方法
+    MethodNotFound,
+    NotLoggedIn,
+    /// 服务器返回了错误信息
+    Server,
+    /// 返回数据无法解析
+    Decode,
+    Timeout,
+    Other,
+}
+
+impl THSError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            THSError::IoError(_) => ErrorKind::Io,
+            THSError::LibraryError(_) | THSError::LibraryBusy { .. } => ErrorKind::Library,
+            THSError::InvalidCode(_) | THSError::InvalidDate(_) | THSError::UnknownField(_) => ErrorKind::InvalidInput,
+            THSError::NoData(_) => ErrorKind::NoData,
+            THSError::UnsupportedPlatform(_) => ErrorKind::UnsupportedPlatform,
+            THSError::ApiError(_) => ErrorKind::Other,
+            THSError::BufferTooSmall { .. } => ErrorKind::BufferTooSmall,
+            THSError::MethodNotFound { .. } => ErrorKind::MethodNotFound,
+            THSError::NotLoggedIn => ErrorKind::NotLoggedIn,
+            THSError::Server { .. } => ErrorKind::Server,
+            THSError::Decode { .. } => ErrorKind::Decode,
+            THSError::Timeout { .. } => ErrorKind::Timeout,
+        }
+    }
+}
 
 impl From<io::Error> for THSError {
     fn from(err: io::Error) -> Self {
         THSError::IoError(err)
     }
-} 
\ No newline at end of file
+}
+
+impl From<Infallible> for THSError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
\ No newline at end of file
@@ -0,0 +1,370 @@
+//! 本地复权
+//!
+//! 服务器返回的前复权价格以请求当天为基准，每次除权除息后都会变化，保存下来的前复权数据随之失效。
+//! [`AdjustFactors`] 比较同一段时间的不复权和复权日K线，得到每个交易日的复权因子；
+//! 之后只需保存不复权的K线，按任意基准日在本地复权。
+//!
+//! 复权按等比方式计算：复权价 = 不复权价 × 当日因子 / 基准日因子。因子只在除权除息日变化，
+//! 因此按区间保存，每个区间从 `start` 开始，到下一个区间开始前结束。
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::error::THSError;
+use crate::store::write_replacing;
+use crate::ths::Adjust;
+use crate::types::KLineData;
+
+/// 服务器返回价格的精度，按四舍五入到分估计
+const PRICE_TOLERANCE: f64 = 0.005;
+/// 合并时相邻区间的因子相差小于这个比例就视为同一个区间，分别计算的因子带有舍入误差
+const MERGE_TOLERANCE: f64 = 1e-3;
+
+/// 从 `start` 开始生效的复权因子
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FactorSegment {
+    pub start: NaiveDate,
+    pub factor: f64,
+}
+
+/// 一只证券的复权因子
+///
+/// 因子只有相对大小有意义，第一个区间之前的日期使用第一个因子，`end` 之后的日期使用最后一个因子。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdjustFactors {
+    /// 按 `start` 升序排列
+    pub segments: Vec<FactorSegment>,
+    /// 计算时用到的最后一个交易日
+    pub end: NaiveDate,
+}
+
+impl AdjustFactors {
+    /// 比较不复权和复权（前复权或后复权均可）的日K线，计算复权因子
+    ///
+    /// 两组K线按日期配对，只使用两边都有且价格为正的交易日。服务器的复权价格有舍入误差，
+    /// 只有比值的变化超出误差范围时才认为是除权除息日。
+    pub fn derive(raw: &[KLineData], adjusted: &[KLineData]) -> Result<Self, THSError> {
+        let mut adjusted: Vec<_> = adjusted.iter().map(|bar| (bar.time.date_naive(), bar)).collect();
+        adjusted.sort_by_key(|(date, _)| *date);
+
+        let mut raw: Vec<_> = raw.iter().map(|bar| (bar.time.date_naive(), bar)).collect();
+        raw.sort_by_key(|(date, _)| *date);
+
+        let mut segments: Vec<FactorSegment> = Vec::new();
+        // 当前区间内比值的可能范围
+        let mut range: Option<(f64, f64)> = None;
+        let mut end = None;
+
+        for (date, raw_bar) in raw {
+            let Ok(index) = adjusted.binary_search_by_key(&date, |(d, _)| *d) else {
+                continue;
+            };
+            let Some((low, high)) = ratio_range(raw_bar, adjusted[index].1) else {
+                continue;
+            };
+
+            range = match range {
+                Some((l, h)) if low.max(l) <= high.min(h) => Some((low.max(l), high.min(h))),
+                _ => {
+                    segments.push(FactorSegment { start: date, factor: 0.0 });
+                    Some((low, high))
+                }
+            };
+            if let (Some(last), Some((l, h))) = (segments.last_mut(), range) {
+                last.factor = (l + h) / 2.0;
+            }
+            end = Some(date);
+        }
+
+        match end {
+            Some(end) => Ok(Self { segments, end }),
+            None => Err(THSError::NoData("不复权和复权K线没有共同的交易日".into())),
+        }
+    }
+
+    /// 某一日的复权因子
+    pub fn factor_at(&self, date: NaiveDate) -> f64 {
+        let index = self.segments.partition_point(|s| s.start <= date);
+        self.segments.get(index.saturating_sub(1)).map_or(1.0, |s| s.factor)
+    }
+
+    /// 以 `reference` 为基准复权，基准日的价格不变
+    ///
+    /// 只调整价格，成交量和成交额保持不变。
+    pub fn apply(&self, bars: &[KLineData], reference: NaiveDate) -> Vec<KLineData> {
+        let base = self.factor_at(reference);
+        bars.iter()
+            .map(|bar| {
+                let ratio = self.factor_at(bar.time.date_naive()) / base;
+                KLineData {
+                    open: bar.open * ratio,
+                    high: bar.high * ratio,
+                    low: bar.low * ratio,
+                    close: bar.close * ratio,
+                    ..bar.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// 按复权方式复权：前复权以 `end` 为基准，后复权以第一个区间为基准
+    ///
+    /// 后复权只有在因子覆盖到上市首日时才与服务器的结果一致。
+    pub fn adjust(&self, bars: &[KLineData], adjust: Adjust) -> Vec<KLineData> {
+        match adjust {
+            Adjust::Forward => self.apply(bars, self.end),
+            Adjust::Backward => self.apply(bars, self.segments.first().map_or(self.end, |s| s.start)),
+            Adjust::None => bars.to_vec(),
+        }
+    }
+
+    /// 用新计算的因子更新，新因子覆盖的日期以新因子为准
+    ///
+    /// 新因子按两者共同覆盖的最后一个交易日换算到当前的比例，因此两者必须有重叠的日期。
+    pub fn merge(&mut self, newer: &AdjustFactors) -> Result<(), THSError> {
+        let Some(cut) = newer.segments.first().map(|s| s.start) else {
+            return Ok(());
+        };
+        if cut > self.end {
+            return Err(THSError::ApiError(format!(
+                "复权因子没有重叠的日期: 已有因子截止 {}, 新因子开始于 {}", self.end, cut
+            )));
+        }
+
+        let overlap = self.end.min(newer.end);
+        let scale = self.factor_at(overlap) / newer.factor_at(overlap);
+        self.segments.retain(|s| s.start < cut);
+        for segment in &newer.segments {
+            let factor = segment.factor * scale;
+            // 与前一个区间的因子在误差范围内相同时合并
+            match self.segments.last() {
+                Some(last) if ((factor - last.factor) / last.factor).abs() < MERGE_TOLERANCE => {}
+                _ => self.segments.push(FactorSegment { start: segment.start, factor }),
+            }
+        }
+        self.end = self.end.max(newer.end);
+        Ok(())
+    }
+
+    /// 从 JSON 文件读取
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, THSError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| THSError::decode(format!("复权因子文件格式错误: {}", path.display()), e))
+    }
+
+    /// 保存为 JSON 文件，先写入临时文件再替换，写入失败时原文件不变
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), THSError> {
+        write_replacing(path.as_ref(), |writer| {
+            serde_json::to_writer_pretty(writer, self).map_err(|e| THSError::decode("复权因子序列化失败", e))
+        })
+    }
+}
+
+/// 一个交易日复权价与不复权价之比的可能范围，按开高低收四个价格的舍入误差取交集
+fn ratio_range(raw: &KLineData, adjusted: &KLineData) -> Option<(f64, f64)> {
+    let pairs = [
+        (raw.open, adjusted.open),
+        (raw.high, adjusted.high),
+        (raw.low, adjusted.low),
+        (raw.close, adjusted.close),
+    ];
+
+    let mut range: Option<(f64, f64)> = None;
+    for (raw, adjusted) in pairs {
+        if raw <= 0.0 || adjusted <= 0.0 {
+            continue;
+        }
+        let low = (adjusted - PRICE_TOLERANCE) / (raw + PRICE_TOLERANCE);
+        let high = (adjusted + PRICE_TOLERANCE) / (raw - PRICE_TOLERANCE).max(f64::MIN_POSITIVE);
+        range = match range {
+            None => Some((low, high)),
+            Some((l, h)) if low.max(l) <= high.min(h) => Some((low.max(l), high.min(h))),
+            // 同一天的价格互相矛盾，以收盘价为准
+            Some(_) => Some((low, high)),
+        };
+    }
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, TimeZone};
+
+    use super::*;
+
+    /// 除权除息日（从第一天起的天数）和当天的复权比例
+    const EX_DATES: [(i64, f64); 3] = [(100, 1.03), (250, 1.002), (400, 1.5)];
+
+    fn first_day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 1, 1).unwrap()
+    }
+
+    fn round2(x: f64) -> f64 {
+        (x * 100.0).round() / 100.0
+    }
+
+    /// 第 `day` 天的后复权比例
+    fn backward_ratio(day: i64) -> f64 {
+        EX_DATES.iter().filter(|(d, _)| day >= *d).map(|(_, r)| r).product()
+    }
+
+    fn total_ratio() -> f64 {
+        EX_DATES.iter().map(|(_, r)| r).product()
+    }
+
+    fn raw_bar(day: i64) -> KLineData {
+        let date = first_day() + Duration::days(day);
+        let price = 10.0 + (day as f64 * 0.1).sin();
+        KLineData {
+            time: Local.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).unwrap(),
+            open: round2(price),
+            high: round2(price * 1.02),
+            low: round2(price * 0.98),
+            close: round2(price * 1.01),
+            volume: 100,
+            amount: 1000.0,
+        }
+    }
+
+    /// 按比例复权，价格和服务器一样舍入到分
+    fn scaled(bar: &KLineData, ratio: f64) -> KLineData {
+        KLineData {
+            open: round2(bar.open * ratio),
+            high: round2(bar.high * ratio),
+            low: round2(bar.low * ratio),
+            close: round2(bar.close * ratio),
+            ..bar.clone()
+        }
+    }
+
+    fn series(days: std::ops::Range<i64>) -> (Vec<KLineData>, Vec<KLineData>, Vec<KLineData>) {
+        let raw: Vec<_> = days.clone().map(raw_bar).collect();
+        let backward = days.clone().zip(&raw).map(|(d, bar)| scaled(bar, backward_ratio(d))).collect();
+        let forward = days.zip(&raw).map(|(d, bar)| scaled(bar, backward_ratio(d) / total_ratio())).collect();
+        (raw, backward, forward)
+    }
+
+    #[test]
+    fn derive_finds_ex_dates() {
+        let (raw, backward, _) = series(0..500);
+        let factors = AdjustFactors::derive(&raw, &backward).unwrap();
+
+        let starts: Vec<_> = factors.segments.iter().map(|s| s.start).collect();
+        let expected: Vec<_> = [0, 100, 250, 400].iter().map(|d| first_day() + Duration::days(*d)).collect();
+        assert_eq!(starts, expected);
+        assert_eq!(factors.end, first_day() + Duration::days(499));
+        for (segment, day) in factors.segments.iter().zip([0, 100, 250, 400]) {
+            assert!((segment.factor - backward_ratio(day)).abs() < 1e-3, "{:?}", segment);
+        }
+    }
+
+    #[test]
+    fn rounding_noise_is_not_an_ex_date() {
+        // 比值只在舍入误差范围内波动时不会产生新的区间
+        let raw: Vec<_> = (0..200).map(raw_bar).collect();
+        let adjusted: Vec<_> = raw.iter().map(|bar| scaled(bar, 0.987_654)).collect();
+        let factors = AdjustFactors::derive(&raw, &adjusted).unwrap();
+        assert_eq!(factors.segments.len(), 1);
+        assert!((factors.segments[0].factor - 0.987_654).abs() < 1e-3);
+    }
+
+    #[test]
+    fn derive_skips_unmatched_and_invalid_days() {
+        let (raw, mut backward, _) = series(0..50);
+        backward.remove(10);
+        backward[20].open = 0.0;
+        backward[20].high = 0.0;
+        backward[20].low = 0.0;
+        backward[20].close = 0.0;
+        let factors = AdjustFactors::derive(&raw, &backward).unwrap();
+        assert_eq!(factors.segments.len(), 1);
+
+        assert!(matches!(AdjustFactors::derive(&raw, &[]), Err(THSError::NoData(_))));
+    }
+
+    #[test]
+    fn forward_and_backward_adjust() {
+        let (raw, backward, forward) = series(0..500);
+        let factors = AdjustFactors::derive(&raw, &backward).unwrap();
+
+        for (adjust, expected) in [(Adjust::Forward, &forward), (Adjust::Backward, &backward)] {
+            let adjusted = factors.adjust(&raw, adjust);
+            for (a, e) in adjusted.iter().zip(expected.iter()) {
+                assert!((a.close - e.close).abs() <= 0.011, "{:?}: {} != {}", adjust, a.close, e.close);
+                assert_eq!(a.volume, e.volume);
+            }
+        }
+        let unadjusted = factors.adjust(&raw, Adjust::None);
+        assert!(unadjusted.iter().zip(&raw).all(|(a, r)| a.close == r.close));
+    }
+
+    #[test]
+    fn factor_at_outside_segments() {
+        let factors = AdjustFactors {
+            segments: vec![
+                FactorSegment { start: first_day(), factor: 1.0 },
+                FactorSegment { start: first_day() + Duration::days(10), factor: 2.0 },
+            ],
+            end: first_day() + Duration::days(20),
+        };
+        assert_eq!(factors.factor_at(first_day() - Duration::days(1)), 1.0);
+        assert_eq!(factors.factor_at(first_day() + Duration::days(9)), 1.0);
+        assert_eq!(factors.factor_at(first_day() + Duration::days(10)), 2.0);
+        assert_eq!(factors.factor_at(first_day() + Duration::days(100)), 2.0);
+    }
+
+    #[test]
+    fn merge_rescales_newer_factors() {
+        let (raw, backward, forward) = series(0..500);
+        // 已有因子由前 300 天的后复权数据计算，新因子由后 300 天的前复权数据计算，比例不同
+        let mut factors = AdjustFactors::derive(&raw[..300], &backward[..300]).unwrap();
+        let newer = AdjustFactors::derive(&raw[200..], &forward[200..]).unwrap();
+        factors.merge(&newer).unwrap();
+
+        let starts: Vec<_> = factors.segments.iter().map(|s| s.start).collect();
+        let expected: Vec<_> = [0, 100, 250, 400].iter().map(|d| first_day() + Duration::days(*d)).collect();
+        assert_eq!(starts, expected);
+        assert_eq!(factors.end, first_day() + Duration::days(499));
+        // 合并后的比例与已有因子一致
+        assert!((factors.factor_at(first_day() + Duration::days(450)) - total_ratio()).abs() < 1e-3);
+
+        let adjusted = factors.adjust(&raw, Adjust::Forward);
+        for (a, e) in adjusted.iter().zip(&forward) {
+            assert!((a.close - e.close).abs() <= 0.011, "{} != {}", a.close, e.close);
+        }
+    }
+
+    #[test]
+    fn merge_requires_overlap() {
+        let (raw, backward, _) = series(0..300);
+        let mut factors = AdjustFactors::derive(&raw[..100], &backward[..100]).unwrap();
+        let newer = AdjustFactors::derive(&raw[150..], &backward[150..]).unwrap();
+        assert!(factors.merge(&newer).is_err());
+        assert_eq!(factors.end, first_day() + Duration::days(99));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let (raw, backward, _) = series(0..500);
+        let factors = AdjustFactors::derive(&raw, &backward).unwrap();
+        let path = std::env::temp_dir().join(format!("rusths_factors_{}.json", std::process::id()));
+        std::fs::write(&path, "旧的内容").unwrap();
+        factors.save(&path).unwrap();
+        let loaded = AdjustFactors::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+
+        assert_eq!(loaded.end, factors.end);
+        assert_eq!(loaded.segments.len(), factors.segments.len());
+        for (a, b) in loaded.segments.iter().zip(&factors.segments) {
+            assert_eq!(a.start, b.start);
+            assert!((a.factor - b.factor).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,824 @@
+//! datatype 字段字典
+//!
+//! 每个字段有数字 id（请求中的 datatype）、中文名称（返回数据中的键）和英文别名，三者都可以用来查找字段。
+//! 构造请求时可以直接使用 [`Field`]，也可以用 [`fields!`](crate::fields!) 按名称列出：
+//!
+//! ```
+//! use rusths::fields;
+//! use rusths::fields::Field;
+//!
+//! assert_eq!(fields!["涨幅", "turnover_rate"], [Field::ChangePct, Field::TurnoverRate]);
+//! assert_eq!(Field::ChangePct.id(), 199112);
+//! ```
+//!
+//! 解析返回数据时使用 [`Record`]，它接受以数字 id 或中文名称为键的数据。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use serde_json::{Map, Value};
+
+use crate::error::THSError;
+use crate::types::value_f64;
+
+/// 字段分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// 价格、成交量、涨跌幅等行情数据
+    Quote,
+    /// 买卖档位和委托
+    OrderBook,
+    /// 大中小单、主力资金和融资融券
+    MoneyFlow,
+    /// 股本、财务指标、估值和基金数据
+    Fundamental,
+    /// 债券、期权、权证和期货
+    BondOption,
+    /// 含义不明确的字段
+    Other,
+}
+
+/// 字段值的单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// 无单位或单位不确定
+    None,
+    /// 元
+    Yuan,
+    /// 百分比，值为 5.0 表示 5%
+    Percent,
+    /// 股
+    Share,
+    /// 手
+    Lot,
+    /// 个数、笔数、家数
+    Count,
+    /// 倍
+    Multiple,
+}
+
+/// 字段值的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Float,
+    Integer,
+    Text,
+    /// `YYYYMMDD` 形式的日期
+    Date,
+    /// 时间或时间戳，格式随请求不同
+    Time,
+}
+
+/// 字段的完整信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldInfo {
+    /// 请求中的 datatype
+    pub id: i32,
+    /// 中文名称，与 `FIELD_NAME_MAP` 一致
+    pub name: &'static str,
+    /// 英文别名，snake_case，不会随版本改变
+    pub alias: &'static str,
+    pub category: Category,
+    pub unit: Unit,
+    pub value_type: ValueType,
+}
+
+macro_rules! define_fields {
+    ($($variant:ident = $id:literal, $name:literal, $alias:literal, $category:ident, $unit:ident, $value_type:ident;)*) => {
+        /// datatype 字段，文档中为字段的中文名称
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[non_exhaustive]
+        pub enum Field {
+            $(
+                #[doc = $name]
+                $variant,
+            )*
+        }
+
+        /// 按 `Field` 的声明顺序排列
+        const FIELD_INFO: &[FieldInfo] = &[
+            $(
+                FieldInfo {
+                    id: $id,
+                    name: $name,
+                    alias: $alias,
+                    category: Category::$category,
+                    unit: Unit::$unit,
+                    value_type: ValueType::$value_type,
+                },
+            )*
+        ];
+
+        impl Field {
+            /// 全部字段
+            pub const ALL: &'static [Field] = &[$(Field::$variant,)*];
+        }
+    };
+}
+
+define_fields! {
+    Time = 1, "时间", "time", Quote, None, Time;
+    Code = 5, "代码", "code", Quote, None, Text;
+    PreClose = 6, "昨收价", "pre_close", Quote, Yuan, Float;
+    Open = 7, "开盘价", "open", Quote, Yuan, Float;
+    High = 8, "最高价", "high", Quote, Yuan, Float;
+    Low = 9, "最低价", "low", Quote, Yuan, Float;
+    Price = 10, "价格", "price", Quote, Yuan, Float;
+    Close = 11, "收盘价", "close", Quote, Yuan, Float;
+    TradeDirection = 12, "成交方向", "trade_direction", Quote, None, Integer;
+    Volume = 13, "成交量", "volume", Quote, Share, Integer;
+    OuterVolume = 14, "外盘成交量", "outer_volume", Quote, Share, Integer;
+    InnerVolume = 15, "内盘成交量", "inner_volume", Quote, Share, Integer;
+    CrossVolume = 16, "对倒成交量", "cross_volume", Quote, Share, Integer;
+    OpenVolume = 17, "开盘成交量", "open_volume", Quote, Share, Integer;
+    TradeCount = 18, "交易笔数", "trade_count", Quote, Count, Integer;
+    Amount = 19, "总金额", "amount", Quote, Yuan, Float;
+    OrderBidPrice = 20, "委托买入价", "order_bid_price", OrderBook, Yuan, Float;
+    OrderAskPrice = 21, "委托卖出价", "order_ask_price", OrderBook, Yuan, Float;
+    OrderBidVolume = 22, "委买总量", "order_bid_volume", OrderBook, Share, Integer;
+    OrderAskVolume = 23, "委卖总量", "order_ask_volume", OrderBook, Share, Integer;
+    Bid1Price = 24, "买1价", "bid1_price", OrderBook, Yuan, Float;
+    Bid1Volume = 25, "买1量", "bid1_volume", OrderBook, Share, Integer;
+    Bid2Price = 26, "买2价", "bid2_price", OrderBook, Yuan, Float;
+    Bid2Volume = 27, "买2量", "bid2_volume", OrderBook, Share, Integer;
+    Bid3Price = 28, "买3价", "bid3_price", OrderBook, Yuan, Float;
+    Bid3Volume = 29, "买3量", "bid3_volume", OrderBook, Share, Integer;
+    Ask1Price = 30, "卖1价", "ask1_price", OrderBook, Yuan, Float;
+    Ask1Volume = 31, "卖1量", "ask1_volume", OrderBook, Share, Integer;
+    Ask2Price = 32, "卖2价", "ask2_price", OrderBook, Yuan, Float;
+    Ask2Volume = 33, "卖2量", "ask2_volume", OrderBook, Share, Integer;
+    Ask3Price = 34, "卖3价", "ask3_price", OrderBook, Yuan, Float;
+    Ask3Volume = 35, "卖3量", "ask3_volume", OrderBook, Share, Integer;
+    IndexType = 36, "指数种类", "index_type", Quote, None, Integer;
+    StockCount = 37, "股票总数", "stock_count", Quote, Count, Integer;
+    Advancers = 38, "上涨家数", "advancers", Quote, Count, Integer;
+    Decliners = 39, "下跌家数", "decliners", Quote, Count, Integer;
+    LeadingIndicator = 40, "领先指标", "leading_indicator", Quote, None, Float;
+    UpTrend = 41, "上涨趋势", "up_trend", Quote, None, Float;
+    DownTrend = 42, "下跌趋势", "down_trend", Quote, None, Float;
+    LastAmount = 43, "最近成交金额", "last_amount", Quote, Yuan, Float;
+    NameTraditional = 44, "证券名称(繁体)", "name_traditional", Quote, None, Text;
+    Volume5d = 45, "五日成交总量", "volume_5d", Quote, Share, Integer;
+    NameEnglish = 46, "证券名称(英文)", "name_english", Quote, None, Text;
+    NameUnicode = 47, "证券名称(Unicode)", "name_unicode", Quote, None, Text;
+    Speed = 48, "涨速", "speed", Quote, Percent, Float;
+    CurrentVolume = 49, "当前量(手)", "current_volume", Quote, Lot, Integer;
+    CodeHk = 50, "代码(港)", "code_hk", Quote, None, Text;
+    CommissionRatioAlt = 53, "委比(53)", "commission_ratio_alt", OrderBook, Percent, Float;
+    AvgPriceCffex = 54, "均价(中金所)", "avg_price_cffex", Quote, Yuan, Float;
+    Name = 55, "名称", "name", Quote, None, Text;
+    OrderTime = 56, "挂单时间", "order_time", OrderBook, None, Time;
+    HSharePreClose = 60, "H股昨收", "h_share_pre_close", Quote, Yuan, Float;
+    AnomalyType1 = 61, "异动类型1", "anomaly_type_1", Quote, None, Integer;
+    Status = 64, "状态", "status", Quote, None, Integer;
+    OpenInterest = 65, "持仓", "open_interest", BondOption, None, Integer;
+    PreSettlement = 66, "昨结", "pre_settlement", BondOption, Yuan, Float;
+    LimitUp = 69, "涨停价", "limit_up", Quote, Yuan, Float;
+    LimitDown = 70, "跌停价", "limit_down", Quote, Yuan, Float;
+    OpenInterestChange = 71, "现增仓", "open_interest_change", BondOption, None, Integer;
+    Settlement = 72, "今结", "settlement", BondOption, Yuan, Float;
+    PreOpenInterest = 73, "昨持仓", "pre_open_interest", BondOption, None, Integer;
+    BidOrderId = 74, "买单ID", "bid_order_id", OrderBook, None, Integer;
+    AskOrderId = 75, "卖单ID", "ask_order_id", OrderBook, None, Integer;
+    Interest = 80, "利息", "interest", BondOption, Yuan, Float;
+    CancelTime = 82, "撤单时间", "cancel_time", OrderBook, None, Time;
+    Industry = 84, "所属行业", "industry", Fundamental, None, Text;
+    Profitability = 85, "盈利情况", "profitability", Fundamental, None, Text;
+    TransferStatus = 89, "转让状态参数", "transfer_status", Quote, None, Text;
+    BlockFloatMarketValue = 90, "板块流通市值", "block_float_market_value", Quote, Yuan, Float;
+    Pe = 91, "市盈率", "pe", Fundamental, Multiple, Float;
+    BlockTotalMarketValue = 92, "板块总市值", "block_total_market_value", Quote, Yuan, Float;
+    TradeUnit = 93, "交易单位", "trade_unit", Quote, Share, Integer;
+    High52w = 95, "52周最高", "high_52w", Quote, Yuan, Float;
+    Low52w = 96, "52周最低", "low_52w", Quote, Yuan, Float;
+    PriceHk = 100, "现价(港)", "price_hk", Quote, Yuan, Float;
+    Bid6Price = 102, "买6价", "bid6_price", OrderBook, Yuan, Float;
+    Bid6Volume = 103, "买6量", "bid6_volume", OrderBook, Share, Integer;
+    Ask6Price = 104, "卖6价", "ask6_price", OrderBook, Yuan, Float;
+    Ask6Volume = 105, "卖6量", "ask6_volume", OrderBook, Share, Integer;
+    Bid7Price = 106, "买7价", "bid7_price", OrderBook, Yuan, Float;
+    Bid7Volume = 107, "买7量", "bid7_volume", OrderBook, Share, Integer;
+    Ask7Price = 108, "卖7价", "ask7_price", OrderBook, Yuan, Float;
+    Ask7Volume = 109, "卖7量", "ask7_volume", OrderBook, Share, Integer;
+    Bid8Price = 110, "买8价", "bid8_price", OrderBook, Yuan, Float;
+    Bid8Volume = 111, "买8量", "bid8_volume", OrderBook, Share, Integer;
+    Ask8Price = 112, "卖8价", "ask8_price", OrderBook, Yuan, Float;
+    Ask8Volume = 113, "卖8量", "ask8_volume", OrderBook, Share, Integer;
+    Bid9Price = 114, "买9价", "bid9_price", OrderBook, Yuan, Float;
+    Bid9Volume = 115, "买9量", "bid9_volume", OrderBook, Share, Integer;
+    Ask9Price = 116, "卖9价", "ask9_price", OrderBook, Yuan, Float;
+    Ask9Volume = 117, "卖9量", "ask9_volume", OrderBook, Share, Integer;
+    Bid10Price = 118, "买10价", "bid10_price", OrderBook, Yuan, Float;
+    Bid10Volume = 119, "买10量", "bid10_volume", OrderBook, Share, Integer;
+    Ask10Price = 120, "卖10价", "ask10_price", OrderBook, Yuan, Float;
+    Ask10Volume = 121, "卖10量", "ask10_volume", OrderBook, Share, Integer;
+    WeightedBidPrice = 122, "加权平均买价", "weighted_bid_price", OrderBook, Yuan, Float;
+    TotalBidVolume = 123, "总委买量", "total_bid_volume", OrderBook, Share, Integer;
+    WeightedAskPrice = 124, "加权平均卖价", "weighted_ask_price", OrderBook, Yuan, Float;
+    TotalAskVolume = 125, "总委卖量", "total_ask_volume", OrderBook, Share, Integer;
+    VolumeHk = 130, "总手(港)", "volume_hk", Quote, Lot, Integer;
+    VcmReferencePrice = 141, "波动性中断参考价", "vcm_reference_price", Quote, Yuan, Float;
+    VcmIndicativeVolume = 142, "波动性中断虚拟匹配量", "vcm_indicative_volume", Quote, Share, Integer;
+    ContractCode = 143, "合约代码", "contract_code", BondOption, None, Text;
+    UnderlyingCode = 144, "标的证券代码", "underlying_code", BondOption, None, Text;
+    UnderlyingName = 145, "标的证券名称", "underlying_name", BondOption, None, Text;
+    UnderlyingType = 146, "标的证券类型", "underlying_type", BondOption, None, Text;
+    OptionType = 147, "期权类型", "option_type", BondOption, None, Text;
+    CallPut = 148, "认购认沽", "call_put", BondOption, None, Text;
+    ContractUnit = 149, "合约单位", "contract_unit", BondOption, None, Integer;
+    Bid4Price = 150, "买4价", "bid4_price", OrderBook, Yuan, Float;
+    Bid4Volume = 151, "买4量", "bid4_volume", OrderBook, Share, Integer;
+    Ask4Price = 152, "卖4价", "ask4_price", OrderBook, Yuan, Float;
+    Ask4Volume = 153, "卖4量", "ask4_volume", OrderBook, Share, Integer;
+    Bid5Price = 154, "买5价", "bid5_price", OrderBook, Yuan, Float;
+    Bid5Volume = 155, "买5量", "bid5_volume", OrderBook, Share, Integer;
+    Ask5Price = 156, "卖5价", "ask5_price", OrderBook, Yuan, Float;
+    Ask5Volume = 157, "卖5量", "ask5_volume", OrderBook, Share, Integer;
+    MarketTier = 160, "市场分层", "market_tier", Quote, None, Text;
+    BidSpread = 191, "买差价", "bid_spread", OrderBook, Yuan, Float;
+    AskSpread = 192, "卖差价", "ask_spread", OrderBook, Yuan, Float;
+    ActiveBuyXlVolume = 201, "主动买入特大单量", "active_buy_xl_volume", MoneyFlow, Share, Integer;
+    ActiveSellXlVolume = 202, "主动卖出特大单量", "active_sell_xl_volume", MoneyFlow, Share, Integer;
+    ActiveBuyLVolume = 203, "主动买入大单量", "active_buy_l_volume", MoneyFlow, Share, Integer;
+    ActiveSellLVolume = 204, "主动卖出大单量", "active_sell_l_volume", MoneyFlow, Share, Integer;
+    ActiveBuyMVolume = 205, "主动买入中单量", "active_buy_m_volume", MoneyFlow, Share, Integer;
+    ActiveSellMVolume = 206, "主动卖出中单量", "active_sell_m_volume", MoneyFlow, Share, Integer;
+    PassiveBuyXlVolume = 207, "被动买入特大单量", "passive_buy_xl_volume", MoneyFlow, Share, Integer;
+    PassiveSellXlVolume = 208, "被动卖出特大单量", "passive_sell_xl_volume", MoneyFlow, Share, Integer;
+    PassiveBuyLVolume = 209, "被动买入大单量", "passive_buy_l_volume", MoneyFlow, Share, Integer;
+    PassiveSellLVolume = 210, "被动卖出大单量", "passive_sell_l_volume", MoneyFlow, Share, Integer;
+    PassiveBuyMVolume = 211, "被动买入中单量", "passive_buy_m_volume", MoneyFlow, Share, Integer;
+    PassiveSellMVolume = 212, "被动卖出中单量", "passive_sell_m_volume", MoneyFlow, Share, Integer;
+    ActiveBuySVolume = 213, "主动买入小单量", "active_buy_s_volume", MoneyFlow, Share, Integer;
+    ActiveSellSVolume = 214, "主动卖出小单量", "active_sell_s_volume", MoneyFlow, Share, Integer;
+    ActiveBuyXlCount = 215, "主动买入特大单笔数", "active_buy_xl_count", MoneyFlow, Count, Integer;
+    ActiveSellXlCount = 216, "主动卖出特大单笔数", "active_sell_xl_count", MoneyFlow, Count, Integer;
+    ActiveBuyLCount = 217, "主动买入大单笔数", "active_buy_l_count", MoneyFlow, Count, Integer;
+    ActiveSellLCount = 218, "主动卖出大单笔数", "active_sell_l_count", MoneyFlow, Count, Integer;
+    PassiveBuyXlCount = 219, "被动买入特大单笔数", "passive_buy_xl_count", MoneyFlow, Count, Integer;
+    PassiveSellXlCount = 220, "被动卖出特大单笔数", "passive_sell_xl_count", MoneyFlow, Count, Integer;
+    PassiveBuyLCount = 221, "被动买入大单笔数", "passive_buy_l_count", MoneyFlow, Count, Integer;
+    PassiveSellLCount = 222, "被动卖出大单笔数", "passive_sell_l_count", MoneyFlow, Count, Integer;
+    ActiveBuyXlAmount = 223, "主动买入特大单金额", "active_buy_xl_amount", MoneyFlow, Yuan, Float;
+    ActiveSellXlAmount = 224, "主动卖出特大单金额", "active_sell_xl_amount", MoneyFlow, Yuan, Float;
+    ActiveBuyLAmount = 225, "主动买入大单金额", "active_buy_l_amount", MoneyFlow, Yuan, Float;
+    ActiveSellLAmount = 226, "主动卖出大单金额", "active_sell_l_amount", MoneyFlow, Yuan, Float;
+    PassiveBuyXlAmount = 227, "被动买入特大单金额", "passive_buy_xl_amount", MoneyFlow, Yuan, Float;
+    PassiveSellXlAmount = 228, "被动卖出特大单金额", "passive_sell_xl_amount", MoneyFlow, Yuan, Float;
+    PassiveBuyLAmount = 229, "被动买入大单金额", "passive_buy_l_amount", MoneyFlow, Yuan, Float;
+    PassiveSellLAmount = 230, "被动卖出大单金额", "passive_sell_l_amount", MoneyFlow, Yuan, Float;
+    BuyOrderCount = 231, "买入单数量", "buy_order_count", MoneyFlow, Count, Integer;
+    SellOrderCount = 232, "卖出单数量", "sell_order_count", MoneyFlow, Count, Integer;
+    MoneyInflow = 233, "资金流入", "money_inflow", MoneyFlow, Yuan, Float;
+    MoneyOutflow = 234, "资金流出", "money_outflow", MoneyFlow, Yuan, Float;
+    LargeNetVolumePositive = 235, "大单净量正", "large_net_volume_positive", MoneyFlow, None, Float;
+    LargeNetVolumeNegative = 236, "大单净量负", "large_net_volume_negative", MoneyFlow, None, Float;
+    ActiveBuySAmount = 237, "主动买入小单金额", "active_buy_s_amount", MoneyFlow, Yuan, Float;
+    ActiveSellSAmount = 238, "主动卖出小单金额", "active_sell_s_amount", MoneyFlow, Yuan, Float;
+    DealCount = 239, "成交笔数", "deal_count", Quote, Count, Integer;
+    PreCloseYield = 240, "昨日收盘收益率", "pre_close_yield", BondOption, Percent, Float;
+    PreWeightedAvgYield = 241, "昨日加权平均收益率", "pre_weighted_avg_yield", BondOption, Percent, Float;
+    OpenYield = 242, "开盘收益率", "open_yield", BondOption, Percent, Float;
+    HighYield = 243, "最高收益率", "high_yield", BondOption, Percent, Float;
+    LowYield = 244, "最低收益率", "low_yield", BondOption, Percent, Float;
+    LastYield = 245, "最新收益率", "last_yield", BondOption, Percent, Float;
+    WeightedAvgYield = 246, "当日加权平均收益率", "weighted_avg_yield", BondOption, Percent, Float;
+    BidAmountTop5 = 250, "委托买入前五档金额", "bid_amount_top5", OrderBook, Yuan, Float;
+    AskAmountTop5 = 251, "委托卖出前五档金额", "ask_amount_top5", OrderBook, Yuan, Float;
+    BidAmountTop10 = 252, "委托买入前十档金额", "bid_amount_top10", OrderBook, Yuan, Float;
+    AskAmountTop10 = 253, "委托卖出前十档金额", "ask_amount_top10", OrderBook, Yuan, Float;
+    ActiveBuyMCount = 255, "主动买入中单笔数", "active_buy_m_count", MoneyFlow, Count, Integer;
+    ActiveSellMCount = 256, "主动卖出中单笔数", "active_sell_m_count", MoneyFlow, Count, Integer;
+    PassiveBuyMCount = 257, "被动买入中单笔数", "passive_buy_m_count", MoneyFlow, Count, Integer;
+    PassiveSellMCount = 258, "被动卖出中单笔数", "passive_sell_m_count", MoneyFlow, Count, Integer;
+    ActiveBuyMAmount = 259, "主动买入中单金额", "active_buy_m_amount", MoneyFlow, Yuan, Float;
+    ActiveSellMAmount = 260, "主动卖出中单金额", "active_sell_m_amount", MoneyFlow, Yuan, Float;
+    PassiveBuyMAmount = 261, "被动买入中单金额", "passive_buy_m_amount", MoneyFlow, Yuan, Float;
+    PassiveSellMAmount = 262, "被动卖出中单金额", "passive_sell_m_amount", MoneyFlow, Yuan, Float;
+    High52wDate = 271, "52周最高日期", "high_52w_date", Quote, None, Date;
+    Low52wDate = 272, "52周最低日期", "low_52w_date", Quote, None, Date;
+    YearHighDate = 273, "年度最高日期", "year_high_date", Quote, None, Date;
+    YearLowDate = 274, "年度最低日期", "year_low_date", Quote, None, Date;
+    LeadingStock = 275, "领涨股", "leading_stock", Quote, None, Text;
+    LimitUpCount = 276, "涨停家数", "limit_up_count", Quote, Count, Integer;
+    LimitDownCount = 277, "跌停家数", "limit_down_count", Quote, Count, Integer;
+    AfterHoursPrice = 278, "盘后最新价", "after_hours_price", Quote, Yuan, Float;
+    HkIndexAmountCny = 279, "港股指数人民币成交金额", "hk_index_amount_cny", Quote, Yuan, Float;
+    StrikePrice = 280, "期权行权价", "strike_price", BondOption, Yuan, Float;
+    FirstTradeDate = 281, "首个交易日", "first_trade_date", BondOption, None, Date;
+    LastTradeDate1 = 282, "最后交易日1", "last_trade_date_1", BondOption, None, Date;
+    ExerciseDate = 283, "期权行权日", "exercise_date", BondOption, None, Date;
+    ExpiryDate = 284, "期权到期日", "expiry_date", BondOption, None, Date;
+    ContractVersion = 285, "合约版本号", "contract_version", BondOption, None, Text;
+    DeliveryDate = 286, "行权交割日", "delivery_date", BondOption, None, Date;
+    UnderlyingPreClose = 288, "标的证券前收盘", "underlying_pre_close", BondOption, Yuan, Float;
+    PriceLimitType = 289, "涨跌幅限制类型", "price_limit_type", Quote, None, Text;
+    MarginRatio1 = 290, "保证金比例参数1", "margin_ratio_1", BondOption, Percent, Float;
+    MarginRatio2 = 291, "保证金比例参数2", "margin_ratio_2", BondOption, Percent, Float;
+    UnitMargin = 292, "单位保证金", "unit_margin", BondOption, Yuan, Float;
+    RoundLot = 294, "整手数", "round_lot", BondOption, None, Integer;
+    LimitOrderMin = 295, "单笔限价申报下限", "limit_order_min", BondOption, None, Integer;
+    LimitOrderMax = 296, "单笔限价申报上限", "limit_order_max", BondOption, None, Integer;
+    MarketOrderMin = 297, "单笔市价申报下限", "market_order_min", BondOption, None, Integer;
+    MarketOrderMax = 298, "单笔市价申报上限", "market_order_max", BondOption, None, Integer;
+    ContractStatus = 299, "期权合约状态标签", "contract_status", BondOption, None, Text;
+    TotalShares = 402, "总股本", "total_shares", Fundamental, Share, Float;
+    FloatShares = 407, "流通股本", "float_shares", Fundamental, Share, Float;
+    FloatBShares = 410, "流通B股", "float_b_shares", Fundamental, Share, Float;
+    DividendInfo = 471, "权息资料", "dividend_info", Fundamental, None, Text;
+    ConversionPrice = 497, "转股价", "conversion_price", BondOption, Yuan, Float;
+    BondBalance = 499, "债券余额", "bond_balance", BondOption, Yuan, Float;
+    CurrentAssets = 520, "流动资产", "current_assets", Fundamental, Yuan, Float;
+    TotalAssets = 543, "资产总计", "total_assets", Fundamental, Yuan, Float;
+    Reserve = 593, "公积金", "reserve", Fundamental, Yuan, Float;
+    MainRevenue = 602, "主营收入", "main_revenue", Fundamental, Yuan, Float;
+    OperatingProfit = 605, "营业利润", "operating_profit", Fundamental, Yuan, Float;
+    TotalProfit = 615, "利润总额", "total_profit", Fundamental, Yuan, Float;
+    NetProfit1 = 619, "净利润1", "net_profit_1", Fundamental, Yuan, Float;
+    SubscriptionLimit = 672, "申购限额(万股)", "subscription_limit", Fundamental, None, Float;
+    ActualChangePct = 675, "实际涨幅", "actual_change_pct", Quote, Percent, Float;
+    FirstDayAmplitude = 676, "首日振幅", "first_day_amplitude", Quote, Percent, Float;
+    ImpliedVolatility = 873, "引伸波幅", "implied_volatility", BondOption, Percent, Float;
+    Delta = 874, "对冲值", "delta", BondOption, None, Float;
+    StreetRatio = 875, "街货占比", "street_ratio", BondOption, Percent, Float;
+    StreetVolume = 876, "街货量", "street_volume", BondOption, Share, Integer;
+    LastTradeDate2 = 877, "最后交易日2", "last_trade_date_2", BondOption, None, Date;
+    CallPrice = 879, "回收价", "call_price", BondOption, Yuan, Float;
+    CbbcType = 880, "牛熊证种类", "cbbc_type", BondOption, None, Text;
+    Underlying = 881, "标的证券", "underlying", BondOption, None, Text;
+    WarrantType = 882, "权证类型", "warrant_type", BondOption, None, Text;
+    ExercisePrice = 887, "行使价", "exercise_price", BondOption, Yuan, Float;
+    ConversionRatio1 = 888, "换股比率1", "conversion_ratio_1", BondOption, None, Float;
+    MaturityDate = 890, "到期日", "maturity_date", BondOption, None, Date;
+    FinancialData = 899, "财务数据项", "financial_data", Fundamental, None, Text;
+    FloatSharesChange = 900, "流通股变动量", "float_shares_change", Fundamental, Share, Float;
+    HkdCnyRate = 981, "港元人民币汇率", "hkd_cny_rate", Quote, None, Float;
+    Eps = 1002, "每股收益", "eps", Fundamental, Yuan, Float;
+    Bps = 1005, "每股净资产", "bps", Fundamental, Yuan, Float;
+    Roe = 1015, "净资产收益率", "roe", Fundamental, Percent, Float;
+    BondSize = 1024, "债券规模", "bond_size", BondOption, Yuan, Float;
+    ConversionStartDate = 1047, "转股起始日", "conversion_start_date", BondOption, None, Date;
+    StarRating = 1110, "星级", "star_rating", Other, None, Integer;
+    Mark = 1121, "标记", "mark", Other, None, Text;
+    InterestRate = 1322, "利率", "interest_rate", BondOption, Percent, Float;
+    MarginBalance = 1384, "融资余额", "margin_balance", MoneyFlow, Yuan, Float;
+    ShortBalance = 1385, "融券余额", "short_balance", MoneyFlow, Yuan, Float;
+    MarginBuy = 1386, "融资买入", "margin_buy", MoneyFlow, Yuan, Float;
+    ShortSell = 1387, "融券卖出", "short_sell", MoneyFlow, None, Float;
+    NetProfit2 = 1566, "净利润2", "net_profit_2", Fundamental, Yuan, Float;
+    IssuePrice = 1606, "发行价", "issue_price", Fundamental, Yuan, Float;
+    AllotmentRate = 1612, "中签率", "allotment_rate", Fundamental, Percent, Float;
+    ShareholderCount = 1670, "股东总数", "shareholder_count", Fundamental, Count, Integer;
+    FloatAShares = 1674, "流通A股", "float_a_shares", Fundamental, Share, Float;
+    Rating = 2026, "评级", "rating", Other, None, Text;
+    BondValue = 2039, "纯债价值", "bond_value", BondOption, Yuan, Float;
+    OptionValue = 2041, "期权价值", "option_value", BondOption, Yuan, Float;
+    SellSignal = 2570, "卖出信号", "sell_signal", Other, None, Integer;
+    InstitutionRatio = 2579, "机构持股比例", "institution_ratio", Fundamental, Percent, Float;
+    AvgHoldingPerShareholder = 2719, "人均持股数", "avg_holding_per_shareholder", Fundamental, Share, Float;
+    PeDynamic1 = 2942, "市盈率(动态)1", "pe_dynamic_1", Fundamental, Multiple, Float;
+    PeStatic = 2946, "市盈率(静态)", "pe_static", Fundamental, Multiple, Float;
+    Pb1 = 2947, "市净率1", "pb_1", Fundamental, Multiple, Float;
+    PeTtm = 3153, "市盈率TTM", "pe_ttm", Fundamental, Multiple, Float;
+    Change5d = 3250, "5日涨幅", "change_5d", Quote, Percent, Float;
+    Change10d = 3251, "10日涨幅", "change_10d", Quote, Percent, Float;
+    Change20d = 3252, "20日涨幅", "change_20d", Quote, Percent, Float;
+    Nav = 3397, "净值", "nav", Fundamental, Yuan, Float;
+    PremiumRate = 9810, "溢价率", "premium_rate", BondOption, Percent, Float;
+    Timestamp = 32772, "时间戳", "timestamp", Quote, None, Time;
+    BlockMainNetVolume1 = 68107, "板块主力净量1", "block_main_net_volume_1", MoneyFlow, None, Float;
+    BlockMainInflow = 68166, "板块主力流入", "block_main_inflow", MoneyFlow, Yuan, Float;
+    BlockMainOutflow = 68167, "板块主力流出", "block_main_outflow", MoneyFlow, Yuan, Float;
+    BlockMainNetInflow = 68213, "板块主力净流入", "block_main_net_inflow", MoneyFlow, Yuan, Float;
+    BlockMainNetVolume2 = 68285, "板块主力净量2", "block_main_net_volume_2", MoneyFlow, None, Float;
+    BlockOpen = 68759, "板块开盘价", "block_open", Quote, Yuan, Float;
+    SubIndustry = 133702, "细分行业", "sub_industry", Fundamental, None, Text;
+    Basis = 133778, "基差", "basis", BondOption, None, Float;
+    DailyOiChange1 = 133964, "日增仓1", "daily_oi_change_1", BondOption, None, Float;
+    PsTtm = 134071, "市销率TTM", "ps_ttm", Fundamental, Multiple, Float;
+    RoeTtm = 134072, "净资产收益率TTM", "roe_ttm", Fundamental, Percent, Float;
+    NetProfitGrowth = 134141, "净利润增长率", "net_profit_growth", Fundamental, Percent, Float;
+    RevenueGrowth = 134143, "营业收入增长率", "revenue_growth", Fundamental, Percent, Float;
+    PeStatic2 = 134152, "市盈率(静态)2", "pe_static_2", Fundamental, Multiple, Float;
+    ConversionRatio2 = 134160, "换股比率2", "conversion_ratio_2", BondOption, None, Float;
+    DiscountPremiumRate = 134162, "折溢率", "discount_premium_rate", BondOption, Percent, Float;
+    LeverageRatio = 134237, "杠杆比率", "leverage_ratio", BondOption, Multiple, Float;
+    Premium = 134238, "溢价", "premium", BondOption, Percent, Float;
+    ChangePct = 199112, "涨幅", "change_pct", Quote, Percent, Float;
+    LargeNetVolume = 199643, "大单净量", "large_net_volume", MoneyFlow, None, Float;
+    Change = 264648, "涨跌", "change", Quote, Yuan, Float;
+    AnomalyType2 = 330321, "异动类型2", "anomaly_type_2", Quote, None, Integer;
+    AuctionRating1 = 330322, "竞价评级1", "auction_rating_1", Quote, None, Text;
+    LimitUpType = 330325, "涨停类型", "limit_up_type", Quote, None, Text;
+    LimitUpStatus = 330329, "涨停状态", "limit_up_status", Quote, None, Text;
+    MainPositionRatio1d = 331070, "今日主力增仓占比", "main_position_ratio_1d", MoneyFlow, Percent, Float;
+    MainPositionRatio2d = 331077, "2日主力增仓占比", "main_position_ratio_2d", MoneyFlow, Percent, Float;
+    MainPositionRatio3d = 331078, "3日主力增仓占比", "main_position_ratio_3d", MoneyFlow, Percent, Float;
+    MainPositionRatio5d = 331079, "5日主力增仓占比", "main_position_ratio_5d", MoneyFlow, Percent, Float;
+    MainPositionRatio10d = 331080, "10日主力增仓占比", "main_position_ratio_10d", MoneyFlow, Percent, Float;
+    MainPositionRank2d = 331124, "2日主力增仓排名", "main_position_rank_2d", MoneyFlow, None, Integer;
+    MainPositionRank3d = 331125, "3日主力增仓排名", "main_position_rank_3d", MoneyFlow, None, Integer;
+    MainPositionRank5d = 331126, "5日主力增仓排名", "main_position_rank_5d", MoneyFlow, None, Integer;
+    MainPositionRank10d = 331127, "10日主力增仓排名", "main_position_rank_10d", MoneyFlow, None, Integer;
+    MainPositionRank1d = 331128, "今日主力增仓排名", "main_position_rank_1d", MoneyFlow, None, Integer;
+    OrderDiff = 395720, "委差", "order_diff", OrderBook, Share, Integer;
+    CommissionRatio = 461256, "委比", "commission_ratio", OrderBook, Percent, Float;
+    ChangeYtd = 461346, "年初至今涨幅", "change_ytd", Quote, Percent, Float;
+    Speed10m = 461438, "涨速(10分钟)", "speed_10m", Quote, Percent, Float;
+    Speed15m = 461439, "涨速(15分钟)", "speed_15m", Quote, Percent, Float;
+    RetailCount = 462057, "散户数量", "retail_count", MoneyFlow, Count, Float;
+    Amplitude = 526792, "振幅", "amplitude", Quote, Percent, Float;
+    Unknown527198 = 527198, "未知字段527198", "unknown_527198", Other, None, Float;
+    Speed3m = 527526, "涨速(3分钟)", "speed_3m", Quote, Percent, Float;
+    Speed1m = 527527, "涨速(1分钟)", "speed_1m", Quote, Percent, Float;
+    Contribution = 592544, "贡献度", "contribution", Quote, None, Float;
+    Pb2 = 592920, "市净率2", "pb_2", Fundamental, Multiple, Float;
+    InstitutionTrend = 592741, "机构动向", "institution_trend", MoneyFlow, None, Float;
+    MainNetVolume = 592888, "主力净量", "main_net_volume", MoneyFlow, None, Float;
+    MainNetInflow = 592890, "主力净流入", "main_net_inflow", MoneyFlow, Yuan, Float;
+    LargeNetVolume5d = 592893, "5日大单净量", "large_net_volume_5d", MoneyFlow, None, Float;
+    LargeNetVolume10d = 592894, "10日大单净量", "large_net_volume_10d", MoneyFlow, None, Float;
+    LongShortRatio = 592946, "多空比", "long_short_ratio", MoneyFlow, None, Float;
+    ReservePerShare = 625362, "每股公积金", "reserve_per_share", Fundamental, Yuan, Float;
+    DealCount2 = 625295, "成交笔数2", "deal_count_2", Quote, Count, Integer;
+    GoldenCrossCount = 658784, "金叉个数", "golden_cross_count", Other, Count, Integer;
+    PositiveNews = 658785, "利好", "positive_news", Other, None, Integer;
+    NegativeNews = 658786, "利空", "negative_news", Other, None, Integer;
+    OpenChangePct = 920371, "开盘涨幅", "open_change_pct", Quote, Percent, Float;
+    BodyChangePct = 920372, "实体涨幅", "body_change_pct", Quote, Percent, Float;
+    StockClassFlag = 920428, "股票分类标记", "stock_class_flag", Other, None, Integer;
+    Pb3 = 1149395, "市净率3", "pb_3", Fundamental, Multiple, Float;
+    AvgPrice = 1378761, "均价", "avg_price", Quote, Yuan, Float;
+    AvgHoldingPerHousehold = 1509847, "户均持股数", "avg_holding_per_household", Fundamental, Share, Float;
+    LotsPerTrade = 1640904, "手每笔", "lots_per_trade", Quote, Lot, Float;
+    VolumeRatio = 1771976, "量比", "volume_ratio", Quote, Multiple, Float;
+    TurnoverRate = 1968584, "换手率", "turnover_rate", Quote, Percent, Float;
+    ChangePctHk = 1991120, "涨幅(港)", "change_pct_hk", Quote, Percent, Float;
+    PeDynamic2 = 2034120, "市盈率(动态)2", "pe_dynamic_2", Fundamental, Multiple, Float;
+    DebtRatio = 2034121, "资产负债率", "debt_ratio", Fundamental, Percent, Float;
+    FloatShares2 = 2097453, "流通股", "float_shares_2", Fundamental, Share, Float;
+    FloatRatio = 2263506, "流通比例", "float_ratio", Fundamental, Percent, Float;
+    AvgTradeAmount = 2427336, "均笔额", "avg_trade_amount", Quote, Yuan, Float;
+    HShareChange = 2646480, "H股涨跌", "h_share_change", Quote, Yuan, Float;
+    InnerVolume2 = 2820564, "内盘", "inner_volume_2", Quote, Share, Integer;
+    ChangePctSettlement = 3082712, "涨幅(结算)", "change_pct_settlement", BondOption, Percent, Float;
+    FloatMarketValue = 3475914, "流通市值", "float_market_value", Fundamental, Yuan, Float;
+    TotalMarketValue = 3541450, "总市值", "total_market_value", Fundamental, Yuan, Float;
+    BlockSpeed = 3934664, "板块涨速", "block_speed", Quote, Percent, Float;
+    BidPrice = 4065737, "买价", "bid_price", OrderBook, Yuan, Float;
+    DailyOiChange2 = 4099083, "日增仓2", "daily_oi_change_2", BondOption, None, Float;
+    AskPrice = 4131273, "卖价", "ask_price", OrderBook, Yuan, Float;
+    SmallInflow = 4525375, "小单流入", "small_inflow", MoneyFlow, Yuan, Float;
+    MediumInflow = 4525376, "中单流入", "medium_inflow", MoneyFlow, Yuan, Float;
+    LargeInflow = 4525377, "大单流入", "large_inflow", MoneyFlow, Yuan, Float;
+    FundSizeRatio1 = 7000001, "占基金规模1", "fund_size_ratio_1", Fundamental, Percent, Float;
+    HoldingChange1 = 7000002, "持股变动1", "holding_change_1", Fundamental, Share, Float;
+    FundSize1 = 7000003, "基金规模1", "fund_size_1", Fundamental, Yuan, Float;
+    Code2 = 7000004, "代码2", "code_2", Quote, None, Text;
+    ChangePercent = 7000005, "涨幅百分比", "change_percent", Quote, Percent, Float;
+    HoldingChange2 = 7000006, "持股变动2", "holding_change_2", Fundamental, Share, Float;
+    FundSizeRatio2 = 7000007, "占基金规模2", "fund_size_ratio_2", Fundamental, Percent, Float;
+    Performance = 7000008, "业绩表现", "performance", Fundamental, Percent, Float;
+    Return1y = 7000009, "近一年收益", "return_1y", Fundamental, Percent, Float;
+    Return1w = 7000010, "近一周收益", "return_1w", Fundamental, Percent, Float;
+    Return1m = 7000011, "近一月收益", "return_1m", Fundamental, Percent, Float;
+    Return3m = 7000012, "近三月收益", "return_3m", Fundamental, Percent, Float;
+    ReturnYtd = 7000013, "今年以来收益", "return_ytd", Fundamental, Percent, Float;
+    ReturnSinceInception = 7000014, "成立以来收益", "return_since_inception", Fundamental, Percent, Float;
+    ProductType = 7000015, "产品类型", "product_type", Fundamental, None, Text;
+    FundSize2 = 7000016, "基金规模2", "fund_size_2", Fundamental, Yuan, Float;
+    FundCompany = 7000017, "基金公司", "fund_company", Fundamental, None, Text;
+    InvestmentType = 7000018, "投资类型", "investment_type", Fundamental, None, Text;
+    FundManager = 7000019, "基金经理", "fund_manager", Fundamental, None, Text;
+    AssetRatio = 7000020, "资产占比", "asset_ratio", Fundamental, Percent, Float;
+    ChangeFromPrevious = 7000021, "较上期", "change_from_previous", Fundamental, None, Float;
+    Category = 8311855, "类别", "category", Other, None, Text;
+    SmallOutflow = 8719679, "小单流出", "small_outflow", MoneyFlow, Yuan, Float;
+    MediumOutflow = 8719680, "中单流出", "medium_outflow", MoneyFlow, Yuan, Float;
+    LargeOutflow = 8719681, "大单流出", "large_outflow", MoneyFlow, Yuan, Float;
+    AShareThemes = 12345671, "A股关联主题", "a_share_themes", Fundamental, None, Text;
+    SmallNetAmount = 12913983, "小单净额", "small_net_amount", MoneyFlow, Yuan, Float;
+    MediumNetAmount = 12913984, "中单净额", "medium_net_amount", MoneyFlow, Yuan, Float;
+    LargeNetAmount = 12913985, "大单净额", "large_net_amount", MoneyFlow, Yuan, Float;
+    SmallNetRatio = 17108287, "小单净额占比", "small_net_ratio", MoneyFlow, Percent, Float;
+    MediumNetRatio = 17108288, "中单净额占比", "medium_net_ratio", MoneyFlow, Percent, Float;
+    LargeNetRatio = 17108289, "大单净额占比", "large_net_ratio", MoneyFlow, Percent, Float;
+    ComponentCount = 18550831, "成分股数", "component_count", Quote, Count, Integer;
+    Tags = 20190901, "标签", "tags", Other, None, Text;
+    SmallTotalAmount = 21302591, "小单总额", "small_total_amount", MoneyFlow, Yuan, Float;
+    MediumTotalAmount = 21302592, "中单总额", "medium_total_amount", MoneyFlow, Yuan, Float;
+    LargeTotalAmount = 21302593, "大单总额", "large_total_amount", MoneyFlow, Yuan, Float;
+    SmallTotalRatio = 25496895, "小单总额占比", "small_total_ratio", MoneyFlow, Percent, Float;
+    MediumTotalRatio = 25496896, "中单总额占比", "medium_total_ratio", MoneyFlow, Percent, Float;
+    LargeTotalRatio = 25496897, "大单总额占比", "large_total_ratio", MoneyFlow, Percent, Float;
+    ComputedData = 189546735, "计算数据项", "computed_data", Other, None, Text;
+    AuctionRating2 = 2018090319, "竞价评级2", "auction_rating_2", Quote, None, Text;
+    AuctionAnomalyDesc = 2018090320, "竞价异动说明", "auction_anomaly_desc", Quote, None, Text;
+    AnomalyDesc = 2018090410, "异动说明", "anomaly_desc", Quote, None, Text;
+    AuctionChangePct = 2018090411, "竞价涨幅", "auction_change_pct", Quote, Percent, Float;
+}
+
+lazy_static! {
+    static ref BY_ID: HashMap<i32, Field> = Field::ALL.iter().map(|f| (f.id(), *f)).collect();
+    static ref BY_NAME: HashMap<&'static str, Field> = Field::ALL.iter().map(|f| (f.name(), *f)).collect();
+    static ref BY_ALIAS: HashMap<&'static str, Field> = Field::ALL.iter().map(|f| (f.alias(), *f)).collect();
+}
+
+impl Field {
+    pub fn info(self) -> &'static FieldInfo {
+        &FIELD_INFO[self as usize]
+    }
+
+    pub fn id(self) -> i32 {
+        self.info().id
+    }
+
+    pub fn name(self) -> &'static str {
+        self.info().name
+    }
+
+    pub fn alias(self) -> &'static str {
+        self.info().alias
+    }
+
+    pub fn category(self) -> Category {
+        self.info().category
+    }
+
+    pub fn unit(self) -> Unit {
+        self.info().unit
+    }
+
+    pub fn value_type(self) -> ValueType {
+        self.info().value_type
+    }
+
+    pub fn from_id(id: i32) -> Option<Field> {
+        BY_ID.get(&id).copied()
+    }
+
+    /// 按中文名称查找
+    pub fn from_name(name: &str) -> Option<Field> {
+        BY_NAME.get(name).copied()
+    }
+
+    /// 按英文别名查找
+    pub fn from_alias(alias: &str) -> Option<Field> {
+        BY_ALIAS.get(alias).copied()
+    }
+
+    /// 按数字 id、中文名称或英文别名查找
+    pub fn from_key(key: &str) -> Option<Field> {
+        match key.trim().parse::<i32>() {
+            Ok(id) => Field::from_id(id),
+            Err(_) => Field::from_name(key).or_else(|| Field::from_alias(key)),
+        }
+    }
+
+    /// 某一分类的全部字段
+    pub fn in_category(category: Category) -> impl Iterator<Item = Field> {
+        Field::ALL.iter().copied().filter(move |f| f.category() == category)
+    }
+
+    /// 按 `fields!` 中的字面量查找：带引号的是中文名称或英文别名，其余是数字 id，在编译期求值
+    #[doc(hidden)]
+    pub const fn __from_literal(literal: &str) -> Field {
+        let found = match literal.as_bytes() {
+            [b'"', text @ .., b'"'] => find_text(text),
+            digits => find_id(digits),
+        };
+        match found {
+            Some(field) => field,
+            None => panic!("fields! 中有未知的字段"),
+        }
+    }
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// 按中文名称或英文别名查找，供编译期使用
+const fn find_text(text: &[u8]) -> Option<Field> {
+    let mut i = 0;
+    while i < FIELD_INFO.len() {
+        if bytes_eq(FIELD_INFO[i].name.as_bytes(), text) || bytes_eq(FIELD_INFO[i].alias.as_bytes(), text) {
+            return Some(Field::ALL[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// 按十进制的数字 id 查找，供编译期使用
+const fn find_id(digits: &[u8]) -> Option<Field> {
+    if digits.is_empty() {
+        return None;
+    }
+    let mut id: i64 = 0;
+    let mut i = 0;
+    while i < digits.len() {
+        if !digits[i].is_ascii_digit() || id > i32::MAX as i64 {
+            return None;
+        }
+        id = id * 10 + (digits[i] - b'0') as i64;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < FIELD_INFO.len() {
+        if FIELD_INFO[i].id as i64 == id {
+            return Some(Field::ALL[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Field {
+    type Err = THSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Field::from_key(s).ok_or_else(|| THSError::UnknownField(s.to_string()))
+    }
+}
+
+impl From<Field> for i32 {
+    fn from(field: Field) -> Self {
+        field.id()
+    }
+}
+
+/// 可以转换为 [`Field`] 的类型：`Field`、数字 id、中文名称或英文别名
+pub trait IntoField {
+    fn into_field(self) -> Result<Field, THSError>;
+}
+
+impl IntoField for Field {
+    fn into_field(self) -> Result<Field, THSError> {
+        Ok(self)
+    }
+}
+
+impl IntoField for i32 {
+    fn into_field(self) -> Result<Field, THSError> {
+        Field::from_id(self).ok_or_else(|| THSError::UnknownField(self.to_string()))
+    }
+}
+
+impl IntoField for &str {
+    fn into_field(self) -> Result<Field, THSError> {
+        self.parse()
+    }
+}
+
+impl IntoField for &String {
+    fn into_field(self) -> Result<Field, THSError> {
+        self.parse()
+    }
+}
+
+impl IntoField for String {
+    fn into_field(self) -> Result<Field, THSError> {
+        self.parse()
+    }
+}
+
+/// 按名称、别名或 id 的字面量列出字段，得到 `[Field; N]`
+///
+/// 字段在编译期查找，未知的字段无法通过编译；运行时的输入用 [`Field::from_str`] 或 [`IntoField`]。
+///
+/// ```compile_fail
+/// let _ = rusths::fields!["不存在的字段"];
+/// ```
+#[macro_export]
+macro_rules! fields {
+    ($($key:literal),* $(,)?) => {
+        [$(const { $crate::fields::Field::__from_literal(::core::stringify!($key)) }),*]
+    };
+}
+
+/// 字段列表对应的 datatype
+pub fn ids(fields: &[Field]) -> Vec<i32> {
+    fields.iter().map(|f| f.id()).collect()
+}
+
+/// 按字段解析的一行返回数据
+///
+/// 键可以是数字 id 或中文名称，无法识别的键保留在 [`Record::other`] 中。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Record {
+    values: HashMap<Field, Value>,
+    other: Map<String, Value>,
+}
+
+impl Record {
+    pub fn from_row(row: &Value) -> Result<Self, THSError> {
+        let obj = row.as_object()
+            .ok_or_else(|| THSError::decode("返回数据格式错误", format!("应为对象: {}", row)))?;
+
+        let mut record = Record::default();
+        for (key, value) in obj {
+            match Field::from_key(key) {
+                Some(field) => {
+                    record.values.insert(field, value.clone());
+                }
+                None => {
+                    record.other.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(record)
+    }
+
+    pub fn get(&self, field: Field) -> Option<&Value> {
+        self.values.get(&field)
+    }
+
+    /// 数字或数字形式的字符串
+    pub fn get_f64(&self, field: Field) -> Option<f64> {
+        self.get(field).and_then(value_f64)
+    }
+
+    pub fn get_str(&self, field: Field) -> Option<&str> {
+        self.get(field).and_then(Value::as_str)
+    }
+
+    /// 包含的字段
+    pub fn fields(&self) -> impl Iterator<Item = Field> + '_ {
+        self.values.keys().copied()
+    }
+
+    /// 无法识别的键
+    pub fn other(&self) -> &Map<String, Value> {
+        &self.other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::FIELD_NAME_MAP;
+
+    #[test]
+    fn all_fields_round_trip() {
+        assert_eq!(FIELD_NAME_MAP.len(), Field::ALL.len());
+        for &field in Field::ALL {
+            assert_eq!(FIELD_NAME_MAP.get(&field.id()), Some(&field.name()));
+            assert_eq!(Field::from_id(field.id()), Some(field), "{:?}", field);
+            assert_eq!(Field::from_name(field.name()), Some(field), "{:?} 的名称重复", field);
+            assert_eq!(Field::from_alias(field.alias()), Some(field), "{:?} 的别名重复", field);
+        }
+    }
+
+    #[test]
+    fn duplicate_name_is_disambiguated() {
+        assert_eq!(Field::from_name("委比"), Some(Field::CommissionRatio));
+        assert_eq!(Field::from_name("委比(53)"), Some(Field::CommissionRatioAlt));
+        assert_eq!(Field::from_id(53), Some(Field::CommissionRatioAlt));
+    }
+
+    #[test]
+    fn macro_accepts_names_aliases_and_ids() {
+        assert_eq!(
+            fields!["涨幅", "turnover_rate", 461256, "委比(53)"],
+            [Field::ChangePct, Field::TurnoverRate, Field::CommissionRatio, Field::CommissionRatioAlt]
+        );
+    }
+
+    #[test]
+    fn lookup_by_key() {
+        assert_eq!("199112".parse::<Field>().unwrap(), Field::ChangePct);
+        assert_eq!(" 199112 ".into_field().unwrap(), Field::ChangePct);
+        assert!(matches!("不存在".parse::<Field>(), Err(THSError::UnknownField(_))));
+        assert!(matches!(0.into_field(), Err(THSError::UnknownField(_))));
+    }
+
+    #[test]
+    fn record_keys_by_id_or_name() {
+        let row = serde_json::json!({"5": "600000", "涨幅": "1.5", "未知": 1});
+        let record = Record::from_row(&row).unwrap();
+        assert_eq!(record.get_str(Field::Code), Some("600000"));
+        assert_eq!(record.get_f64(Field::ChangePct), Some(1.5));
+        assert_eq!(record.other().get("未知"), Some(&serde_json::json!(1)));
+        assert!(Record::from_row(&serde_json::json!([1])).is_err());
+    }
+}
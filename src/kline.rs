@@ -0,0 +1,51 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::ths::Interval;
+
+/// 分段获取K线的参数，见 [`THS::klines_range_with`](crate::ths::THS::klines_range_with)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RangeOptions {
+    /// 单次请求的最多K线数量，按此估算每段的时间跨度
+    pub chunk_bars: usize,
+    /// 连续这么多段没有数据时认为已经到了最早的数据，停止向前获取；默认为 `None`，一直获取到起始时间
+    ///
+    /// 设置后可以减少上市前时间段的空请求，但停牌时间超过这个跨度的证券会丢失停牌前的数据，
+    /// 提前停止时会记录一条警告日志。
+    pub max_empty_chunks: Option<usize>,
+}
+
+impl Default for RangeOptions {
+    fn default() -> Self {
+        Self {
+            chunk_bars: 4000,
+            max_empty_chunks: None,
+        }
+    }
+}
+
+impl RangeOptions {
+    /// 每段请求的时间跨度
+    ///
+    /// 按每个自然日都是交易日估算，实际的K线数量不会超过 `chunk_bars`。
+    pub fn chunk_span(&self, interval: Interval) -> Duration {
+        let bars = self.chunk_bars.max(1) as f64;
+        let days = bars / bars_per_day(interval);
+        Duration::minutes((days * 24.0 * 60.0).ceil().max(1.0) as i64)
+    }
+}
+
+/// 一个交易日中的K线数量，A 股每天交易 240 分钟；周线及以上按每周 5 个交易日、每月 21 个交易日折算
+pub fn bars_per_day(interval: Interval) -> f64 {
+    match interval.minutes() {
+        Some(minutes) => 240.0 / minutes as f64,
+        None => match interval {
+            Interval::Week => 1.0 / 5.0,
+            Interval::Month => 1.0 / 21.0,
+            Interval::Quarter => 1.0 / 63.0,
+            Interval::Year => 1.0 / 250.0,
+            _ => 1.0,
+        },
+    }
+}
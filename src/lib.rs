@@ -1,6 +1,23 @@
-
+#[cfg(feature = "async")]
+pub mod async_ths;
+pub mod batch;
+pub mod code;
 pub mod constants;
 pub mod error;
+pub mod factor;
+pub mod fields;
+pub mod kline;
+pub mod query;
+pub mod replay;
+pub mod resample;
+pub mod retry;
+pub mod session;
+pub mod store;
+pub mod subscription;
 pub mod ths;
+pub mod timeout;
+pub mod transport;
 pub mod types;
+pub mod worker;
 pub mod guest;
+pub mod library;
@@ -0,0 +1,222 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants::LIB_DIR_ENV;
+
+/// 当前平台动态库文件的扩展名
+#[cfg(target_os = "linux")]
+pub const LIB_EXTENSION: &str = "so";
+#[cfg(target_os = "macos")]
+pub const LIB_EXTENSION: &str = "dylib";
+#[cfg(target_os = "windows")]
+pub const LIB_EXTENSION: &str = "dll";
+
+/// 客户端实际使用的动态库
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryInfo {
+    /// 动态库的路径
+    pub path: PathBuf,
+    /// 动态库的版本号，即文件名 `hq{version}` 中的部分，可能为空
+    pub version: String,
+    /// 请求中使用的 zipversion，配置中没有指定时为连接后协商的结果
+    pub zip_version: i32,
+}
+
+/// 一个候选的动态库文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Candidate {
+    pub path: PathBuf,
+    pub version: String,
+}
+
+/// 版本号对应的文件名，如 "hq116.so"
+pub fn lib_file_name(version: &str) -> String {
+    format!("hq{}.{}", version, LIB_EXTENSION)
+}
+
+/// 从文件名中取出版本号，不是 `hq*.{扩展名}` 格式时返回 `None`
+fn version_of(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(LIB_EXTENSION)?.strip_suffix('.')?;
+    let version = stem.strip_prefix("hq")?;
+    version.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-').then_some(version)
+}
+
+/// 比较两个版本号，按其中的数字段依次比较，没有数字的版本最旧
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |v: &str| -> Vec<u64> {
+        v.split(|c: char| !c.is_ascii_digit())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().unwrap_or(u64::MAX))
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+}
+
+/// 动态库的搜索目录，依次为 `lib_dir`、环境变量 `RUSTHS_LIB_DIR` 中的目录（可以有多个，按平台的路径分隔符分隔）
+/// 和当前目录下的 `lib` 目录
+pub fn search_dirs(lib_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = lib_dir {
+        dirs.push(dir.to_path_buf());
+    }
+    if let Some(paths) = std::env::var_os(LIB_DIR_ENV) {
+        dirs.extend(std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        dirs.push(dir.join("lib"));
+    }
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| seen.insert(dir.clone()));
+    dirs
+}
+
+/// 扫描目录中的 `hq*` 动态库，按版本从新到旧排列，相同版本时靠前目录中的优先
+pub fn discover(dirs: &[PathBuf]) -> Vec<(String, PathBuf)> {
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        let mut in_dir: Vec<_> = entries
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| {
+                let name = e.file_name();
+                let version = version_of(name.to_str()?)?.to_string();
+                Some((version, e.path()))
+            })
+            .filter(|(version, _)| !found.iter().any(|(v, _)| v == version))
+            .collect();
+        in_dir.sort_by(|a, b| a.1.cmp(&b.1));
+        found.extend(in_dir);
+    }
+    found.sort_by(|a, b| compare_versions(&b.0, &a.0));
+    found
+}
+
+/// 按配置确定候选的动态库，排在前面的优先使用
+///
+/// 指定了 `lib_path` 时只使用该文件；指定了 `lib_ver` 时使用搜索目录中第一个该版本的文件；
+/// 否则扫描全部搜索目录，从最新的版本开始尝试。
+pub(crate) fn candidates(lib_path: Option<&Path>, lib_dir: Option<&Path>, lib_ver: &str) -> Vec<Candidate> {
+    if let Some(path) = lib_path {
+        return vec![Candidate { path: path.to_path_buf(), version: lib_ver.to_string() }];
+    }
+
+    let dirs = search_dirs(lib_dir);
+    if !lib_ver.is_empty() {
+        let name = lib_file_name(lib_ver);
+        let path = dirs.iter()
+            .map(|dir| dir.join(&name))
+            .find(|path| path.is_file())
+            .or_else(|| dirs.first().map(|dir| dir.join(&name)))
+            .unwrap_or_else(|| PathBuf::from(&name));
+        return vec![Candidate { path, version: lib_ver.to_string() }];
+    }
+
+    discover(&dirs)
+        .into_iter()
+        .map(|(version, path)| Candidate { path, version })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// 测试结束时删除的临时目录
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rusths_library_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn touch(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, b"").unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert_eq!(compare_versions("116", "99"), Ordering::Greater);
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2", "2.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("", "1"), Ordering::Less);
+        assert_eq!(compare_versions("99", "99"), Ordering::Equal);
+    }
+
+    #[test]
+    fn candidate_file_names() {
+        assert_eq!(lib_file_name("116"), format!("hq116.{}", LIB_EXTENSION));
+        assert_eq!(version_of(&lib_file_name("116")), Some("116"));
+        assert_eq!(version_of(&lib_file_name("")), Some(""));
+        assert_eq!(version_of(&lib_file_name("1.2_a-b")), Some("1.2_a-b"));
+        assert_eq!(version_of(&format!("libhq116.{}", LIB_EXTENSION)), None);
+        assert_eq!(version_of(&format!("hq 116.{}", LIB_EXTENSION)), None);
+        assert_eq!(version_of("hq116.txt"), None);
+        assert_eq!(version_of(&format!("hq116{}", LIB_EXTENSION)), None);
+    }
+
+    #[test]
+    fn discover_orders_by_version_and_directory() {
+        let first = TempDir::new("first");
+        let second = TempDir::new("second");
+        let hq99 = first.touch(&lib_file_name("99"));
+        first.touch("readme.txt");
+        fs::create_dir(first.0.join(lib_file_name("200"))).unwrap();
+        let hq116 = second.touch(&lib_file_name("116"));
+        second.touch(&lib_file_name("99"));
+
+        let dirs = [first.0.clone(), second.0.join("missing"), second.0.clone()];
+        assert_eq!(discover(&dirs), vec![("116".to_string(), hq116), ("99".to_string(), hq99)]);
+    }
+
+    #[test]
+    fn candidates_follow_options() {
+        let dir = TempDir::new("candidates");
+        // 版本号足够大，不受当前目录下 lib 中动态库的影响
+        let newest = dir.touch(&lib_file_name("99999"));
+        dir.touch(&lib_file_name("99"));
+
+        let explicit = Path::new("/opt/hq.so");
+        assert_eq!(
+            candidates(Some(explicit), Some(&dir.0), "7"),
+            vec![Candidate { path: explicit.to_path_buf(), version: "7".into() }]
+        );
+
+        let pinned = candidates(None, Some(&dir.0), "99");
+        assert_eq!(pinned, vec![Candidate { path: dir.0.join(lib_file_name("99")), version: "99".into() }]);
+
+        // 没有该版本时仍返回第一个目录中的文件名，加载时报告找不到文件
+        let missing = candidates(None, Some(&dir.0), "1");
+        assert_eq!(missing[0].path, dir.0.join(lib_file_name("1")));
+
+        let all = candidates(None, Some(&dir.0), "");
+        assert_eq!(all[0], Candidate { path: newest, version: "99999".into() });
+    }
+
+    #[test]
+    fn search_dirs_are_unique() {
+        let lib = std::env::current_dir().unwrap().join("lib");
+        let dirs = search_dirs(Some(&lib));
+        assert_eq!(dirs[0], lib);
+        assert_eq!(dirs.iter().filter(|d| **d == lib).count(), 1);
+    }
+}
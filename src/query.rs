@@ -0,0 +1,294 @@
+//! `cmd.query_data.*` 查询请求的构造
+//!
+//! 请求参数是 `key=value` 以 `&` 连接后再用双引号包起来的字符串，如
+//! `"id=200&instance=1&zipversion=2&codelist=600000&market=USHA&datatype=5,10"`。
+//! [`QueryRequest`] 负责拼接和编码，参数值中的 `&`、`=`、`"` 等字符会被百分号编码，不会破坏请求的结构。
+
+use std::fmt::Write as _;
+
+use crate::code::SecurityCode;
+
+/// 查询服务，对应方法名 `cmd.query_data.{key}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Service {
+    /// 行情快照，`id=200`
+    Fu,
+    /// 板块，`id=7`
+    Bk,
+    /// 成交明细和分时，`id=205/207/220`
+    Zhu,
+}
+
+impl Service {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Service::Fu => "fu",
+            Service::Bk => "bk",
+            Service::Zhu => "zhu",
+        }
+    }
+
+    /// 请求 id 默认使用的服务，未知的 id 使用 `Zhu`
+    pub fn for_id(id: u32) -> Self {
+        match id {
+            200 => Service::Fu,
+            7 => Service::Bk,
+            _ => Service::Zhu,
+        }
+    }
+}
+
+/// 排序方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "A",
+            SortOrder::Desc => "D",
+        }
+    }
+}
+
+/// 由 [`THS`](crate::ths::THS) 在发送时填写的参数，通过 [`QueryRequest::param`] 设置会被忽略
+const RESERVED_KEYS: [&str; 3] = ["id", "instance", "zipversion"];
+
+/// 默认的输出缓冲区大小
+const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024 * 2;
+
+/// `cmd.query_data.*` 查询请求
+///
+/// ```ignore
+/// let req = QueryRequest::new(205)
+///     .code(&"USHA600000".parse()?)
+///     .range(start, end)
+///     .datatypes(&[1, 5, 10]);
+/// let response = ths.query_raw(&req)?;
+/// ```
+///
+/// 参数按设置的顺序输出，重复设置同一个参数时覆盖原来的值。`id`、`instance` 和 `zipversion`
+/// 在发送时填写。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryRequest {
+    id: u32,
+    service: Service,
+    params: Vec<(String, String)>,
+    buffer_size: usize,
+}
+
+impl QueryRequest {
+    /// 创建请求，服务按 `id` 选择，见 [`Service::for_id`]
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            service: Service::for_id(id),
+            params: Vec::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// 指定服务
+    pub fn service(mut self, service: Service) -> Self {
+        self.service = service;
+        self
+    }
+
+    pub fn service_key(&self) -> &'static str {
+        self.service.key()
+    }
+
+    /// 输出缓冲区大小，默认 2MB
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    pub fn output_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// 设置参数，值会被编码
+    pub fn param(mut self, key: &str, value: impl ToString) -> Self {
+        self.set(key, value.to_string());
+        self
+    }
+
+    /// 单个证券，设置 `code` 和 `market`
+    pub fn code(self, code: &SecurityCode) -> Self {
+        self.param("code", code.short_code()).param("market", code.market())
+    }
+
+    /// 同一市场的多个代码，设置 `codelist` 和 `market`
+    pub fn codelist<S: AsRef<str>>(self, market: &str, short_codes: &[S]) -> Self {
+        self.param("codelist", join(short_codes.iter().map(AsRef::as_ref))).param("market", market)
+    }
+
+    /// 请求的 datatype 列表，可以是数字 id 或 [`Field`](crate::fields::Field)
+    pub fn datatypes<T: Copy + Into<i32>>(self, data_types: &[T]) -> Self {
+        self.param("datatype", join(data_types.iter().map(|&t| t.into())))
+    }
+
+    /// 时间范围，设置 `start` 和 `end`
+    pub fn range(self, start: i64, end: i64) -> Self {
+        self.param("start", start).param("end", end)
+    }
+
+    /// 日期，格式为 `YYYYMMDD`
+    pub fn date(self, date: &str) -> Self {
+        self.param("date", date)
+    }
+
+    /// 排序，设置 `sortbegin`、`sortcount`、`sortorder` 和 `sortid`，`count` 为 0 时返回全部
+    pub fn sort(self, sort_id: i32, order: SortOrder, begin: usize, count: usize) -> Self {
+        self.param("sortbegin", begin)
+            .param("sortcount", count)
+            .param("sortorder", order.as_str())
+            .param("sortid", sort_id)
+    }
+
+    /// 已设置的参数值，未编码
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        match self.params.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.params.push((key.to_string(), value)),
+        }
+    }
+
+    /// 编码为动态库的参数字符串，包括外层的双引号
+    pub fn encode(&self, instance: i32, zip_version: i32) -> String {
+        let mut out = format!("\"id={}&instance={}&zipversion={}", self.id, instance, zip_version);
+        for (key, value) in &self.params {
+            if RESERVED_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            out.push('&');
+            escape_into(&mut out, key);
+            out.push('=');
+            escape_into(&mut out, value);
+        }
+        out.push('"');
+        out
+    }
+}
+
+fn join<T: ToString>(items: impl Iterator<Item = T>) -> String {
+    items.map(|i| i.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// 百分号编码，保留字母、数字和 `-_.~,:`，逗号用于分隔列表
+fn escape_into(out: &mut String, value: &str) {
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~,:".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{:02X}", byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fields::Field;
+
+    fn code(s: &str) -> SecurityCode {
+        s.parse().unwrap()
+    }
+
+    /// 与原来手工拼接的请求字符串一致
+    #[test]
+    fn matches_hand_built_requests() {
+        let cases = [
+            (
+                QueryRequest::new(200).codelist("USHA", &["600000", "600004"]).datatypes(&[5, 10]),
+                "\"id=200&instance=7&zipversion=2&codelist=600000,600004&market=USHA&datatype=5,10\"",
+            ),
+            (
+                QueryRequest::new(7)
+                    .sort(55, SortOrder::Desc, 0, 0)
+                    .param("blockid", format!("{:x}", 0xce5f_i32))
+                    .param("reqflag", "blockserve"),
+                "\"id=7&instance=7&zipversion=2&sortbegin=0&sortcount=0&sortorder=D&sortid=55&blockid=ce5f&reqflag=blockserve\"",
+            ),
+            (
+                QueryRequest::new(7).sort(55, SortOrder::Desc, 0, 0).param("linkcode", "881272"),
+                "\"id=7&instance=7&zipversion=2&sortbegin=0&sortcount=0&sortorder=D&sortid=55&linkcode=881272\"",
+            ),
+            (
+                QueryRequest::new(205)
+                    .code(&code("USZA000001"))
+                    .range(1700000000, 1700003600)
+                    .datatypes(&[1, 5, 10, 12, 18, 49])
+                    .param("TraceDetail", 0),
+                "\"id=205&instance=7&zipversion=2&code=000001&market=USZA&start=1700000000&end=1700003600&datatype=1,5,10,12,18,49&TraceDetail=0\"",
+            ),
+            (
+                QueryRequest::new(220).code(&code("USHA600000")).range(-60, 0).datatypes(&[5, 10, 12, 13]),
+                "\"id=220&instance=7&zipversion=2&code=600000&market=USHA&start=-60&end=0&datatype=5,10,12,13\"",
+            ),
+            (
+                QueryRequest::new(207).code(&code("USHA600000")).datatypes(&[1, 10, 13, 19, 40]).date("20240105"),
+                "\"id=207&instance=7&zipversion=2&code=600000&market=USHA&datatype=1,10,13,19,40&date=20240105\"",
+            ),
+        ];
+        for (req, expected) in cases {
+            assert_eq!(req.encode(7, 2), expected);
+        }
+    }
+
+    #[test]
+    fn escapes_values() {
+        let req = QueryRequest::new(200)
+            .param("name", "a&b=c\"d")
+            .param("text", "浦发 银行%")
+            .param("list", "a,b:c-d_e.f~g");
+        assert_eq!(
+            req.encode(1, 2),
+            "\"id=200&instance=1&zipversion=2&name=a%26b%3Dc%22d&text=%E6%B5%A6%E5%8F%91%20%E9%93%B6%E8%A1%8C%25&list=a,b:c-d_e.f~g\""
+        );
+        // 未编码的值保持原样
+        assert_eq!(req.get("name"), Some("a&b=c\"d"));
+    }
+
+    #[test]
+    fn reserved_keys_are_dropped() {
+        let req = QueryRequest::new(200)
+            .param("id", 999)
+            .param("instance", 5)
+            .param("zipversion", 0)
+            .param("market", "USHA");
+        assert_eq!(req.encode(1, 2), "\"id=200&instance=1&zipversion=2&market=USHA\"");
+    }
+
+    #[test]
+    fn param_replaces_existing_value() {
+        let req = QueryRequest::new(205).param("start", 1).param("end", 2).param("start", 3);
+        assert_eq!(req.encode(1, 2), "\"id=205&instance=1&zipversion=2&start=3&end=2\"");
+        assert_eq!(req.get("start"), Some("3"));
+        assert_eq!(req.get("missing"), None);
+    }
+
+    #[test]
+    fn service_and_datatypes() {
+        assert_eq!(QueryRequest::new(200).service_key(), "fu");
+        assert_eq!(QueryRequest::new(7).service_key(), "bk");
+        assert_eq!(QueryRequest::new(205).service_key(), "zhu");
+        assert_eq!(QueryRequest::new(200).service(Service::Zhu).service_key(), "zhu");
+        assert_eq!(QueryRequest::new(200).buffer_size(1024).output_size(), 1024);
+
+        let req = QueryRequest::new(200).datatypes(&[Field::from_id(10).unwrap(), Field::from_id(199112).unwrap()]);
+        assert_eq!(req.get("datatype"), Some("10,199112"));
+    }
+}
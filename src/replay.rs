@@ -0,0 +1,267 @@
+//! 调用的录制和回放
+//!
+//! 录制：[`THS::start_recording`](crate::ths::THS::start_recording) 之后，每一次动态库调用的方法名、参数、
+//! 缓冲区大小、返回码和输出都以一行 JSON 追加到文件中。
+//!
+//! 回放：[`ReplayTransport`] 读取录制的文件，按方法和参数依次返回录制时的结果，可以在没有动态库和网络的
+//! 环境中重现一次会话。匹配时忽略参数中每次请求都不同的 `instance=` 编号，`connect` 只按方法名匹配。
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::THSError;
+use crate::transport::{CallStatus, Transport};
+
+/// 录制文件中的一次调用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub method: String,
+    pub params: Option<String>,
+    pub buffer_size: usize,
+    /// 动态库的返回码，0 为成功，-1 为缓冲区不足
+    pub return_code: i32,
+    /// 调用成功时的输出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl RecordedCall {
+    /// `connect` 参数中的密码会被替换掉，录制文件可以直接附在问题报告中
+    pub fn new(method: &str, params: Option<&str>, buffer_size: usize, status: &CallStatus) -> Self {
+        Self {
+            method: method.to_string(),
+            params: params.map(|p| redact_params(method, p)),
+            buffer_size,
+            return_code: status.code(),
+            output: match status {
+                CallStatus::Ok(output) => Some(output.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    /// 录制时的调用结果
+    pub fn status(&self) -> CallStatus {
+        match self.return_code {
+            0 => CallStatus::Ok(self.output.clone().unwrap_or_default()),
+            -1 => CallStatus::BufferTooSmall,
+            code => CallStatus::Error(code),
+        }
+    }
+}
+
+/// 隐藏 `connect` 参数中的密码，其它方法的参数原样返回，用于录制和日志
+pub(crate) fn redact_params(method: &str, params: &str) -> String {
+    if method != "connect" {
+        return params.to_string();
+    }
+    match serde_json::from_str::<serde_json::Value>(params) {
+        Ok(mut value) => {
+            if let Some(password) = value.get_mut("password") {
+                *password = serde_json::Value::String("******".into());
+            }
+            value.to_string()
+        }
+        Err(_) => params.to_string(),
+    }
+}
+
+/// 把调用追加写入 JSONL 文件
+pub struct Recorder {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl Recorder {
+    /// 打开录制文件，文件已存在时追加
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, THSError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { out: Mutex::new(BufWriter::new(file)) })
+    }
+
+    /// 写入一次调用，每次写入后立即刷新，进程崩溃时已录制的内容不会丢失
+    pub fn record(&self, call: &RecordedCall) -> Result<(), THSError> {
+        let line = serde_json::to_string(call).map_err(|e| THSError::decode("录制数据序列化失败", e))?;
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(out, "{}", line)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+/// 回放时用于匹配调用的键
+fn match_key(method: &str, params: Option<&str>) -> String {
+    if method == "connect" {
+        return method.to_string();
+    }
+    let params = params.unwrap_or("");
+    let mut key = String::with_capacity(method.len() + params.len() + 1);
+    key.push_str(method);
+    key.push('\n');
+
+    let mut rest = params;
+    while let Some(pos) = rest.find("instance=") {
+        let (head, tail) = rest.split_at(pos + "instance=".len());
+        key.push_str(head);
+        rest = tail.trim_start_matches(|c: char| c.is_ascii_digit() || c == '-');
+    }
+    key.push_str(rest);
+    key
+}
+
+#[derive(Debug, Default)]
+struct ReplayQueue {
+    calls: Vec<RecordedCall>,
+    next: usize,
+}
+
+/// 回放录制文件的调用通道
+///
+/// 相同方法和参数的调用按录制的顺序依次返回，用完后重复返回最后一次的结果；
+/// 录制中没有的调用返回错误。
+#[derive(Debug, Default)]
+pub struct ReplayTransport {
+    queues: Mutex<HashMap<String, ReplayQueue>>,
+}
+
+impl ReplayTransport {
+    /// 读取录制文件
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, THSError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let mut calls = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let call = serde_json::from_str::<RecordedCall>(&line)
+                .map_err(|e| THSError::decode(format!("录制文件格式错误: {}:{}", path.display(), index + 1), e))?;
+            calls.push(call);
+        }
+        Ok(Self::from_calls(calls))
+    }
+
+    pub fn from_calls(calls: impl IntoIterator<Item = RecordedCall>) -> Self {
+        let mut queues: HashMap<String, ReplayQueue> = HashMap::new();
+        for call in calls {
+            queues.entry(match_key(&call.method, call.params.as_deref()))
+                .or_default()
+                .calls
+                .push(call);
+        }
+        Self { queues: Mutex::new(queues) }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn call(&self, method: &str, params: Option<&str>, _buffer_size: usize) -> Result<CallStatus, THSError> {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = queues.get_mut(&match_key(method, params))
+            .ok_or_else(|| THSError::ApiError(format!("录制中没有该调用: {} {}", method, params.unwrap_or(""))))?;
+
+        let index = queue.next.min(queue.calls.len() - 1);
+        queue.next += 1;
+        Ok(queue.calls[index].status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ths::{THS, ThsOption};
+    use crate::transport::MockTransport;
+
+    const OK: &str = r#"{"err_info":"","payload":{"result":"ok"}}"#;
+
+    /// 测试结束时删除的临时文件
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("rusths_replay_{}_{}.jsonl", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn match_key_ignores_instance() {
+        let a = match_key("cmd.query_data.200", Some("\"id=200&instance=3&zipversion=2&codelist=600000\""));
+        let b = match_key("cmd.query_data.200", Some("\"id=200&instance=-17&zipversion=2&codelist=600000\""));
+        let c = match_key("cmd.query_data.200", Some("\"id=200&instance=3&zipversion=2&codelist=600004\""));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, match_key("cmd.query_data.207", Some("\"id=200&instance=3&zipversion=2&codelist=600000\"")));
+        assert_eq!(match_key("connect", Some(r#"{"username":"a"}"#)), match_key("connect", None));
+        assert_ne!(match_key("help", Some("x")), match_key("help", None));
+    }
+
+    #[test]
+    fn connect_password_is_redacted() {
+        let params = r#"{"username":"user","password":"secret"}"#;
+        let call = RecordedCall::new("connect", Some(params), 1024, &CallStatus::Ok(OK.into()));
+        let recorded = call.params.unwrap();
+        assert!(!recorded.contains("secret"), "{}", recorded);
+        assert!(recorded.contains("\"user\""));
+
+        assert_eq!(redact_params("help", params), params);
+        assert_eq!(redact_params("connect", "not json"), "not json");
+    }
+
+    #[test]
+    fn replays_in_recorded_order() {
+        let file = TempFile::new("order");
+        let recorder = Recorder::create(&file.0).unwrap();
+        for status in [CallStatus::BufferTooSmall, CallStatus::Ok(OK.into()), CallStatus::Error(-3)] {
+            recorder.record(&RecordedCall::new("help", Some("x"), 1024, &status)).unwrap();
+        }
+        drop(recorder);
+
+        let replay = ReplayTransport::open(&file.0).unwrap();
+        assert_eq!(replay.call("help", Some("x"), 1024).unwrap(), CallStatus::BufferTooSmall);
+        assert_eq!(replay.call("help", Some("x"), 2048).unwrap(), CallStatus::Ok(OK.into()));
+        assert_eq!(replay.call("help", Some("x"), 2048).unwrap(), CallStatus::Error(-3));
+        // 用完后重复最后一次
+        assert_eq!(replay.call("help", Some("x"), 2048).unwrap(), CallStatus::Error(-3));
+        assert!(matches!(replay.call("help", Some("y"), 1024), Err(THSError::ApiError(_))));
+    }
+
+    #[test]
+    fn recorded_session_replays_through_ths() {
+        let file = TempFile::new("session");
+        let quote = r#"{"err_info":"","payload":{"result":[{"代码":"600000","价格":10.5}]}}"#;
+        let ops = ThsOption { username: "user".into(), password: "secret".into(), ..ThsOption::default() };
+
+        let mock = MockTransport::new().on_ok("connect", OK).on_ok("cmd.query_data.fu", quote);
+        let live = THS::with_transport(Some(ops.clone()), mock);
+        live.start_recording(&file.0).unwrap();
+        live.connect().unwrap();
+        let expected = live.quotes("USHA600000", None).unwrap();
+        live.stop_recording();
+
+        let content = std::fs::read_to_string(&file.0).unwrap();
+        assert!(!content.contains("secret"));
+
+        let replayed = THS::with_transport(Some(ops), ReplayTransport::open(&file.0).unwrap());
+        replayed.connect().unwrap();
+        let actual = replayed.quotes("USHA600000", None).unwrap();
+        assert_eq!(serde_json::to_value(actual).unwrap(), serde_json::to_value(expected).unwrap());
+    }
+}
@@ -0,0 +1,330 @@
+//! K线重采样
+//!
+//! 把较细周期的 [`KLineData`] 合并成服务器不提供的周期，如 3 分钟、10 分钟、2 小时、双周和 N 日K线。
+//! 分钟K线按 A 股的交易时段对齐：上午 9:30–11:30、下午 13:00–15:00，每天 240 分钟，
+//! K线的时间为所在区间的结束时间，跨越午休的区间在下午继续计时。
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+use crate::error::THSError;
+use crate::ths::Interval;
+use crate::types::{local_datetime, KLineData};
+
+/// 每个交易日的分钟数
+const SESSION_MINUTES: u32 = 240;
+/// 上午的分钟数
+const MORNING_MINUTES: u32 = 120;
+
+/// 重采样的目标周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    /// N 分钟，按交易时段计时，2 小时为 `Minutes(120)`
+    Minutes(u32),
+    /// N 个交易日，从输入中的第一个交易日开始计数
+    Days(u32),
+    /// N 周，以 1970-01-05（周一）起的自然周对齐
+    Weeks(u32),
+    /// N 个月，以每年 1 月对齐，季度为 `Months(3)`
+    Months(u32),
+}
+
+impl Period {
+    /// 服务器提供的周期对应的目标周期
+    pub fn of_interval(interval: Interval) -> Self {
+        match interval {
+            Interval::Day => Period::Days(1),
+            Interval::Week => Period::Weeks(1),
+            Interval::Month => Period::Months(1),
+            Interval::Quarter => Period::Months(3),
+            Interval::Year => Period::Months(12),
+            minute => Period::Minutes(minute.minutes().unwrap_or(1)),
+        }
+    }
+
+    /// 检查 `source` 周期的K线能否合并成该周期
+    fn check_source(&self, source: Interval) -> Result<(), THSError> {
+        let ok = match (*self, source.minutes()) {
+            (Period::Minutes(0) | Period::Days(0) | Period::Weeks(0) | Period::Months(0), _) => false,
+            (Period::Minutes(n), Some(m)) => n % m == 0 && n <= SESSION_MINUTES,
+            (Period::Minutes(_), None) => false,
+            (_, Some(_)) => true,
+            (Period::Days(_), None) => source == Interval::Day,
+            (Period::Weeks(_), None) => matches!(source, Interval::Day | Interval::Week),
+            (Period::Months(n), None) => match source {
+                Interval::Day | Interval::Month => true,
+                Interval::Quarter => n % 3 == 0,
+                Interval::Year => n % 12 == 0,
+                _ => false,
+            },
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(THSError::ApiError(format!("无法把{}周期的K线合并为{:?}", source, self)))
+        }
+    }
+}
+
+/// 把 `source` 周期的K线合并为 `period` 周期，结果按时间升序排列
+///
+/// - 分钟周期：9:30 及之前的集合竞价K线并入当天第一根，15:00 之后的盘后K线并入当天最后一根；
+///   240 分钟不能整除时当天最后一根K线较短。
+/// - 日及以上周期：按交易日分组，K线时间为区间内最后一个交易日。
+///
+/// 开盘价取第一根，收盘价取最后一根，最高价、最低价取极值，成交量和成交额求和。
+/// 输入可以是乱序的，时间相同的K线只保留最后一根。
+pub fn resample(bars: &[KLineData], source: Interval, period: Period) -> Result<Vec<KLineData>, THSError> {
+    period.check_source(source)?;
+
+    let sorted: BTreeMap<DateTime<Local>, &KLineData> = bars.iter().map(|bar| (bar.time, bar)).collect();
+
+    // 交易日 -> 在输入中的序号，用于 N 日K线
+    let mut trading_days: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    for day in sorted.keys().map(|t| t.date_naive()) {
+        let next = trading_days.len() as i64;
+        trading_days.entry(day).or_insert(next);
+    }
+
+    // 分桶键 -> 合并中的K线
+    let mut buckets: BTreeMap<(i64, u32), KLineData> = BTreeMap::new();
+
+    for (time, bar) in sorted {
+        let day = time.date_naive();
+        let (key, end) = match period {
+            Period::Minutes(n) => {
+                let index = session_index(time.time());
+                let bucket_end = ((index - 1) / n + 1) * n;
+                let end = local_datetime(day.and_time(session_clock(bucket_end.min(SESSION_MINUTES))))?;
+                ((day.num_days_from_ce() as i64, bucket_end), Some(end))
+            }
+            Period::Days(n) => ((trading_days[&day] / n as i64, 0), None),
+            Period::Weeks(n) => ((weeks_since_epoch(day).div_euclid(n as i64), 0), None),
+            Period::Months(n) => {
+                let months = day.year() as i64 * 12 + day.month0() as i64;
+                ((months.div_euclid(n as i64), 0), None)
+            }
+        };
+
+        match buckets.get_mut(&key) {
+            Some(merged) => {
+                merged.high = merged.high.max(bar.high);
+                merged.low = merged.low.min(bar.low);
+                merged.close = bar.close;
+                merged.volume += bar.volume;
+                merged.amount += bar.amount;
+                if end.is_none() {
+                    merged.time = local_datetime(day.and_time(NaiveTime::MIN))?;
+                }
+            }
+            None => {
+                let mut merged = (*bar).clone();
+                merged.time = match end {
+                    Some(end) => end,
+                    None => local_datetime(day.and_time(NaiveTime::MIN))?,
+                };
+                buckets.insert(key, merged);
+            }
+        }
+    }
+
+    Ok(buckets.into_values().collect())
+}
+
+/// 交易时段内的分钟序号，9:31 为 1，11:30 为 120，13:01 为 121，15:00 为 240
+fn session_index(time: NaiveTime) -> u32 {
+    let minute = time.hour() * 60 + time.minute() + u32::from(time.second() > 0);
+    let index = if minute <= 11 * 60 + 30 {
+        minute.saturating_sub(9 * 60 + 30)
+    } else if minute <= 13 * 60 {
+        MORNING_MINUTES
+    } else {
+        MORNING_MINUTES + (minute - 13 * 60)
+    };
+    index.clamp(1, SESSION_MINUTES)
+}
+
+/// 分钟序号对应的时间
+fn session_clock(index: u32) -> NaiveTime {
+    let minute = if index <= MORNING_MINUTES {
+        9 * 60 + 30 + index
+    } else {
+        13 * 60 + (index - MORNING_MINUTES)
+    };
+    NaiveTime::from_hms_opt(minute / 60, minute % 60, 0).unwrap_or(NaiveTime::MIN)
+}
+
+/// 从 1970-01-05（周一）起的周数
+fn weeks_since_epoch(day: NaiveDate) -> i64 {
+    let monday = NaiveDate::from_ymd_opt(1970, 1, 5).unwrap_or_default();
+    (day - monday).num_days().div_euclid(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: NaiveDate, h: u32, m: u32) -> DateTime<Local> {
+        local_datetime(day.and_time(NaiveTime::from_hms_opt(h, m, 0).unwrap())).unwrap()
+    }
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+    }
+
+    fn bar(time: DateTime<Local>, price: f64) -> KLineData {
+        KLineData { time, open: price, high: price + 0.5, low: price - 0.5, close: price + 0.1, volume: 100, amount: 1000.0 }
+    }
+
+    /// 一天完整的 1 分钟K线：9:30 集合竞价、9:31–11:30、13:01–15:00，再加一根 15:30 的盘后K线
+    fn one_minute_day() -> Vec<KLineData> {
+        let mut times = vec![at(day(), 9, 30)];
+        times.extend((1..=SESSION_MINUTES).map(|i| local_datetime(day().and_time(session_clock(i))).unwrap()));
+        times.push(at(day(), 15, 30));
+        times.into_iter().enumerate().map(|(i, t)| bar(t, i as f64)).collect()
+    }
+
+    fn times(bars: &[KLineData]) -> Vec<String> {
+        bars.iter().map(|b| b.time.format("%H:%M").to_string()).collect()
+    }
+
+    #[test]
+    fn session_index_and_clock() {
+        let cases = [
+            ((9, 25), 1), ((9, 30), 1), ((9, 31), 1), ((10, 0), 30), ((11, 30), 120),
+            ((12, 0), 120), ((13, 0), 120), ((13, 1), 121), ((15, 0), 240), ((15, 30), 240),
+        ];
+        for ((h, m), index) in cases {
+            assert_eq!(session_index(NaiveTime::from_hms_opt(h, m, 0).unwrap()), index, "{}:{}", h, m);
+        }
+        // 带秒数的时间属于下一分钟
+        assert_eq!(session_index(NaiveTime::from_hms_opt(9, 31, 20).unwrap()), 2);
+        assert_eq!(session_clock(120), NaiveTime::from_hms_opt(11, 30, 0).unwrap());
+        assert_eq!(session_clock(121), NaiveTime::from_hms_opt(13, 1, 0).unwrap());
+        assert_eq!(session_clock(240), NaiveTime::from_hms_opt(15, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn three_minutes() {
+        let bars = resample(&one_minute_day(), Interval::Min1, Period::Minutes(3)).unwrap();
+        assert_eq!(bars.len(), 80);
+        let t = times(&bars);
+        assert_eq!(&t[..2], ["09:33", "09:36"]);
+        assert_eq!(&t[39..41], ["11:30", "13:03"]);
+        assert_eq!(t[79], "15:00");
+
+        // 第一根包含 9:30 的集合竞价和 9:31–9:33
+        assert_eq!(bars[0].open, 0.0);
+        assert_eq!(bars[0].close, 3.1);
+        assert_eq!(bars[0].high, 3.5);
+        assert_eq!(bars[0].low, -0.5);
+        assert_eq!(bars[0].volume, 400);
+        assert_eq!(bars[0].amount, 4000.0);
+        // 最后一根包含 15:30 的盘后K线
+        assert_eq!(bars[79].volume, 400);
+        assert_eq!(bars[79].close, 241.1);
+    }
+
+    #[test]
+    fn ten_minutes_and_two_hours() {
+        let bars = resample(&one_minute_day(), Interval::Min1, Period::Minutes(10)).unwrap();
+        assert_eq!(bars.len(), 24);
+        let t = times(&bars);
+        assert_eq!(&t[11..13], ["11:30", "13:10"]);
+
+        let bars = resample(&one_minute_day(), Interval::Min1, Period::Minutes(120)).unwrap();
+        assert_eq!(times(&bars), ["11:30", "15:00"]);
+        assert_eq!(bars[0].volume, 121 * 100);
+        assert_eq!(bars[1].volume, 121 * 100);
+        assert_eq!(bars[1].open, 121.0);
+    }
+
+    #[test]
+    fn buckets_across_lunch_break() {
+        // 90 分钟：9:31–11:00、11:01–11:30 和 13:01–14:00、14:01–15:00（较短）
+        let bars = resample(&one_minute_day(), Interval::Min1, Period::Minutes(90)).unwrap();
+        assert_eq!(times(&bars), ["11:00", "14:00", "15:00"]);
+        assert_eq!(bars[1].open, 91.0);
+        assert_eq!(bars[1].close, 180.1);
+        assert_eq!(bars[1].volume, 90 * 100);
+        assert_eq!(bars[2].volume, 61 * 100);
+
+        // 240 不能被 7 整除，最后一根只有 2 分钟
+        let bars = resample(&one_minute_day(), Interval::Min1, Period::Minutes(7)).unwrap();
+        assert_eq!(bars.len(), 35);
+        assert_eq!(bars[34].time, at(day(), 15, 0));
+        assert_eq!(bars[34].volume, 3 * 100);
+    }
+
+    #[test]
+    fn from_five_minutes() {
+        let five = resample(&one_minute_day(), Interval::Min1, Period::Minutes(5)).unwrap();
+        let direct = resample(&one_minute_day(), Interval::Min1, Period::Minutes(30)).unwrap();
+        let via_five = resample(&five, Interval::Min5, Period::Minutes(30)).unwrap();
+        let fields = |bars: &[KLineData]| -> Vec<_> {
+            bars.iter().map(|b| (b.time, b.open, b.high, b.low, b.close, b.volume)).collect()
+        };
+        assert_eq!(fields(&via_five), fields(&direct));
+    }
+
+    fn daily(from: NaiveDate, to: NaiveDate) -> Vec<KLineData> {
+        from.iter_days()
+            .take_while(|d| *d <= to)
+            .filter(|d| d.weekday().number_from_monday() <= 5)
+            .enumerate()
+            .map(|(i, d)| bar(local_datetime(d.and_time(NaiveTime::MIN)).unwrap(), i as f64))
+            .collect()
+    }
+
+    fn dates(bars: &[KLineData]) -> Vec<String> {
+        bars.iter().map(|b| b.time.format("%Y-%m-%d").to_string()).collect()
+    }
+
+    #[test]
+    fn two_weeks_aligned_to_epoch_monday() {
+        let ymd = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let bars = resample(&daily(ymd(1, 2), ymd(1, 26)), Interval::Day, Period::Weeks(2)).unwrap();
+        // 2024-01-01 是 1970-01-05 之后的第 2817 周，1 月 8 日–21 日为同一个双周
+        assert_eq!(dates(&bars), ["2024-01-05", "2024-01-19", "2024-01-26"]);
+        assert_eq!(bars[0].volume, 4 * 100);
+        assert_eq!(bars[1].volume, 10 * 100);
+        assert_eq!(bars[1].open, 4.0);
+    }
+
+    #[test]
+    fn quarters_aligned_to_january() {
+        let ymd = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let source = daily(ymd(2, 15), ymd(7, 10));
+        let bars = resample(&source, Interval::Day, Period::Months(3)).unwrap();
+        assert_eq!(dates(&bars), ["2024-03-29", "2024-06-28", "2024-07-10"]);
+        assert_eq!(bars[0].open, source[0].open);
+        assert_eq!(bars.iter().map(|b| b.volume).sum::<i64>(), source.len() as i64 * 100);
+
+        let monthly = resample(&source, Interval::Day, Period::Months(1)).unwrap();
+        let via_month = resample(&monthly, Interval::Month, Period::Months(3)).unwrap();
+        assert_eq!(dates(&via_month), dates(&bars));
+    }
+
+    #[test]
+    fn n_days_counted_from_first_trading_day() {
+        let ymd = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let bars = resample(&daily(ymd(1, 2), ymd(1, 12)), Interval::Day, Period::Days(4)).unwrap();
+        assert_eq!(dates(&bars), ["2024-01-05", "2024-01-11", "2024-01-12"]);
+    }
+
+    #[test]
+    fn rejects_incompatible_source() {
+        let bars = one_minute_day();
+        assert!(resample(&bars, Interval::Min5, Period::Minutes(7)).is_err());
+        assert!(resample(&bars, Interval::Min1, Period::Minutes(0)).is_err());
+        assert!(resample(&bars, Interval::Min1, Period::Minutes(300)).is_err());
+        assert!(resample(&bars, Interval::Week, Period::Days(2)).is_err());
+        assert!(resample(&bars, Interval::Month, Period::Weeks(1)).is_err());
+        assert!(resample(&bars, Interval::Quarter, Period::Months(4)).is_err());
+        assert!(resample(&bars, Interval::Day, Period::Minutes(5)).is_err());
+        assert!(resample(&[], Interval::Day, Period::Weeks(1)).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorKind, THSError};
+
+/// 重试策略，`connect` 和各个查询共用
+///
+/// 第 n 次失败后等待 `base_delay * 2^n`，不超过 `max_delay`，再按 `jitter` 比例随机缩短；
+/// 只有类别在 `retry_on` 中的错误才会重试。服务器返回的错误大多是永久性的（代码不存在、密码错误等），
+/// 默认不重试；确定可以重试的 `err_info` 可以加入 `retry_err_info`。
+/// `BufferTooSmall` 比较特殊：扩大缓冲区后立即重试，不等待。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// 最大尝试次数（包括第一次），至少为 1
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// 随机抖动比例，取值 0.0 ~ 1.0，0 表示不抖动
+    pub jitter: f64,
+    pub retry_on: Vec<ErrorKind>,
+    /// `err_info` 包含其中任一字符串的服务器错误也会重试，`retry_on` 包含 `Server` 时不需要设置
+    pub retry_err_info: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(16),
+            jitter: 0.0,
+            retry_on: vec![ErrorKind::BufferTooSmall, ErrorKind::Io, ErrorKind::Timeout],
+            retry_err_info: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.max(1)
+    }
+
+    pub fn is_retryable(&self, err: &THSError) -> bool {
+        match err {
+            THSError::Server { err_info } if !self.retry_on.contains(&ErrorKind::Server) => {
+                self.retry_err_info.iter().any(|s| err_info.contains(s.as_str()))
+            }
+            _ => self.retry_on.contains(&err.kind()),
+        }
+    }
+
+    /// 第 `attempt` 次（从 0 开始）失败后的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .checked_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter > 0.0 {
+            delay.mul_f64(1.0 - jitter * rand::random::<f64>())
+        } else {
+            delay
+        }
+    }
+}
+
+thread_local! {
+    /// 当前线程中是否由调用方负责重试前的等待，见 [`defer_backoff`]
+    static DEFERRED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 执行 `f`，其中需要等待后才能重试的错误直接返回，由调用方等待后重新执行
+///
+/// 异步客户端用它把重试的等待从阻塞线程移到 `tokio::time::sleep`。
+#[cfg(feature = "async")]
+pub(crate) fn defer_backoff<R>(f: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEFERRED.with(|d| d.set(self.0));
+        }
+    }
+
+    let _restore = Restore(DEFERRED.with(|d| d.replace(true)));
+    f()
+}
+
+/// 当前线程中是否在 [`defer_backoff`] 内
+pub(crate) fn backoff_deferred() -> bool {
+    DEFERRED.with(Cell::get)
+}
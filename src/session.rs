@@ -0,0 +1,120 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Weak;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::error::THSError;
+use crate::ths::THS;
+
+/// 会话保持策略
+///
+/// 查询时如果发现会话已失效（错误类别为 `NotLoggedIn`，或错误信息中包含 `lost_markers` 中的任意一项），
+/// 会自动重新连接并重放一次失败的请求。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    /// 会话失效时是否自动重连
+    pub auto_reconnect: bool,
+    /// 使用游客账号时，重连前是否换一个新的游客账号
+    pub rotate_guest: bool,
+    /// 表示会话失效的错误信息片段
+    pub lost_markers: Vec<String>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            auto_reconnect: true,
+            rotate_guest: false,
+            lost_markers: ["未登录", "登录失效", "重新登录", "连接已断开", "连接断开"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 服务器返回的错误信息是否表示会话失效
+    pub fn is_lost_info(&self, err_info: &str) -> bool {
+        !err_info.is_empty() && self.lost_markers.iter().any(|m| err_info.contains(m.as_str()))
+    }
+
+    /// 调用错误是否表示会话失效，只有 `NotLoggedIn` 和服务器返回的错误信息参与判断
+    pub fn is_lost_error(&self, err: &THSError) -> bool {
+        match err {
+            THSError::NotLoggedIn => true,
+            THSError::Server { err_info } => self.is_lost_info(err_info),
+            _ => false,
+        }
+    }
+}
+
+/// 心跳线程的句柄，丢弃或调用 [`Heartbeat::stop`] 后线程退出
+#[derive(Debug)]
+pub struct Heartbeat {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub(crate) fn spawn(ths: Weak<THS>, interval: Duration) -> Result<Self, THSError> {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = std::thread::Builder::new()
+            .name("rusths-heartbeat".into())
+            .spawn(move || loop {
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+                let Some(ths) = ths.upgrade() else {
+                    return;
+                };
+                match ths.heartbeat() {
+                    Ok(()) => debug!(method = "heartbeat", "心跳正常"),
+                    Err(e) => warn!(method = "heartbeat", error = %e, "心跳失败"),
+                }
+            })?;
+
+        Ok(Self { stop: Some(stop), handle: Some(handle) })
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lost_session_is_matched_by_variant() {
+        let policy = ReconnectPolicy::default();
+        assert!(policy.is_lost_error(&THSError::NotLoggedIn));
+        assert!(policy.is_lost_error(&THSError::Server { err_info: "登录失效，请重新登录".into() }));
+        assert!(!policy.is_lost_error(&THSError::Server { err_info: "代码不存在".into() }));
+        assert!(!policy.is_lost_error(&THSError::Server { err_info: String::new() }));
+
+        // 其它错误的描述里恰好包含关键字也不算会话失效
+        assert!(!policy.is_lost_error(&THSError::ApiError("未登录".into())));
+        assert!(!policy.is_lost_error(&THSError::decode("连接断开", "eof")));
+        assert!(!policy.is_lost_error(&THSError::MethodNotFound { code: -2, method: "未登录".into() }));
+    }
+}
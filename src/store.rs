@@ -0,0 +1,746 @@
+//! 本地K线库
+//!
+//! 每个代码和周期一个文件，保存不复权的K线，按时间升序排列：
+//!
+//! ```text
+//! {root}/{interval}/{USHA600000}.bin   K线
+//! {root}/factors/{USHA600000}.json     复权因子
+//! ```
+//!
+//! K线文件以 8 字节的文件头开始，之后每根K线占 56 字节：时间（Unix 秒）、开高低收、成交量和成交额，
+//! 均为小端序。记录定长，读取时按时间二分查找；新数据直接追加到末尾，与已有数据重叠时
+//! 通过临时文件替换整个文件。
+//!
+//! 缺口检测以上证指数（USHI1A0001）的日K线作为交易日历，需要先调用 [`KlineStore::update_calendar`]。
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone};
+use tracing::{debug, info, warn};
+
+use crate::code::{IntoSecurityCode, SecurityCode};
+use crate::error::THSError;
+use crate::factor::AdjustFactors;
+use crate::ths::{Adjust, Interval, THS};
+use crate::types::{local_datetime, KLineData};
+
+const MAGIC: &[u8; 8] = b"RTHSKL1\0";
+const RECORD_SIZE: u64 = 56;
+
+/// 用作交易日历的证券
+pub const CALENDAR_CODE: &str = "USHI1A0001";
+
+/// 缺少数据的连续交易日，包括首尾两天
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gap {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+/// 本地K线库，见模块文档
+#[derive(Debug, Clone)]
+pub struct KlineStore {
+    root: PathBuf,
+}
+
+impl KlineStore {
+    /// 打开目录中的K线库，目录不存在时创建
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, THSError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, code: &SecurityCode, interval: Interval) -> PathBuf {
+        self.root.join(interval.as_str()).join(format!("{}.bin", code))
+    }
+
+    fn factors_path(&self, code: &SecurityCode) -> PathBuf {
+        self.root.join("factors").join(format!("{}.json", code))
+    }
+
+    /// 读取一段时间内的K线，不访问服务器；没有保存过的代码返回空列表
+    pub fn read(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        interval: Interval,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+    ) -> Result<Vec<KLineData>, THSError> {
+        let code = ths_code.into_security_code()?;
+        let Some(mut file) = SeriesFile::open(&self.path(&code, interval))? else {
+            return Ok(Vec::new());
+        };
+
+        let from = match start_time {
+            Some(start) => file.position_of(start.timestamp())?,
+            None => 0,
+        };
+        let bars = file.read_from(from)?;
+        Ok(match end_time {
+            Some(end) => bars.into_iter().take_while(|bar| bar.time <= end).collect(),
+            None => bars,
+        })
+    }
+
+    /// 读取K线并按保存的复权因子在本地复权，没有复权因子时返回错误
+    pub fn read_adjusted(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        interval: Interval,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        adjust: Adjust,
+    ) -> Result<Vec<KLineData>, THSError> {
+        let code = ths_code.into_security_code()?;
+        let bars = self.read(&code, interval, start_time, end_time)?;
+        if adjust == Adjust::None {
+            return Ok(bars);
+        }
+        let factors = self.factors(&code)?
+            .ok_or_else(|| THSError::NoData(format!("没有复权因子: {}", code)))?;
+        Ok(factors.adjust(&bars, adjust))
+    }
+
+    /// 最后一根K线的时间
+    pub fn last_time(&self, ths_code: impl IntoSecurityCode, interval: Interval) -> Result<Option<DateTime<Local>>, THSError> {
+        let code = ths_code.into_security_code()?;
+        match SeriesFile::open(&self.path(&code, interval))? {
+            Some(mut file) if file.len > 0 => {
+                let last = file.len - 1;
+                Ok(Some(file.read_at(last)?.time))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// 写入K线，与已有K线时间相同的以新数据为准
+    ///
+    /// 新数据都在已有K线之后时直接追加，中断只会在末尾留下被忽略的不完整记录；
+    /// 否则把不变的前半部分和合并后的尾部写入临时文件，再替换原文件。
+    pub fn write(&self, ths_code: impl IntoSecurityCode, interval: Interval, bars: &[KLineData]) -> Result<usize, THSError> {
+        let code = ths_code.into_security_code()?;
+        if bars.is_empty() {
+            return Ok(0);
+        }
+
+        let path = self.path(&code, interval);
+        let first = bars.iter().map(|bar| bar.time).min().unwrap_or_else(Local::now);
+        let (position, len, existing) = match SeriesFile::open(&path)? {
+            Some(mut file) => {
+                let position = file.position_of(first.timestamp())?;
+                (position, file.len, file.read_from(position)?)
+            }
+            None => (0, 0, Vec::new()),
+        };
+
+        let mut merged: BTreeMap<i64, KLineData> = existing
+            .into_iter()
+            .map(|bar| (bar.time.timestamp(), bar))
+            .collect();
+        merged.extend(bars.iter().map(|bar| (bar.time.timestamp(), bar.clone())));
+
+        let total = if position == len {
+            let mut file = SeriesFile::create(&path)?;
+            file.append(merged.values())?;
+            file.len
+        } else {
+            SeriesFile::replace_tail(&path, position, merged.values())?
+        };
+        debug!(code = %code, interval = %interval, count = bars.len(), total, "写入K线");
+        Ok(bars.len())
+    }
+
+    /// 从服务器获取最后一根K线之后的数据，还没有数据时从 `since` 开始获取
+    ///
+    /// 最后一根K线可能是盘中未完成的K线，会重新获取并覆盖。返回写入的K线数量。
+    pub fn update(
+        &self,
+        ths: &THS,
+        ths_code: impl IntoSecurityCode,
+        interval: Interval,
+        since: DateTime<Local>,
+    ) -> Result<usize, THSError> {
+        let code = ths_code.into_security_code()?;
+        let start = self.last_time(&code, interval)?.unwrap_or(since);
+        let end = Local::now();
+        if start >= end {
+            return Ok(0);
+        }
+
+        let bars = ths.klines_range(&code, start, end, Adjust::None, interval)?;
+        self.write(&code, interval, &bars)
+    }
+
+    /// 依次更新多个代码，单个代码的失败不影响其余代码
+    pub fn update_all<I>(
+        &self,
+        ths: &THS,
+        codes: I,
+        interval: Interval,
+        since: DateTime<Local>,
+    ) -> Vec<Result<usize, THSError>>
+    where I: IntoIterator, I::Item: IntoSecurityCode {
+        codes.into_iter()
+            .map(|code| {
+                let code = code.into_security_code()?;
+                let result = self.update(ths, &code, interval, since);
+                if let Err(e) = &result {
+                    warn!(code = %code, interval = %interval, error = %e, "更新K线失败");
+                }
+                result
+            })
+            .collect()
+    }
+
+    /// 更新交易日历
+    pub fn update_calendar(&self, ths: &THS, since: DateTime<Local>) -> Result<usize, THSError> {
+        self.update(ths, CALENDAR_CODE, Interval::Day, since)
+    }
+
+    /// 交易日历，没有更新过时为空
+    pub fn trading_days(&self) -> Result<Vec<NaiveDate>, THSError> {
+        Ok(self.read(CALENDAR_CODE, Interval::Day, None, None)?
+            .iter()
+            .map(|bar| bar.time.date_naive())
+            .collect())
+    }
+
+    /// 检查已保存的K线中缺少的交易日
+    ///
+    /// 检查范围为第一根K线到最后一根K线（不超过交易日历的最后一天）。日线只检查整天缺失，
+    /// 分钟K线还会检查当天的K线数量是否完整。停牌的交易日也会被报告为缺口。
+    pub fn gaps(&self, ths_code: impl IntoSecurityCode, interval: Interval) -> Result<Vec<Gap>, THSError> {
+        let code = ths_code.into_security_code()?;
+        if interval != Interval::Day && !interval.is_minute() {
+            return Err(THSError::ApiError(format!("只能检查日线和分钟K线的缺口: {}", interval)));
+        }
+
+        let calendar = self.trading_days()?;
+        if calendar.is_empty() {
+            return Err(THSError::NoData("没有交易日历，请先调用 update_calendar".into()));
+        }
+
+        let bars = self.read(&code, interval, None, None)?;
+        let (Some(first), Some(last)) = (bars.first(), bars.last()) else {
+            return Ok(Vec::new());
+        };
+        let (first, last) = (first.time.date_naive(), last.time.date_naive());
+
+        let mut counts: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+        for bar in &bars {
+            *counts.entry(bar.time.date_naive()).or_default() += 1;
+        }
+        let expected = interval.minutes().map_or(1, |m| (240 / m).max(1));
+
+        let mut gaps: Vec<Gap> = Vec::new();
+        let mut previous: Option<NaiveDate> = None;
+        for &day in calendar.iter().filter(|d| **d >= first && **d <= last) {
+            if counts.get(&day).copied().unwrap_or(0) < expected {
+                match gaps.last_mut() {
+                    Some(gap) if previous == Some(gap.end) => gap.end = day,
+                    _ => gaps.push(Gap { start: day, end: day }),
+                }
+            }
+            previous = Some(day);
+        }
+        Ok(gaps)
+    }
+
+    /// 重新获取缺口中的K线，返回写入的K线数量
+    pub fn repair(&self, ths: &THS, ths_code: impl IntoSecurityCode, interval: Interval) -> Result<usize, THSError> {
+        let code = ths_code.into_security_code()?;
+        let mut written = 0;
+        for gap in self.gaps(&code, interval)? {
+            let start = local_datetime(gap.start.and_time(NaiveTime::MIN))?;
+            let end = local_datetime(gap.end.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap_or(NaiveTime::MIN)))?;
+            let bars = ths.klines_range(&code, start, end, Adjust::None, interval)?;
+            info!(code = %code, start = %gap.start, end = %gap.end, count = bars.len(), "修补K线缺口");
+            written += self.write(&code, interval, &bars)?;
+        }
+        Ok(written)
+    }
+
+    /// 保存的复权因子
+    pub fn factors(&self, ths_code: impl IntoSecurityCode) -> Result<Option<AdjustFactors>, THSError> {
+        let path = self.factors_path(&ths_code.into_security_code()?);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        AdjustFactors::load(path).map(Some)
+    }
+
+    pub fn save_factors(&self, ths_code: impl IntoSecurityCode, factors: &AdjustFactors) -> Result<(), THSError> {
+        let path = self.factors_path(&ths_code.into_security_code()?);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        factors.save(path)
+    }
+
+    /// 从服务器计算最近的复权因子并与保存的合并，还没有复权因子时从 `since` 开始计算
+    pub fn update_factors(&self, ths: &THS, ths_code: impl IntoSecurityCode, since: DateTime<Local>) -> Result<AdjustFactors, THSError> {
+        let code = ths_code.into_security_code()?;
+        let factors = match self.factors(&code)? {
+            Some(mut factors) => {
+                let start = local_datetime(factors.end.and_time(NaiveTime::MIN))?;
+                let newer = ths.adjust_factors(&code, start, Local::now())?;
+                factors.merge(&newer)?;
+                factors
+            }
+            None => ths.adjust_factors(&code, since, Local::now())?,
+        };
+        self.save_factors(&code, &factors)?;
+        Ok(factors)
+    }
+}
+
+/// 通过同目录的临时文件替换 `path`：`write` 写完后刷新并同步到磁盘，再重命名为 `path`
+///
+/// 中途失败时删除临时文件，原文件不变。
+pub(crate) fn write_replacing<T>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, THSError>,
+) -> Result<T, THSError> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        let value = write(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// 一个K线文件
+struct SeriesFile {
+    file: File,
+    path: PathBuf,
+    /// K线数量
+    len: u64,
+}
+
+impl SeriesFile {
+    /// 打开已有的文件，不存在时返回 `None`
+    fn open(path: &Path) -> Result<Option<Self>, THSError> {
+        match OpenOptions::new().read(true).open(path) {
+            Ok(file) => Self::from_file(file, path).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 打开用于写入，不存在时创建
+    fn create(path: &Path) -> Result<Self, THSError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(MAGIC)?;
+        }
+        Self::from_file(file, path)
+    }
+
+    fn from_file(mut file: File, path: &Path) -> Result<Self, THSError> {
+        let size = file.metadata()?.len();
+        let mut magic = [0u8; 8];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(THSError::decode(format!("K线文件格式错误: {}", path.display()), "文件头不匹配"));
+        }
+        let body = size - MAGIC.len() as u64;
+        if !body.is_multiple_of(RECORD_SIZE) {
+            warn!(path = %path.display(), "K线文件末尾有不完整的记录，已忽略");
+        }
+        Ok(Self { file, path: path.to_path_buf(), len: body / RECORD_SIZE })
+    }
+
+    fn offset(index: u64) -> u64 {
+        MAGIC.len() as u64 + index * RECORD_SIZE
+    }
+
+    fn read_at(&mut self, index: u64) -> Result<KLineData, THSError> {
+        self.file.seek(SeekFrom::Start(Self::offset(index)))?;
+        let mut buf = [0u8; RECORD_SIZE as usize];
+        self.file.read_exact(&mut buf)?;
+        self.decode(&buf)
+    }
+
+    /// 第一根时间不早于 `timestamp` 的K线的位置
+    fn position_of(&mut self, timestamp: i64) -> Result<u64, THSError> {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.read_at(mid)?.time.timestamp() < timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(low)
+    }
+
+    fn read_from(&mut self, index: u64) -> Result<Vec<KLineData>, THSError> {
+        self.file.seek(SeekFrom::Start(Self::offset(index)))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut bars = Vec::with_capacity(self.len.saturating_sub(index) as usize);
+        let mut buf = [0u8; RECORD_SIZE as usize];
+        for _ in index..self.len {
+            reader.read_exact(&mut buf)?;
+            bars.push(self.decode(&buf)?);
+        }
+        Ok(bars)
+    }
+
+    /// 保留前 `keep` 根K线，其后替换为 `bars`，返回K线数量
+    ///
+    /// 先写入同目录的临时文件再重命名，中途失败时原文件不变。
+    fn replace_tail<'a>(path: &Path, keep: u64, bars: impl IntoIterator<Item = &'a KLineData>) -> Result<u64, THSError> {
+        write_replacing(path, |writer| {
+            let mut source = File::open(path)?;
+            let copied = std::io::copy(&mut (&mut source).take(Self::offset(keep)), writer)?;
+            if copied != Self::offset(keep) {
+                return Err(THSError::decode(format!("K线文件格式错误: {}", path.display()), "文件在写入时被截断"));
+            }
+            let mut count = keep;
+            for bar in bars {
+                writer.write_all(&encode(bar))?;
+                count += 1;
+            }
+            Ok(count)
+        })
+    }
+
+    fn append<'a>(&mut self, bars: impl IntoIterator<Item = &'a KLineData>) -> Result<(), THSError> {
+        self.file.seek(SeekFrom::Start(Self::offset(self.len)))?;
+        let mut writer = BufWriter::new(&self.file);
+        let mut count = 0;
+        for bar in bars {
+            writer.write_all(&encode(bar))?;
+            count += 1;
+        }
+        writer.flush()?;
+        self.len += count;
+        Ok(())
+    }
+
+    fn decode(&self, buf: &[u8; RECORD_SIZE as usize]) -> Result<KLineData, THSError> {
+        let field = |i: usize| -> [u8; 8] {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buf[i * 8..i * 8 + 8]);
+            bytes
+        };
+        let timestamp = i64::from_le_bytes(field(0));
+        let time = Local.timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| THSError::decode(format!("K线文件格式错误: {}", self.path.display()), format!("无效的时间: {}", timestamp)))?;
+        Ok(KLineData {
+            time,
+            open: f64::from_le_bytes(field(1)),
+            high: f64::from_le_bytes(field(2)),
+            low: f64::from_le_bytes(field(3)),
+            close: f64::from_le_bytes(field(4)),
+            volume: i64::from_le_bytes(field(5)),
+            amount: f64::from_le_bytes(field(6)),
+        })
+    }
+}
+
+fn encode(bar: &KLineData) -> [u8; RECORD_SIZE as usize] {
+    let mut buf = [0u8; RECORD_SIZE as usize];
+    let fields = [
+        bar.time.timestamp().to_le_bytes(),
+        bar.open.to_le_bytes(),
+        bar.high.to_le_bytes(),
+        bar.low.to_le_bytes(),
+        bar.close.to_le_bytes(),
+        bar.volume.to_le_bytes(),
+        bar.amount.to_le_bytes(),
+    ];
+    for (i, bytes) in fields.iter().enumerate() {
+        buf[i * 8..i * 8 + 8].copy_from_slice(bytes);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use chrono::{Datelike, NaiveDateTime, Weekday};
+    use serde_json::json;
+
+    use super::*;
+    use crate::transport::{CallStatus, MockTransport};
+
+    const CODE: &str = "USHA600000";
+
+    /// 测试结束时删除的临时目录
+    struct TempStore {
+        store: KlineStore,
+    }
+
+    impl TempStore {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("rusths_store_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            Self { store: KlineStore::open(root).unwrap() }
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.store.root());
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn at(day: NaiveDate) -> DateTime<Local> {
+        local_datetime(day.and_time(NaiveTime::MIN)).unwrap()
+    }
+
+    fn bar(day: NaiveDate, price: f64) -> KLineData {
+        KLineData {
+            time: at(day),
+            open: price,
+            high: price + 0.5,
+            low: price - 0.5,
+            close: price + 0.25,
+            volume: 100,
+            amount: price * 100.0,
+        }
+    }
+
+    fn days(start: NaiveDate, count: usize) -> Vec<NaiveDate> {
+        start.iter_days().take(count).collect()
+    }
+
+    fn closes(bars: &[KLineData]) -> Vec<f64> {
+        bars.iter().map(|bar| bar.close).collect()
+    }
+
+    fn tempfile() -> File {
+        let path = std::env::temp_dir().join(format!("rusths_store_decode_{}", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        file
+    }
+    /// 2024-01-02 起每个工作日一根日K线；`hole` 为真时个股缺少 2024-03-05
+    fn daily_server(hole: Arc<AtomicBool>) -> MockTransport {
+        MockTransport::new().with_handler(move |method, params, _| {
+            if method != "klines" {
+                return CallStatus::Ok(r#"{"err_info":"","payload":{"result":null}}"#.into());
+            }
+            let params: serde_json::Value = serde_json::from_str(params.unwrap()).unwrap();
+            let time = |key: &str| {
+                NaiveDateTime::parse_from_str(params[key].as_str().unwrap(), "%Y-%m-%d %H:%M:%S").unwrap().date()
+            };
+            let calendar = params["code"].as_str().unwrap() == CALENDAR_CODE;
+            let rows: Vec<_> = time("start_time").max(date(2024, 1, 2))
+                .iter_days()
+                .take_while(|day| *day <= time("end_time"))
+                .filter(|day| !matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+                .filter(|day| calendar || *day != date(2024, 3, 5) || !hole.load(Ordering::SeqCst))
+                .map(|day| {
+                    let price = 10.0 + day.ordinal() as f64 / 100.0;
+                    json!({
+                        "时间": day.format("%Y%m%d").to_string(),
+                        "开盘价": price, "最高价": price, "最低价": price, "收盘价": price,
+                        "成交量": 100, "总金额": 1000.0,
+                    })
+                })
+                .collect();
+            CallStatus::Ok(json!({"err_info": "", "payload": {"result": rows}}).to_string())
+        })
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let original = KLineData { volume: -7, amount: f64::MAX, ..bar(date(2024, 1, 2), 12.34) };
+        let file = SeriesFile { file: tempfile(), path: PathBuf::from("test.bin"), len: 0 };
+        let decoded = file.decode(&encode(&original)).unwrap();
+        assert_eq!(decoded.time, original.time);
+        assert_eq!(
+            (decoded.open, decoded.high, decoded.low, decoded.close, decoded.volume, decoded.amount),
+            (original.open, original.high, original.low, original.close, original.volume, original.amount),
+        );
+    }
+
+
+    #[test]
+    fn write_and_read_ranges() {
+        let temp = TempStore::new("ranges");
+        let store = &temp.store;
+        assert!(store.read(CODE, Interval::Day, None, None).unwrap().is_empty());
+        assert_eq!(store.last_time(CODE, Interval::Day).unwrap(), None);
+
+        let bars: Vec<_> = days(date(2024, 1, 1), 10).into_iter().enumerate().map(|(i, d)| bar(d, i as f64)).collect();
+        assert_eq!(store.write(CODE, Interval::Day, &bars).unwrap(), 10);
+        assert_eq!(store.read(CODE, Interval::Day, None, None).unwrap().len(), 10);
+        assert_eq!(store.last_time(CODE, Interval::Day).unwrap(), Some(at(date(2024, 1, 10))));
+
+        let part = store.read(CODE, Interval::Day, Some(at(date(2024, 1, 3))), Some(at(date(2024, 1, 5)))).unwrap();
+        assert_eq!(closes(&part), vec![2.25, 3.25, 4.25]);
+        let tail = store.read(CODE, Interval::Day, Some(at(date(2024, 1, 9))), None).unwrap();
+        assert_eq!(tail.len(), 2);
+        assert!(store.read(CODE, Interval::Day, Some(at(date(2025, 1, 1))), None).unwrap().is_empty());
+        // 周期之间互不影响
+        assert!(store.read(CODE, Interval::Week, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_merges_and_overwrites() {
+        let temp = TempStore::new("merge");
+        let store = &temp.store;
+        let first: Vec<_> = days(date(2024, 1, 1), 5).into_iter().map(|d| bar(d, 1.0)).collect();
+        store.write(CODE, Interval::Day, &first).unwrap();
+
+        // 与末尾重叠，重叠部分以新数据为准
+        let second: Vec<_> = days(date(2024, 1, 4), 4).into_iter().map(|d| bar(d, 2.0)).collect();
+        store.write(CODE, Interval::Day, &second).unwrap();
+        let bars = store.read(CODE, Interval::Day, None, None).unwrap();
+        assert_eq!(closes(&bars), vec![1.25, 1.25, 1.25, 2.25, 2.25, 2.25, 2.25]);
+
+        // 插入中间缺少的一天，并且输入无序
+        let mut third = vec![bar(date(2024, 1, 20), 3.0), bar(date(2024, 1, 2), 4.0)];
+        third.push(bar(date(2024, 1, 15), 3.0));
+        store.write(CODE, Interval::Day, &third).unwrap();
+        let bars = store.read(CODE, Interval::Day, None, None).unwrap();
+        assert_eq!(bars.len(), 9);
+        assert!(bars.windows(2).all(|w| w[0].time < w[1].time));
+        assert_eq!(closes(&bars)[1], 4.25);
+        assert_eq!(store.last_time(CODE, Interval::Day).unwrap(), Some(at(date(2024, 1, 20))));
+
+        let path = store.path(&CODE.parse().unwrap(), Interval::Day);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 8 + 9 * RECORD_SIZE);
+        assert!(!path.with_extension("bin.tmp").exists());
+    }
+
+    #[test]
+    fn incomplete_record_is_ignored_and_overwritten() {
+        let temp = TempStore::new("partial");
+        let store = &temp.store;
+        store.write(CODE, Interval::Day, &[bar(date(2024, 1, 1), 1.0)]).unwrap();
+        let path = store.path(&CODE.parse().unwrap(), Interval::Day);
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[0xff; 20]).unwrap();
+
+        assert_eq!(store.read(CODE, Interval::Day, None, None).unwrap().len(), 1);
+        store.write(CODE, Interval::Day, &[bar(date(2024, 1, 2), 2.0)]).unwrap();
+        assert_eq!(closes(&store.read(CODE, Interval::Day, None, None).unwrap()), vec![1.25, 2.25]);
+    }
+
+    #[test]
+    fn failed_replace_keeps_original() {
+        let temp = TempStore::new("replace");
+        let path = temp.store.root().join("factors.json");
+        fs::write(&path, "原文件").unwrap();
+
+        let result = write_replacing(&path, |writer| -> Result<(), THSError> {
+            writer.write_all("写了一半".as_bytes())?;
+            Err(THSError::NoData("中途失败".into()))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "原文件");
+        assert!(!temp.store.root().join("factors.json.tmp").exists());
+
+        write_replacing(&path, |writer| Ok(writer.write_all("新文件".as_bytes())?)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "新文件");
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let temp = TempStore::new("magic");
+        let store = &temp.store;
+        let path = store.path(&CODE.parse().unwrap(), Interval::Day);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"NOTAKLINEFILE").unwrap();
+        let err = store.read(CODE, Interval::Day, None, None).unwrap_err();
+        assert_eq!(err.kind(), crate::error::ErrorKind::Decode);
+        assert!(store.write(CODE, Interval::Day, &[bar(date(2024, 1, 1), 1.0)]).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"NOTAKLINEFILE");
+    }
+
+    #[test]
+    fn gaps_need_calendar_and_supported_interval() {
+        let temp = TempStore::new("gap_errors");
+        let store = &temp.store;
+        assert!(matches!(store.gaps(CODE, Interval::Day), Err(THSError::NoData(_))));
+        assert!(matches!(store.gaps(CODE, Interval::Week), Err(THSError::ApiError(_))));
+    }
+
+    #[test]
+    fn gaps_in_minute_bars() {
+        let temp = TempStore::new("minute_gaps");
+        let store = &temp.store;
+        let calendar: Vec<_> = days(date(2024, 1, 1), 5).into_iter().map(|d| bar(d, 1.0)).collect();
+        store.write(CALENDAR_CODE, Interval::Day, &calendar).unwrap();
+
+        // 每天 4 根 60 分钟K线，1-02 只有 3 根，1-03 没有
+        let mut bars = Vec::new();
+        for day in [date(2024, 1, 1), date(2024, 1, 2), date(2024, 1, 4), date(2024, 1, 5)] {
+            let count = if day == date(2024, 1, 2) { 3 } else { 4 };
+            for hour in 10..10 + count {
+                bars.push(KLineData { time: local_datetime(day.and_hms_opt(hour, 0, 0).unwrap()).unwrap(), ..bar(day, 1.0) });
+            }
+        }
+        store.write(CODE, Interval::Min60, &bars).unwrap();
+        assert_eq!(
+            store.gaps(CODE, Interval::Min60).unwrap(),
+            vec![Gap { start: date(2024, 1, 2), end: date(2024, 1, 3) }],
+        );
+    }
+
+    #[test]
+    fn update_gaps_and_repair() {
+        let hole = Arc::new(AtomicBool::new(true));
+        let mock = daily_server(hole.clone());
+        let ths = THS::with_transport(None, mock.clone());
+        ths.connect().unwrap();
+        let temp = TempStore::new("repair");
+        let store = &temp.store;
+        let since = at(date(2024, 1, 1));
+
+        store.update_calendar(&ths, since).unwrap();
+        let written = store.update(&ths, CODE, Interval::Day, since).unwrap();
+        let bars = store.read(CODE, Interval::Day, None, None).unwrap();
+        assert_eq!(bars.len(), written);
+        assert!(bars.windows(2).all(|w| w[0].time < w[1].time));
+        assert_eq!(store.gaps(CODE, Interval::Day).unwrap(), vec![Gap { start: date(2024, 3, 5), end: date(2024, 3, 5) }]);
+
+        hole.store(false, Ordering::SeqCst);
+        let calls = mock.calls_to("klines").len();
+        assert_eq!(store.repair(&ths, CODE, Interval::Day).unwrap(), 1);
+        assert_eq!(mock.calls_to("klines").len(), calls + 1);
+        assert!(store.gaps(CODE, Interval::Day).unwrap().is_empty());
+        assert_eq!(store.read(CODE, Interval::Day, None, None).unwrap().len(), bars.len() + 1);
+
+        // 增量更新只重新获取最后一根
+        let calls = mock.calls_to("klines").len();
+        assert_eq!(store.update(&ths, CODE, Interval::Day, since).unwrap(), 1);
+        assert_eq!(mock.calls_to("klines").len(), calls + 1);
+        assert_eq!(store.read(CODE, Interval::Day, None, None).unwrap().len(), bars.len() + 1);
+
+        let results = store.update_all(&ths, ["USHA600001", "bad"], Interval::Day, since);
+        assert!(results[0].is_ok() && results[1].is_err());
+    }
+}
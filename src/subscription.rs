@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::constants::{DATA_CLASS_LIST, DATA_CLASS_NAMES, DATA_OP_TYPE, SUB_OP_TYPE};
+use crate::error::THSError;
+
+/// 推送数据类别，对应 `DATA_CLASS_LIST`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DataClass {
+    Index,
+    Stock,
+    Queue,
+    Order,
+    Trans,
+    SuperStock,
+}
+
+impl DataClass {
+    pub const ALL: [DataClass; 6] = [
+        DataClass::Index,
+        DataClass::Stock,
+        DataClass::Queue,
+        DataClass::Order,
+        DataClass::Trans,
+        DataClass::SuperStock,
+    ];
+
+    fn position(self) -> usize {
+        Self::ALL.iter().position(|&c| c == self).unwrap()
+    }
+
+    /// 协议中的类别编号
+    pub fn id(self) -> i32 {
+        DATA_CLASS_LIST[self.position()]
+    }
+
+    /// 类别名称，如 "stock"
+    pub fn name(self) -> &'static str {
+        DATA_CLASS_NAMES[self.position()]
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        DATA_CLASS_LIST.iter().position(|&i| i == id).map(|p| Self::ALL[p])
+    }
+}
+
+impl fmt::Display for DataClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 订阅操作，对应 `SUB_OP_TYPE` 的前两项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SubOp {
+    Subscribe,
+    Unsubscribe,
+}
+
+impl SubOp {
+    fn id(self) -> i32 {
+        match self {
+            SubOp::Subscribe => SUB_OP_TYPE[0],
+            SubOp::Unsubscribe => SUB_OP_TYPE[1],
+        }
+    }
+}
+
+/// 一条推送数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushUpdate {
+    pub data_class: DataClass,
+    pub code: String,
+    pub data: Value,
+}
+
+impl PushUpdate {
+    /// 解析推送帧，格式为 `{"data_class": 2, "code": "USHA600000", "data": {...}}`
+    pub fn parse(frame: &str) -> Result<Self, THSError> {
+        #[derive(Deserialize)]
+        struct Frame {
+            data_class: i32,
+            code: String,
+            #[serde(default)]
+            data: Value,
+        }
+
+        let frame: Frame = serde_json::from_str(frame)
+            .map_err(|e| THSError::decode("推送数据解析失败", e))?;
+        let data_class = DataClass::from_id(frame.data_class)
+            .ok_or_else(|| THSError::decode("推送数据解析失败", format!("未知的推送类别: {}", frame.data_class)))?;
+        Ok(Self { data_class, code: frame.code.to_uppercase(), data: frame.data })
+    }
+}
+
+/// 构造订阅请求的参数
+pub(crate) fn request_params(op: SubOp, data_class: DataClass, codes: &[String]) -> String {
+    serde_json::json!({
+        "op": op.id(),
+        "data_class": data_class.id(),
+        "data_op": DATA_OP_TYPE[0],
+        "codes": codes.join(","),
+    }).to_string()
+}
+
+type Deliver = Arc<dyn Fn(&PushUpdate) -> bool + Send + Sync>;
+
+/// 一个推送接收方，`deliver` 返回 false 表示接收方已经关闭
+struct Sink {
+    id: u64,
+    data_class: DataClass,
+    codes: BTreeSet<String>,
+    deliver: Deliver,
+}
+
+/// 订阅状态和推送分发
+///
+/// 记录当前订阅的代码，用于重连后重新订阅；推送到达时按类别和代码分发给接收方。
+#[derive(Default)]
+pub(crate) struct SubscriptionHub {
+    active: Mutex<BTreeMap<DataClass, BTreeSet<String>>>,
+    sinks: Mutex<Vec<Sink>>,
+    next_sink: AtomicU64,
+}
+
+impl SubscriptionHub {
+    /// 分发一帧推送数据，无法解析的帧直接丢弃
+    ///
+    /// 调用接收方时不持有锁，回调中可以订阅或取消订阅。
+    pub(crate) fn dispatch(&self, frame: &str) {
+        let Ok(update) = PushUpdate::parse(frame) else {
+            return;
+        };
+
+        let targets: Vec<(u64, Deliver)> = self.sinks.lock().unwrap()
+            .iter()
+            .filter(|sink| sink.data_class == update.data_class && sink.codes.contains(&update.code))
+            .map(|sink| (sink.id, Arc::clone(&sink.deliver)))
+            .collect();
+        let closed: Vec<u64> = targets.into_iter()
+            .filter(|(_, deliver)| !deliver(&update))
+            .map(|(id, _)| id)
+            .collect();
+        if !closed.is_empty() {
+            self.sinks.lock().unwrap().retain(|sink| !closed.contains(&sink.id));
+        }
+    }
+
+    /// 添加通道接收方，返回接收方的编号和接收端
+    pub(crate) fn add_channel(&self, data_class: DataClass, codes: &[String]) -> (u64, Receiver<PushUpdate>) {
+        let (tx, rx): (Sender<PushUpdate>, _) = mpsc::channel();
+        let id = self.add_sink(data_class, codes, Arc::new(move |u| tx.send(u.clone()).is_ok()));
+        (id, rx)
+    }
+
+    /// 添加回调接收方，返回接收方的编号
+    pub(crate) fn add_callback<F>(&self, data_class: DataClass, codes: &[String], callback: F) -> u64
+    where F: Fn(PushUpdate) + Send + Sync + 'static {
+        self.add_sink(data_class, codes, Arc::new(move |u| {
+            callback(u.clone());
+            true
+        }))
+    }
+
+    fn add_sink(&self, data_class: DataClass, codes: &[String], deliver: Deliver) -> u64 {
+        let id = self.next_sink.fetch_add(1, Ordering::Relaxed);
+        self.sinks.lock().unwrap().push(Sink {
+            id,
+            data_class,
+            codes: codes.iter().cloned().collect(),
+            deliver,
+        });
+        id
+    }
+
+    /// 移除接收方，用于订阅请求失败时撤销
+    pub(crate) fn remove_sink(&self, id: u64) {
+        self.sinks.lock().unwrap().retain(|sink| sink.id != id);
+    }
+
+    /// 记录新订阅的代码
+    pub(crate) fn mark_subscribed(&self, data_class: DataClass, codes: &[String]) {
+        self.active.lock().unwrap()
+            .entry(data_class)
+            .or_default()
+            .extend(codes.iter().cloned());
+    }
+
+    /// 移除订阅的代码，同时从接收方中移除，不再关注任何代码的接收方会被丢弃
+    pub(crate) fn mark_unsubscribed(&self, data_class: DataClass, codes: &[String]) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(set) = active.get_mut(&data_class) {
+            for code in codes {
+                set.remove(code);
+            }
+            if set.is_empty() {
+                active.remove(&data_class);
+            }
+        }
+        drop(active);
+
+        let mut sinks = self.sinks.lock().unwrap();
+        for sink in sinks.iter_mut().filter(|s| s.data_class == data_class) {
+            for code in codes {
+                sink.codes.remove(code);
+            }
+        }
+        sinks.retain(|s| !s.codes.is_empty());
+    }
+
+    /// 当前订阅的全部代码
+    pub(crate) fn active(&self) -> Vec<(DataClass, Vec<String>)> {
+        self.active.lock().unwrap()
+            .iter()
+            .map(|(class, codes)| (*class, codes.iter().cloned().collect()))
+            .collect()
+    }
+}
@@ -1,22 +1,36 @@
-use std::collections::HashMap;
-use chrono::{DateTime, Local, TimeZone};
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use libloading::{Library};
-use std::path::PathBuf;
-use std::ffi::{CString, CStr};
-use std::os::raw::{c_char, c_int, c_void};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
 use once_cell::sync::OnceCell;
+use tracing::{debug, info, trace, warn};
 
 
-use crate::constants::{MARKETS, BLOCK_MARKETS};
-use crate::error::THSError;
+use crate::batch::{BatchOptions, BatchQuotes};
+use crate::code::{self, IntoSecurityCode, SecurityCode, SecurityType};
+use crate::constants::{BLOCK_MARKETS, DEFAULT_ZIP_VERSION, STOCK_QUOTE_DATA_TYPES, ZIP_VERSIONS};
+use crate::error::{ErrorKind, THSError};
+use crate::factor::AdjustFactors;
+use crate::fields::Record;
 use crate::guest;
-
-/// 静态变量，用于缓存库和函数指针
-static LIBRARY: OnceCell<Library> = OnceCell::new();
-static CALL_FN: OnceCell<unsafe extern "C" fn(*const c_char, *mut c_char, c_int, *const c_void) -> c_int> = OnceCell::new();
-
+use crate::kline::RangeOptions;
+use crate::library::{self, Candidate, LibraryInfo};
+use crate::query::{QueryRequest, SortOrder};
+use crate::replay::{redact_params, RecordedCall, Recorder};
+use crate::retry::{self, RetryPolicy};
+use crate::session::{Heartbeat, ReconnectPolicy};
+use crate::subscription::{self, DataClass, PushUpdate, SubOp, SubscriptionHub};
+use crate::timeout::{self, Deadline, TimeoutPolicy};
+use crate::transport::{CallStatus, LibTransport, PushHandler, Transport};
+use crate::types::{KLineData, Quote};
+use crate::worker::{WorkerOption, WorkerTransport};
 
 /// 初始化参数
 #[derive(Debug, Clone, Serialize, Deserialize,Default)]
@@ -24,14 +38,78 @@ pub struct ThsOption{
     pub username: String,
     pub password: String,
     pub lib_ver: String,
+    /// 动态库文件的完整路径，设置后忽略 `lib_dir`
+    #[serde(default)]
+    pub lib_path: Option<PathBuf>,
+    /// 动态库所在目录，未设置时依次使用环境变量 `RUSTHS_LIB_DIR` 和当前目录下的 `lib` 目录
+    #[serde(default)]
+    pub lib_dir: Option<PathBuf>,
+    /// 请求中使用的 zipversion，未设置时在连接后与服务器协商，见 [`THS::zip_version`]
+    #[serde(default)]
+    pub zip_version: Option<i32>,
+    /// 设置后在 `rusths-worker` 子进程中加载动态库，动态库崩溃或卡死不会影响当前进程
+    #[serde(default)]
+    pub worker: Option<WorkerOption>,
+    /// 连接和查询的重试策略
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// 会话失效后的重连策略
+    #[serde(default)]
+    pub reconnect: ReconnectPolicy,
+    /// 连接和查询的默认超时
+    #[serde(default)]
+    pub timeouts: TimeoutPolicy,
+    /// 启用推送订阅，默认关闭
+    ///
+    /// 订阅请求的方法名 `subscribe` 和参数格式、以及把推送回调作为 `Call` 的第四个参数交给动态库，
+    /// 都没有公开的协议文档可以对照，只应在验证过的动态库版本上启用。
+    #[serde(default)]
+    pub experimental_push: bool,
 }
 
-#[derive(Debug, Clone)]
+/// 行情客户端
+///
+/// 所有方法都只需要 `&self`，实例是 `Send + Sync` 的，可以放在 `Arc` 中由多个线程共享；
+/// 动态库调用由 `LibTransport` 在内部串行化。
+///
+/// 没有指定动态库版本时，会扫描动态库目录并从最新的版本开始使用，动态库加载失败或与服务器不兼容时依次换用更旧的版本，
+/// 实际使用的动态库和 zipversion 见 [`THS::library`]。
 pub struct THS {
     ops: ThsOption,
-    lib: &'static Library,
-    login: bool,
-    share_instance_id: i32,
+    /// 当前使用的账号和密码，轮换游客账号时会改变
+    credentials: RwLock<(String, String)>,
+    /// 是否使用的游客账号
+    guest: bool,
+    transport: RwLock<Arc<dyn Transport>>,
+    /// 当前使用的动态库，使用自定义调用通道时为 `None`
+    library: RwLock<Option<LibraryInfo>>,
+    /// 连接失败时可以换用的更旧的动态库
+    fallbacks: Mutex<Vec<Candidate>>,
+    zip_version: AtomicI32,
+    /// zipversion 已经确定（配置中指定或已经协商），换用动态库后需要重新协商
+    zip_negotiated: AtomicBool,
+    login: AtomicBool,
+    /// 调用过 `connect` 且没有主动断开，会话失效时需要重连
+    session_wanted: AtomicBool,
+    /// 每次连接成功加一，用于避免多个线程重复重连
+    session_gen: AtomicU64,
+    reconnect_lock: Mutex<()>,
+    share_instance_id: AtomicI32,
+    subscriptions: Arc<SubscriptionHub>,
+    push_installed: OnceCell<()>,
+    /// 开启录制时记录每一次调用
+    recorder: RwLock<Option<Recorder>>,
+}
+
+impl std::fmt::Debug for THS {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("THS")
+            .field("ops", &self.ops)
+            .field("library", &self.library)
+            .field("login", &self.login)
+            .field("share_instance_id", &self.share_instance_id)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,116 +120,353 @@ pub struct Response {
     pub payload: Payload,
 }
 
+impl Response {
+    /// 把 `payload.result` 中的每一行按字段解析，没有数据时返回空列表
+    pub fn records(&self) -> Result<Vec<Record>, THSError> {
+        match &self.payload.result {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(rows)) => rows.iter().map(Record::from_row).collect(),
+            Some(other) => Err(THSError::decode("返回数据格式错误", format!("应为数组: {}", other))),
+        }
+    }
+
+    /// `err_info` 不为空时转换为 `THSError::Server`
+    pub fn into_result(self) -> Result<Self, THSError> {
+        if self.err_info.is_empty() {
+            Ok(self)
+        } else {
+            Err(THSError::Server { err_info: self.err_info })
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Payload {
     pub result: Option<Value>,
     pub dict_extra: Option<HashMap<String, Value>>,
 }
 
-pub struct Adjust;
+/// 复权方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Adjust {
+    /// 前复权
+    #[serde(rename = "forward")]
+    Forward,
+    /// 后复权
+    #[serde(rename = "backward")]
+    Backward,
+    /// 不复权
+    #[default]
+    #[serde(rename = "", alias = "none")]
+    None,
+}
+
 impl Adjust {
-    pub const FORWARD: &'static str = "forward";
-    pub const BACKWARD: &'static str = "backward";
-    pub const NONE: &'static str = "";
+    pub const ALL: [Adjust; 3] = [Adjust::Forward, Adjust::Backward, Adjust::None];
+
+    /// 请求中使用的字符串
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Adjust::Forward => "forward",
+            Adjust::Backward => "backward",
+            Adjust::None => "",
+        }
+    }
+}
 
-    pub fn all_types() -> Vec<&'static str> {
-        vec![Self::FORWARD, Self::BACKWARD, Self::NONE]
+impl fmt::Display for Adjust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
-pub struct Interval;
+impl FromStr for Adjust {
+    type Err = THSError;
+
+    /// 接受请求中的字符串，不复权也可以写作 "none"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "forward" => Ok(Adjust::Forward),
+            "backward" => Ok(Adjust::Backward),
+            "" | "none" => Ok(Adjust::None),
+            _ => Err(THSError::ApiError(format!("无效的复权类型: {}", s))),
+        }
+    }
+}
+
+/// K线周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Interval {
+    #[serde(rename = "1m")]
+    Min1,
+    #[serde(rename = "5m")]
+    Min5,
+    #[serde(rename = "15m")]
+    Min15,
+    #[serde(rename = "30m")]
+    Min30,
+    #[serde(rename = "60m")]
+    Min60,
+    #[serde(rename = "120m")]
+    Min120,
+    #[serde(rename = "day")]
+    Day,
+    #[serde(rename = "week")]
+    Week,
+    #[serde(rename = "month")]
+    Month,
+    #[serde(rename = "quarter")]
+    Quarter,
+    #[serde(rename = "year")]
+    Year,
+}
+
 impl Interval {
-    pub const MIN_1: &'static str = "1m";
-    pub const MIN_5: &'static str = "5m";
-    pub const MIN_15: &'static str = "15m";
-    pub const MIN_30: &'static str = "30m";
-    pub const MIN_60: &'static str = "60m";
-    pub const MIN_120: &'static str = "120m";
-    pub const DAY: &'static str = "day";
-    pub const WEEK: &'static str = "week";
-    pub const MONTH: &'static str = "month";
-    pub const QUARTER: &'static str = "quarter";
-    pub const YEAR: &'static str = "year";
-
-    pub fn minute_intervals() -> Vec<&'static str> {
-        vec![
-            Self::MIN_1,
-            Self::MIN_5,
-            Self::MIN_15,
-            Self::MIN_30,
-            Self::MIN_60,
-            Self::MIN_120,
-        ]
-    }
-
-    pub fn day_and_above_intervals() -> Vec<&'static str> {
-        vec![
-            Self::DAY,
-            Self::WEEK,
-            Self::MONTH,
-            Self::QUARTER,
-            Self::YEAR,
-        ]
-    }
-
-    pub fn all_types() -> Vec<&'static str> {
-        let mut all = Self::minute_intervals();
-        all.extend(Self::day_and_above_intervals());
-        all
+    pub const ALL: [Interval; 11] = [
+        Interval::Min1,
+        Interval::Min5,
+        Interval::Min15,
+        Interval::Min30,
+        Interval::Min60,
+        Interval::Min120,
+        Interval::Day,
+        Interval::Week,
+        Interval::Month,
+        Interval::Quarter,
+        Interval::Year,
+    ];
+
+    /// 请求中使用的字符串
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::Min1 => "1m",
+            Interval::Min5 => "5m",
+            Interval::Min15 => "15m",
+            Interval::Min30 => "30m",
+            Interval::Min60 => "60m",
+            Interval::Min120 => "120m",
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+            Interval::Quarter => "quarter",
+            Interval::Year => "year",
+        }
+    }
+
+    /// 分钟周期的分钟数，日线及以上返回 `None`
+    pub fn minutes(&self) -> Option<u32> {
+        match self {
+            Interval::Min1 => Some(1),
+            Interval::Min5 => Some(5),
+            Interval::Min15 => Some(15),
+            Interval::Min30 => Some(30),
+            Interval::Min60 => Some(60),
+            Interval::Min120 => Some(120),
+            Interval::Day | Interval::Week | Interval::Month | Interval::Quarter | Interval::Year => None,
+        }
+    }
+
+    pub fn is_minute(&self) -> bool {
+        self.minutes().is_some()
+    }
+
+    /// 证券类型支持的周期
+    ///
+    /// `klines` 接口对所有证券使用同一组周期参数，原来的 `Interval::all_types()` 也不区分证券类型，
+    /// 因此各类证券都支持全部周期；某个证券没有数据时由服务器返回空结果或错误。
+    /// 增加按类型的限制时需要注明依据。
+    pub fn supported_by(&self, security_type: SecurityType) -> bool {
+        match security_type {
+            SecurityType::Stock
+            | SecurityType::Index
+            | SecurityType::Fund
+            | SecurityType::Bond
+            | SecurityType::Option
+            | SecurityType::Delisting => true,
+        }
+    }
+
+    /// 证券类型支持的全部周期
+    pub fn supported(security_type: SecurityType) -> Vec<Interval> {
+        Self::ALL.into_iter().filter(|i| i.supported_by(security_type)).collect()
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Interval {
+    type Err = THSError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Self::ALL.into_iter()
+            .find(|i| i.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| THSError::ApiError(format!("无效的周期类型: {}", s)))
     }
 }
 
 impl THS {
     pub fn new(ops: Option<ThsOption>) -> Result<Self, THSError> {
-        let mut ops = ops.unwrap_or_default();
-        if ops.username.is_empty() || ops.password.is_empty() {
+        let ops = ops.unwrap_or_default();
+        if std::env::consts::ARCH == "aarch64" {
+            return Err(THSError::UnsupportedPlatform("Apple M系列芯片暂不支持".into()));
+        }
+
+        let mut candidates = library::candidates(ops.lib_path.as_deref(), ops.lib_dir.as_deref(), &ops.lib_ver);
+        if candidates.is_empty() {
+            return Err(THSError::LibraryError(format!(
+                "未找到动态库，搜索目录: {:?}",
+                library::search_dirs(ops.lib_dir.as_deref())
+            )));
+        }
+
+        let (transport, candidate) = Self::load_first(&mut candidates, ops.worker.as_ref())?;
+        let ths = Self::with_shared_transport(ops, transport);
+        ths.use_library(&candidate);
+        *ths.fallbacks.lock().unwrap() = candidates;
+        Ok(ths)
+    }
+
+    /// 依次加载候选的动态库，返回第一个加载成功的，失败的候选会被移除
+    fn load_first(candidates: &mut Vec<Candidate>, worker: Option<&WorkerOption>) -> Result<(Arc<dyn Transport>, Candidate), THSError> {
+        let mut last_error = None;
+        while !candidates.is_empty() {
+            let candidate = candidates.remove(0);
+            let transport: Result<Arc<dyn Transport>, THSError> = match worker {
+                Some(worker) => WorkerTransport::spawn(worker.clone(), &candidate.path, &candidate.version)
+                    .map(|t| Arc::new(t) as Arc<dyn Transport>),
+                None => LibTransport::load(&candidate.path, &candidate.version)
+                    .map(|t| Arc::new(t) as Arc<dyn Transport>),
+            };
+            match transport {
+                Ok(transport) => return Ok((transport, candidate)),
+                Err(e) => {
+                    warn!(path = %candidate.path.display(), version = %candidate.version, error = %e, "加载动态库失败");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| THSError::LibraryError("没有可用的动态库".into())))
+    }
+
+    fn use_library(&self, candidate: &Candidate) {
+        if self.ops.zip_version.is_none() {
+            self.zip_version.store(DEFAULT_ZIP_VERSION, Ordering::SeqCst);
+            self.zip_negotiated.store(false, Ordering::SeqCst);
+        }
+        let info = LibraryInfo {
+            path: candidate.path.clone(),
+            version: candidate.version.clone(),
+            zip_version: self.zip_version(),
+        };
+        info!(path = %info.path.display(), version = %info.version, zip_version = info.zip_version, "使用动态库");
+        *self.library.write().unwrap_or_else(|e| e.into_inner()) = Some(info);
+    }
+
+    /// 是否是换用更旧的动态库可能解决的错误
+    ///
+    /// 只有动态库本身的问题（缺少方法、调用失败、返回无法解析）才换用，
+    /// 服务器返回的错误（如密码错误）、网络错误和超时换用动态库也无法解决。
+    fn is_library_failure(err: &THSError) -> bool {
+        match err {
+            THSError::LibraryBusy { .. } => false,
+            e => matches!(e.kind(), ErrorKind::Library | ErrorKind::MethodNotFound | ErrorKind::Decode),
+        }
+    }
+
+    /// 换用下一个更旧的动态库，没有可用的动态库时返回 false
+    pub(crate) fn fall_back(&self, err: &THSError) -> bool {
+        if !Self::is_library_failure(err) {
+            return false;
+        }
+        let mut fallbacks = self.fallbacks.lock().unwrap_or_else(|e| e.into_inner());
+        if fallbacks.is_empty() {
+            return false;
+        }
+        let Ok((transport, candidate)) = Self::load_first(&mut fallbacks, self.ops.worker.as_ref()) else {
+            return false;
+        };
+        drop(fallbacks);
+
+        warn!(version = %candidate.version, error = %err, "连接失败，换用更旧的动态库");
+        if self.push_installed.get().is_some()
+            && let Err(e) = transport.set_push_handler(self.push_handler()) {
+            warn!(error = %e, "设置推送处理函数失败");
+        }
+        *self.transport.write().unwrap_or_else(|e| e.into_inner()) = transport;
+        self.use_library(&candidate);
+        true
+    }
+
+    /// 使用指定的调用通道创建实例，例如测试中使用的 `MockTransport`
+    pub fn with_transport(ops: Option<ThsOption>, transport: impl Transport + 'static) -> Self {
+        Self::with_shared_transport(ops.unwrap_or_default(), Arc::new(transport))
+    }
+
+    fn with_shared_transport(mut ops: ThsOption, transport: Arc<dyn Transport>) -> Self {
+        let guest = ops.username.is_empty() || ops.password.is_empty();
+        if guest {
             let account = guest::rand_account();
             ops.username  = account.0;
             ops.password = account.1;
         }
 
-        // let lib = Self::load_library()?;
-        // let default_ver = String::new();
-        let lib_ver = ops.lib_ver.clone();
-        let lib_path = Self::get_lib_path(&*lib_ver)?;
-        let lib: &Library  = LIBRARY.get_or_init(|| unsafe {Library::new(lib_path).unwrap()});
-
-        
-        Ok(Self {
+        Self {
+            credentials: RwLock::new((ops.username.clone(), ops.password.clone())),
+            guest,
+            zip_version: AtomicI32::new(ops.zip_version.unwrap_or(DEFAULT_ZIP_VERSION)),
+            zip_negotiated: AtomicBool::new(ops.zip_version.is_some()),
             ops,
-            lib,
-            login: false,
-            share_instance_id: 6666666 + rand::random::<i32>().abs() % 2222222,
-        })
+            transport: RwLock::new(transport),
+            library: RwLock::new(None),
+            fallbacks: Mutex::new(Vec::new()),
+            login: AtomicBool::new(false),
+            session_wanted: AtomicBool::new(false),
+            session_gen: AtomicU64::new(0),
+            reconnect_lock: Mutex::new(()),
+            share_instance_id: AtomicI32::new(6666666 + rand::random::<i32>().abs() % 2222222),
+            subscriptions: Arc::new(SubscriptionHub::default()),
+            push_installed: OnceCell::new(),
+            recorder: RwLock::new(None),
+        }
     }
 
 
-    fn get_lib_path(version:&str) -> Result<PathBuf, THSError> {
-        if std::env::consts::ARCH == "aarch64" {
-            return Err(THSError::UnsupportedPlatform("Apple M系列芯片暂不支持".into()));
-        }
+    /// 是否已经登录
+    pub fn is_logged_in(&self) -> bool {
+        self.login.load(Ordering::SeqCst)
+    }
 
-        let base_dir = std::env::current_dir()?;
-        
-        #[cfg(target_os = "linux")]
-        let lib_name = format!("hq{}.so", version);
-        #[cfg(target_os = "macos")]
-        let lib_name = format!("hq{}.dylib", version);
-        #[cfg(target_os = "windows")]
-        let lib_name = format!("hq{}.dll", version);
+    /// 请求中使用的 zipversion
+    ///
+    /// 配置中没有指定时，第一次连接成功后从 `ZIP_VERSIONS` 中依次尝试，使用第一个能正常返回数据的版本。
+    pub fn zip_version(&self) -> i32 {
+        self.zip_version.load(Ordering::SeqCst)
+    }
 
-        let lib_path = base_dir.join("lib").join(lib_name);
-        Ok(lib_path)
+    /// 当前使用的动态库，使用自定义调用通道创建的实例返回 `None`
+    pub fn library(&self) -> Option<LibraryInfo> {
+        self.library.read().unwrap_or_else(|e| e.into_inner()).clone()
     }
 
-    pub fn zip_version(&self) -> i32 {
-        2
+    /// 当前使用的动态库版本，未知时为配置中的 `lib_ver`
+    pub fn lib_version(&self) -> String {
+        match &*self.library.read().unwrap_or_else(|e| e.into_inner()) {
+            Some(info) => info.version.clone(),
+            None => self.ops.lib_ver.clone(),
+        }
+    }
+
+    fn transport(&self) -> Arc<dyn Transport> {
+        Arc::clone(&self.transport.read().unwrap_or_else(|e| e.into_inner()))
     }
 
-    pub fn next_share_instance_id(&mut self) -> i32 {
-        let id = self.share_instance_id;
-        self.share_instance_id += 1;
-        id
+    pub fn next_share_instance_id(&self) -> i32 {
+        self.share_instance_id.fetch_add(1, Ordering::Relaxed)
     }
 
     /// 这里的 params参数 python 可以支持多类型 数据，但是 rust只能是 String 类型的，所以，如果传入的参数在python中是对象，那么前后就不用加 ""
@@ -160,93 +475,335 @@ impl THS {
     /// string     "text"             "\"test\""
     /// dict       {'key':'value'}    "{"key":"value"}"
     /// 泛型版本的 call 方法，支持返回不同类型
-    pub fn call<T>(&mut self, method: &str, params: Option<String>, buffer_size: usize) -> Result<T, THSError> 
+    pub fn call<T>(&self, method: &str, params: Option<String>, buffer_size: usize) -> Result<T, THSError> 
     where T: serde::de::DeserializeOwned {
-        let input = format!(
-            r#"{{"method":"{}","params":{}}}"#,
-            method,
-            params.as_deref().unwrap_or("")
-        );
+        self.call_until(method, params, buffer_size, self.query_deadline())
+    }
+
+    /// 在截止时间前完成一次调用
+    fn call_until<T>(&self, method: &str, params: Option<String>, buffer_size: usize, deadline: Option<Deadline>) -> Result<T, THSError>
+    where T: serde::de::DeserializeOwned {
+        trace!(method, buffer_size, params = %redact_params(method, params.as_deref().unwrap_or("")), "调用动态库");
+        let started = Instant::now();
+        let status = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.remaining().ok_or_else(|| deadline.error(method))?;
+                self.transport()
+                    .call_timeout(method, params.as_deref(), buffer_size, remaining)
+                    .map_err(|e| match e {
+                        THSError::Timeout { .. } if deadline.expired() => deadline.error(method),
+                        e => e,
+                    })?
+            }
+            None => self.transport().call(method, params.as_deref(), buffer_size)?,
+        };
+        self.record(method, params.as_deref(), buffer_size, &status);
+        debug!(method, buffer_size, elapsed = ?started.elapsed(), return_code = status.code(), "动态库调用完成");
+
+        match status {
+            CallStatus::Ok(output) => {
+                if !output.is_empty() {
+                    serde_json::from_str::<T>(&output).map_err(|e| THSError::decode("JSON解析失败", e))
+                } else {
+                    serde_json::from_str::<T>("{\"errInfo\":\"\",\"payload\":{}}").map_err(|e| THSError::decode("JSON解析失败", e))
+                }
+            },
+            CallStatus::BufferTooSmall => Err(THSError::BufferTooSmall { size: buffer_size }),
+            CallStatus::Error(code) => Err(THSError::MethodNotFound { code, method: method.to_string() }),
+        }
+    }
 
-        let input_str = CString::new(input).map_err(|e| THSError::ApiError(format!("无效的输入参数: {}", e)))?;
+    /// 开始录制，之后的每一次调用都追加写入 `path`，格式见 [`RecordedCall`]
+    ///
+    /// 录制的文件可以交给 [`ReplayTransport`](crate::replay::ReplayTransport) 回放。
+    pub fn start_recording(&self, path: impl AsRef<Path>) -> Result<(), THSError> {
+        let recorder = Recorder::create(path)?;
+        *self.recorder.write().unwrap_or_else(|e| e.into_inner()) = Some(recorder);
+        Ok(())
+    }
 
-        let mut output_buffer = vec![0u8; buffer_size];
-        let output_ptr = output_buffer.as_mut_ptr() as *mut c_char;
+    /// 停止录制
+    pub fn stop_recording(&self) {
+        self.recorder.write().unwrap_or_else(|e| e.into_inner()).take();
+    }
 
-        unsafe {
-            // 复杂的写法
-            // let call_fn = CALL_FN.get_or_init(|| {
-            //     *self.lib.get::<unsafe extern "C" fn(*const c_char, *mut c_char, c_int, *const c_void) -> c_int>
-            //     (b"Call")
-            //         .map_err(|e| THSError::LibraryError(e.to_string())).unwrap()
-            //     }
-            // );
+    fn record(&self, method: &str, params: Option<&str>, buffer_size: usize, status: &CallStatus) {
+        if let Some(recorder) = self.recorder.read().unwrap_or_else(|e| e.into_inner()).as_ref()
+            && let Err(e) = recorder.record(&RecordedCall::new(method, params, buffer_size, status)) {
+            warn!(method, error = %e, "录制调用失败");
+        }
+    }
 
-            let call_fn = CALL_FN.get_or_init(|| { *self.lib.get(b"Call").unwrap() } );
+    // 为了保持向后兼容性，添加一个专门返回 Response 类型的方法
+    // pub fn call_response(&self, method: &str, params: Option<String>, buffer_size: usize) -> Result<Response, THSError> {
+    //     self.call::<Response>(method, params, buffer_size)
+    // }
 
-            let result = call_fn(input_str.as_ptr(), output_ptr, buffer_size as c_int, std::ptr::null());
+    /// 连接服务器，按重试策略重试，动态库本身出错时换用更旧的动态库再次连接
+    ///
+    /// 整个过程不超过 `ThsOption::timeouts.connect`。
+    pub fn connect(&self) -> Result<Response, THSError> {
+        self.connect_until(self.connect_deadline(), true)
+    }
 
-            match result {
-                0 => {
-                    let output = CStr::from_ptr(output_ptr).to_str().map_err(|e| THSError::ApiError(format!("输出解码失败: {}", e)))?;
-                    if output.len() != 0 {
-                        serde_json::from_str::<T>(output).map_err(|e| THSError::ApiError(format!("JSON解析失败: {}", e)))
-                    } else {
-                        serde_json::from_str::<T>("{\"errInfo\":\"\",\"payload\":{}}").map_err(|e| THSError::ApiError(format!("JSON解析失败: {}", e)))
+    /// `fall_back` 为 false 时不换用动态库，用于会话中的重连，短暂的网络故障不应导致降级
+    fn connect_until(&self, deadline: Option<Deadline>, fall_back: bool) -> Result<Response, THSError> {
+        loop {
+            match self.connect_with_retry(deadline) {
+                Ok(response) => return Ok(response),
+                Err(e) if fall_back && !deadline.is_some_and(|d| d.expired()) && self.fall_back(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn connect_with_retry(&self, deadline: Option<Deadline>) -> Result<Response, THSError> {
+        let policy = self.retry_policy();
+        let mut attempt = 0;
+        loop {
+            match self.connect_once(deadline) {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    warn!(method = "connect", attempt = attempt + 1, error = %e, "连接尝试失败");
+                    attempt += 1;
+                    if attempt >= policy.max_attempts()
+                        || !policy.is_retryable(&e)
+                        || deadline.is_some_and(|d| d.expired())
+                        || retry::backoff_deferred() {
+                        return Err(e);
                     }
-                },
-                -1 => Err(THSError::ApiError(format!(
-                    "缓冲区大小不足,当前大小: {:.2} MB",
-                    buffer_size as f64 / (1024.0 * 1024.0)
-                ))),
-                _ => Err(THSError::ApiError(format!(
-                    "错误代码: {}, 未找到方法: {}",
-                    result, method
-                ))),
+                }
             }
+            std::thread::sleep(Self::backoff(policy.delay(attempt - 1), deadline));
         }
     }
 
-    // 为了保持向后兼容性，添加一个专门返回 Response 类型的方法
-    // pub fn call_response(&mut self, method: &str, params: Option<String>, buffer_size: usize) -> Result<Response, THSError> {
-    //     self.call::<Response>(method, params, buffer_size)
-    // }
+    /// 进行一次连接尝试，服务器返回错误信息时视为失败
+    pub(crate) fn connect_once(&self, deadline: Option<Deadline>) -> Result<Response, THSError> {
+        let started = Instant::now();
+        let (username, password) = self.credentials.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let param = serde_json::json!({
+            "username": username,
+            "password": password,
+            "lib_ver": self.lib_version(),
+        }).to_string();
+        let response = self.call_until::<Response>("connect", Some(param), 10 * 1024, deadline)?.into_result()?;
+
+        self.session_gen.fetch_add(1, Ordering::SeqCst);
+        self.login.store(true, Ordering::SeqCst);
+        self.session_wanted.store(true, Ordering::SeqCst);
+        info!(method = "connect", username = %username, elapsed = ?started.elapsed(), "成功连接到服务器");
+        if !self.zip_negotiated.load(Ordering::SeqCst) {
+            self.negotiate_zip_version(deadline);
+        }
+        if let Err(e) = self.resubscribe() {
+            warn!(method = "subscribe", error = %e, "重新订阅失败");
+        }
+        Ok(response)
+    }
 
-    pub fn connect(&mut self) -> Result<Response, THSError> {
-        for attempt in 0..5 {
-            let param = serde_json::to_string(&self.ops).unwrap();
-            match self.call::<Response>("connect", Some(param), 10 * 1024) {
-                Ok(response) => {
-                    if response.err_info.is_empty() {
-                        self.login = true;
-                        println!("✅ 成功连接到服务器");
-                        return Ok(response);
-                    } else {
-                        println!("❌ 第 {} 次连接尝试失败: {}", attempt + 1, response.err_info);
+    /// 依次尝试 `ZIP_VERSIONS`，使用第一个能正常返回数据的版本
+    ///
+    /// 服务器返回错误或数据无法解析时尝试下一个版本；其它错误（如超时）无法判断，保留当前版本，下次连接时再协商。
+    fn negotiate_zip_version(&self, deadline: Option<Deadline>) {
+        let req = QueryRequest::new(200).codelist("USHI", &["1A0001"]).datatypes(&[10]);
+        let method = format!("cmd.query_data.{}", req.service_key());
+        for zip_version in ZIP_VERSIONS {
+            let params = req.encode(self.next_share_instance_id(), zip_version);
+            let result = self.call_until::<Response>(&method, Some(params), req.output_size(), deadline)
+                .and_then(Response::into_result)
+                .and_then(|response| response.records());
+            match result {
+                Ok(_) => {
+                    self.zip_version.store(zip_version, Ordering::SeqCst);
+                    self.zip_negotiated.store(true, Ordering::SeqCst);
+                    if let Some(info) = self.library.write().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                        info.zip_version = zip_version;
                     }
+                    info!(zip_version, "协商 zipversion 完成");
+                    return;
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::Server | ErrorKind::Decode) => {
+                    debug!(zip_version, error = %e, "zipversion 不可用，尝试下一个");
                 }
                 Err(e) => {
-                    println!("❌ 连接报错: {}", e);
+                    warn!(zip_version = self.zip_version(), error = %e, "无法协商 zipversion，暂时使用当前版本");
+                    return;
                 }
             }
-            std::thread::sleep(std::time::Duration::from_secs(1 << attempt));
         }
-        Err(THSError::ApiError("尝试 5 次后连接失败".into()))
+        self.zip_negotiated.store(true, Ordering::SeqCst);
+        warn!(zip_version = self.zip_version(), "没有可用的 zipversion，使用默认版本");
+    }
+
+    /// 当前使用的重试策略
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.ops.retry
+    }
+
+    /// 在 `timeout` 内执行 `f`，覆盖 `f` 中所有调用的默认超时
+    ///
+    /// `timeout` 是 `f` 中全部调用共用的时间上限，超时的调用返回 `THSError::Timeout`，客户端仍然可以继续使用。
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use rusths::ths::THS;
+    /// # let ths = THS::new(None).unwrap();
+    /// let quotes = ths.with_timeout(Duration::from_secs(3), |ths| ths.quotes("USHA600000", None));
+    /// ```
+    pub fn with_timeout<R>(&self, timeout: Duration, f: impl FnOnce(&Self) -> R) -> R {
+        timeout::with_scoped(Deadline::after(timeout), || f(self))
+    }
+
+    /// 查询的截止时间，`with_timeout` 中使用其设置的时间
+    pub(crate) fn query_deadline(&self) -> Option<Deadline> {
+        timeout::scoped().or_else(|| self.ops.timeouts.query.map(Deadline::after))
+    }
+
+    pub(crate) fn connect_deadline(&self) -> Option<Deadline> {
+        timeout::scoped().or_else(|| self.ops.timeouts.connect.map(Deadline::after))
+    }
+
+    /// 按重试策略需要等待后重试的错误；缓冲区不足时立即重试，会话失效交给重连
+    #[cfg(feature = "async")]
+    pub(crate) fn backs_off(&self, err: &THSError) -> bool {
+        self.retry_policy().is_retryable(err)
+            && err.kind() != ErrorKind::BufferTooSmall
+            && !self.ops.reconnect.is_lost_error(err)
+    }
+
+    /// 重试前的等待时间，不超过剩余时间
+    pub(crate) fn backoff(delay: Duration, deadline: Option<Deadline>) -> Duration {
+        match deadline {
+            Some(deadline) => delay.min(deadline.remaining().unwrap_or_default()),
+            None => delay,
+        }
     }
 
-    pub fn disconnect(&mut self) -> Result<(), THSError> {
-        if self.login {
-            self.login = false;
+    /// 会话失效时重新连接
+    ///
+    /// `generation` 为发起请求时的会话编号，其它线程已经完成重连时直接返回。
+    fn reconnect(&self, generation: u64, deadline: Option<Deadline>) -> Result<(), THSError> {
+        let _guard = self.reconnect_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if self.is_logged_in() && self.session_gen.load(Ordering::SeqCst) != generation {
+            return Ok(());
+        }
+
+        self.login.store(false, Ordering::SeqCst);
+        if self.guest && self.ops.reconnect.rotate_guest {
+            let account = guest::rand_account();
+            info!(method = "connect", username = %account.0, "更换游客账号");
+            *self.credentials.write().unwrap_or_else(|e| e.into_inner()) = account;
+        }
+
+        info!(method = "connect", "会话失效，重新连接");
+        self.connect_until(deadline, false).map(|_| ())
+    }
+
+    /// 发送请求，会话失效时按 `ReconnectPolicy` 重连并重放一次
+    ///
+    /// 重试、重连和重放都在同一个截止时间内完成。
+    fn request(&self, method: &str, params: Option<String>, buffer_size: usize) -> Result<Response, THSError> {
+        let policy = &self.ops.reconnect;
+        let auto_reconnect = policy.auto_reconnect && self.session_wanted.load(Ordering::SeqCst);
+        let deadline = self.query_deadline();
+
+        if auto_reconnect && !self.is_logged_in() {
+            self.reconnect(self.session_gen.load(Ordering::SeqCst), deadline)?;
+        }
+
+        let generation = self.session_gen.load(Ordering::SeqCst);
+        match self.call_with_retry(method, params.clone(), buffer_size, deadline) {
+            Err(e) if auto_reconnect && policy.is_lost_error(&e) => {
+                warn!(method, error = %e, "会话失效，重连后重放请求");
+                self.reconnect(generation, deadline)?;
+                self.call_with_retry(method, params, buffer_size, deadline)
+            }
+            result => result,
+        }
+    }
+
+    /// 启动心跳线程，每隔 `interval` 发送一次轻量查询，会话失效时自动重连
+    ///
+    /// 心跳线程只持有弱引用，客户端释放后自动退出。无法创建线程时返回 `THSError::IoError`。
+    pub fn start_heartbeat(self: &Arc<Self>, interval: Duration) -> Result<Heartbeat, THSError> {
+        Heartbeat::spawn(Arc::downgrade(self), interval)
+    }
+
+    /// 发送一次心跳，没有连接时什么都不做
+    pub(crate) fn heartbeat(&self) -> Result<(), THSError> {
+        if !self.session_wanted.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.stock_market_data_with("USHI1A0001", &[10]).map(|_| ())
+    }
+
+    /// 按重试策略调用，缓冲区不足时加倍后立即重试，其它可重试的错误等待后重试
+    ///
+    /// 服务器返回的 `err_info` 转换为 `THSError::Server`；表示会话失效的错误不在这里重试，交给 `request` 重连。
+    fn call_with_retry(&self, method: &str, params: Option<String>, buffer_size: usize, deadline: Option<Deadline>) -> Result<Response, THSError> {
+        let policy = self.retry_policy();
+        let started = Instant::now();
+        let mut current_buffer_size = buffer_size;
+        let mut attempt = 0;
+
+        loop {
+            let result = self.call_until::<Response>(method, params.clone(), current_buffer_size, deadline)
+                .and_then(Response::into_result);
+            let err = match result {
+                Ok(result) => {
+                    debug!(
+                        method,
+                        buffer_size = current_buffer_size,
+                        attempt = attempt + 1,
+                        elapsed = ?started.elapsed(),
+                        "调用完成"
+                    );
+                    return Ok(result);
+                }
+                Err(e) => e,
+            };
+
+            attempt += 1;
+            if attempt >= policy.max_attempts()
+                || !policy.is_retryable(&err)
+                || self.ops.reconnect.is_lost_error(&err)
+                || deadline.is_some_and(|d| d.expired()) {
+                return Err(err);
+            }
+
+            if err.kind() == ErrorKind::BufferTooSmall {
+                debug!(
+                    method,
+                    buffer_size = current_buffer_size,
+                    new_buffer_size = current_buffer_size * 2,
+                    attempt,
+                    "缓冲区大小不足，扩大后重试"
+                );
+                current_buffer_size *= 2;
+            } else if retry::backoff_deferred() {
+                return Err(err);
+            } else {
+                let delay = Self::backoff(policy.delay(attempt - 1), deadline);
+                warn!(method, attempt, error = %err, delay = ?delay, "调用失败，等待后重试");
+                std::thread::sleep(delay);
+            }
+        }
+    }
+
+    pub fn disconnect(&self) -> Result<(), THSError> {
+        self.session_wanted.store(false, Ordering::SeqCst);
+        if self.login.swap(false, Ordering::SeqCst) {
             self.call::<Response>("disconnect", None, 1024)?;
-            println!("✅ 已成功断开与行情服务器的连接");
+            info!(method = "disconnect", "已断开与行情服务器的连接");
         } else {
-            println!("✅ 已经断开连接");
+            debug!(method = "disconnect", "已经断开连接");
         }
         Ok(())
     }
 
-    pub fn help(&mut self, req: &str) -> Result<String, THSError> {
-        let response = self.call::<Response>("help", Some(req.to_string()), 1024)?;
+    pub fn help(&self, req: &str) -> Result<String, THSError> {
+        let response = self.request("help", Some(req.to_string()), 1024)?;
         
         match response.payload.result {
             Some(serde_json::Value::String(s)) => Ok(s),
@@ -260,76 +817,263 @@ impl THS {
         }
     }
 
-    fn cmd_query_data(&mut self, req: String, service_key: &str, buffer_size: usize, max_attempts: usize) -> Result<Response, THSError> {
-        if !self.login {
-            return Err(THSError::ApiError("未登录".into()));
+    /// 发送 `cmd.query_data.*` 查询，用于 crate 尚未封装的 id 和 datatype 组合
+    ///
+    /// `instance` 和 `zipversion` 由客户端填写，返回的数据不做处理。
+    pub fn query_raw(&self, req: &QueryRequest) -> Result<Response, THSError> {
+        if !self.is_logged_in() && !self.session_wanted.load(Ordering::SeqCst) {
+            return Err(THSError::NotLoggedIn);
         }
 
-        let mut current_buffer_size = buffer_size;
-        let mut attempt = 0;
+        let method = format!("cmd.query_data.{}", req.service_key());
+        let params = req.encode(self.next_share_instance_id(), self.zip_version());
+        trace!(method = %method, req = %params, "查询请求");
+        self.request(&method, Some(params), req.output_size())
+    }
+
+    /// 订阅推送数据，返回接收推送的通道
+    ///
+    /// 订阅会在重新连接后自动恢复；接收端被丢弃后不再向它分发。
+    /// 需要设置 [`ThsOption::experimental_push`]，否则返回错误。
+    pub fn subscribe(&self, data_class: DataClass, codes: &[&str]) -> Result<Receiver<PushUpdate>, THSError> {
+        let codes = Self::subscription_codes(codes)?;
+        self.install_push_handler()?;
+        // 先添加接收方，订阅成功后立即到达的推送不会丢失；订阅失败时撤销
+        let (sink, rx) = self.subscriptions.add_channel(data_class, &codes);
+        if let Err(e) = self.send_subscription(SubOp::Subscribe, data_class, &codes) {
+            self.subscriptions.remove_sink(sink);
+            return Err(e);
+        }
+        self.subscriptions.mark_subscribed(data_class, &codes);
+        Ok(rx)
+    }
+
+    /// 订阅推送数据，推送到达时在推送线程中调用 `callback`
+    ///
+    /// `callback` 中可以订阅或取消订阅。
+    pub fn subscribe_with<F>(&self, data_class: DataClass, codes: &[&str], callback: F) -> Result<(), THSError>
+    where F: Fn(PushUpdate) + Send + Sync + 'static {
+        let codes = Self::subscription_codes(codes)?;
+        self.install_push_handler()?;
+        let sink = self.subscriptions.add_callback(data_class, &codes, callback);
+        if let Err(e) = self.send_subscription(SubOp::Subscribe, data_class, &codes) {
+            self.subscriptions.remove_sink(sink);
+            return Err(e);
+        }
+        self.subscriptions.mark_subscribed(data_class, &codes);
+        Ok(())
+    }
+
+    /// 取消订阅
+    pub fn unsubscribe(&self, data_class: DataClass, codes: &[&str]) -> Result<(), THSError> {
+        let codes = Self::subscription_codes(codes)?;
+        self.subscriptions.mark_unsubscribed(data_class, &codes);
+        self.send_subscription(SubOp::Unsubscribe, data_class, &codes)
+    }
+
+    /// 重新发送当前的全部订阅，连接成功后会自动调用
+    pub fn resubscribe(&self) -> Result<(), THSError> {
+        for (data_class, codes) in self.subscriptions.active() {
+            self.send_subscription(SubOp::Subscribe, data_class, &codes)?;
+        }
+        Ok(())
+    }
+
+    fn subscription_codes(codes: &[&str]) -> Result<Vec<String>, THSError> {
+        if codes.is_empty() {
+            return Err(THSError::ApiError("必须提供订阅的证券代码".into()));
+        }
+        codes.iter()
+            .map(|code| code.into_security_code().map(|c| c.ths_code()))
+            .collect()
+    }
+
+    fn install_push_handler(&self) -> Result<(), THSError> {
+        if !self.ops.experimental_push {
+            return Err(THSError::ApiError("推送订阅需要设置 experimental_push".into()));
+        }
+        self.push_installed.get_or_try_init(|| self.transport().set_push_handler(self.push_handler()))?;
+        Ok(())
+    }
 
-        while attempt < max_attempts {
-            match self.call::<Response>(
-                &format!("cmd.query_data.{}", service_key),
-                Some(req.clone()),
-                current_buffer_size,
-            ) {
-                Ok(response) => {
-                    if !response.err_info.is_empty() {
-                        println!("查询数据错误信息: {}", response.err_info);
+    fn push_handler(&self) -> PushHandler {
+        let hub = Arc::clone(&self.subscriptions);
+        Arc::new(move |frame: &str| hub.dispatch(frame))
+    }
+
+    fn send_subscription(&self, op: SubOp, data_class: DataClass, codes: &[String]) -> Result<(), THSError> {
+        if !self.is_logged_in() {
+            return Err(THSError::NotLoggedIn);
+        }
+
+        let params = subscription::request_params(op, data_class, codes);
+        self.call::<Response>("subscribe", Some(params), 10 * 1024)?.into_result()?;
+        Ok(())
+    }
+
+    pub fn klines(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        adjust: Adjust,
+        interval: Interval,
+        count: i32,
+    ) -> Result<Response, THSError> {
+        let mut response = self.klines_raw(ths_code, start_time, end_time, adjust, interval, count)?;
+
+        // 处理返回数据中的时间字段
+        if let Some(serde_json::Value::Array(arr)) = response.payload.result.as_mut() {
+            for item in arr {
+                if let Some(obj) = item.as_object_mut()
+                    && let Some(time_value) = obj.get("时间") {
+                    if interval.is_minute() {
+                        if let Some(time_int) = time_value.as_i64() {
+                            let hours = time_int / 10000;
+                            let minutes = (time_int % 10000) / 100;
+                            let seconds = time_int % 100;
+                            let time_str = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
+                            obj.insert("时间".to_string(), serde_json::Value::String(time_str));
+                        }
+                    } else if let Some(time_str) = time_value.as_str()
+                        && let Ok(date) = NaiveDate::parse_from_str(time_str, "%Y%m%d") {
+                        obj.insert("时间".to_string(), serde_json::Value::String(date.format("%Y-%m-%d").to_string()));
                     }
-                    println!("查询执行: {}, 类型: {}", req, service_key);
-                    return Ok(response);
-                }
-                Err(THSError::ApiError(e)) if e.contains("缓冲区大小不足") => {
-                    let current_size_mb = current_buffer_size as f64 / (1024.0 * 1024.0);
-                    let new_size_mb = (current_buffer_size * 2) as f64 / (1024.0 * 1024.0);
-                    println!(
-                        "缓冲区大小不足。当前大小: {:.2} MB, 新的大小: {:.2} MB",
-                        current_size_mb, new_size_mb
-                    );
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                    current_buffer_size *= 2;
-                    attempt += 1;
                 }
-                Err(e) => return Err(e),
             }
         }
 
-        Err(THSError::ApiError(format!(
-            "达到最大尝试次数，请求: {}, 最终缓冲区大小: {}",
-            req, current_buffer_size
-        )))
+        Ok(response)
     }
 
-    pub fn klines(
-        &mut self,
-        ths_code: &str,
+    /// 获取K线数据并解析为 [`KLineData`]
+    ///
+    /// 分钟级别的K线会把交易日期和分钟时间合并成完整的时间点，字段缺失或格式错误时返回错误。
+    pub fn klines_typed(
+        &self,
+        ths_code: impl IntoSecurityCode,
         start_time: Option<DateTime<Local>>,
         end_time: Option<DateTime<Local>>,
-        adjust: &str,
-        interval: &str,
+        adjust: Adjust,
+        interval: Interval,
         count: i32,
-    ) -> Result<Response, THSError> {
-        let ths_code = ths_code.to_uppercase();
-        if ths_code.len() != 10 || !MARKETS.iter().any(|&m| ths_code.starts_with(m)) {
-            return Err(THSError::InvalidCode(
-                "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-            ));
+    ) -> Result<Vec<KLineData>, THSError> {
+        let response = self.klines_raw(ths_code, start_time, end_time, adjust, interval, count)?;
+        let minute = interval.is_minute();
+
+        match response.payload.result {
+            Some(serde_json::Value::Array(arr)) => arr.iter()
+                .map(|row| KLineData::from_row(row, minute))
+                .collect(),
+            None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+            Some(other) => Err(THSError::decode("K线数据格式错误", format!("应为数组: {}", other))),
         }
+    }
 
-        if !Adjust::all_types().contains(&adjust) {
-            return Err(THSError::ApiError(format!("无效的复权类型: {}", adjust)));
+    /// 获取一段时间内的全部K线，见 [`THS::klines_range_with`]
+    pub fn klines_range(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        adjust: Adjust,
+        interval: Interval,
+    ) -> Result<Vec<KLineData>, THSError> {
+        self.klines_range_with(ths_code, start_time, end_time, adjust, interval, &RangeOptions::default())
+    }
+
+    /// 获取一段时间内的全部K线，按时间升序返回
+    ///
+    /// 从 `end_time` 开始向前分段请求，每段的跨度按 `options.chunk_bars` 估算，单次返回的数据量不会过大。
+    /// 服务器截断了某一段时，从返回的最早一根K线继续向前获取；分段边界上重复的K线只保留一根。
+    pub fn klines_range_with(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+        adjust: Adjust,
+        interval: Interval,
+        options: &RangeOptions,
+    ) -> Result<Vec<KLineData>, THSError> {
+        let code = ths_code.into_security_code()?;
+        if start_time >= end_time {
+            return Err(THSError::ApiError("开始时间必须小于结束时间".into()));
         }
 
-        if !Interval::all_types().contains(&interval) {
-            return Err(THSError::ApiError(format!("无效的周期类型: {}", interval)));
+        let span = options.chunk_span(interval);
+        let mut bars: BTreeMap<DateTime<Local>, KLineData> = BTreeMap::new();
+        let mut cursor = end_time;
+        let mut empty_chunks = 0;
+
+        while cursor > start_time {
+            let chunk_start = (cursor - span).max(start_time);
+            let chunk = self.klines_typed(&code, Some(chunk_start), Some(cursor), adjust, interval, 0)?;
+            debug!(code = %code, start = %chunk_start, end = %cursor, count = chunk.len(), "分段获取K线");
+
+            let earliest = chunk.iter().map(|bar| bar.time).min();
+            bars.extend(chunk.into_iter().map(|bar| (bar.time, bar)));
+
+            cursor = match earliest {
+                Some(earliest) if earliest > chunk_start && earliest < cursor => {
+                    empty_chunks = 0;
+                    earliest
+                }
+                Some(_) => {
+                    empty_chunks = 0;
+                    chunk_start
+                }
+                None => {
+                    empty_chunks += 1;
+                    if options.max_empty_chunks.is_some_and(|max| empty_chunks >= max) {
+                        warn!(
+                            code = %code,
+                            interval = %interval,
+                            start = %start_time,
+                            before = %chunk_start,
+                            empty_chunks,
+                            "连续多段没有数据，停止向前获取，更早的K线没有获取"
+                        );
+                        break;
+                    }
+                    chunk_start
+                }
+            };
         }
 
+        Ok(bars.into_values()
+            .filter(|bar| bar.time >= start_time && bar.time <= end_time)
+            .collect())
+    }
+
+    /// 获取一段时间的不复权和后复权日K线，计算复权因子，见 [`AdjustFactors`]
+    pub fn adjust_factors(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: DateTime<Local>,
+        end_time: DateTime<Local>,
+    ) -> Result<AdjustFactors, THSError> {
+        let code = ths_code.into_security_code()?;
+        let raw = self.klines_range(&code, start_time, end_time, Adjust::None, Interval::Day)?;
+        let adjusted = self.klines_range(&code, start_time, end_time, Adjust::Backward, Interval::Day)?;
+        AdjustFactors::derive(&raw, &adjusted)
+    }
+
+    /// 请求K线数据，返回服务器的原始结果
+    fn klines_raw(
+        &self,
+        ths_code: impl IntoSecurityCode,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        adjust: Adjust,
+        interval: Interval,
+        count: i32,
+    ) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
+
         let mut params = serde_json::json!({
-            "code": ths_code,
-            "adjust": adjust,
-            "interval": interval,
+            "code": code.ths_code(),
+            "adjust": adjust.as_str(),
+            "interval": interval.as_str(),
         });
 
         if count > 0 {
@@ -343,100 +1087,164 @@ impl THS {
             }
         }
 
-        let mut response = self.call::<Response>("klines", Some(params.to_string()), 1024 * 1024)?;
+        self.request("klines", Some(params.to_string()), 1024 * 1024)
+    }
 
-        // 处理返回数据中的时间字段
-        if let Some(serde_json::Value::Array(arr)) = response.payload.result.as_mut() {
-            for item in arr {
-                if let Some(obj) = item.as_object_mut() {
-                    if let Some(time_value) = obj.get("时间") {
-                        if Interval::minute_intervals().contains(&interval) {
-                            if let Some(time_int) = time_value.as_i64() {
-                                let hours = time_int / 10000;
-                                let minutes = (time_int % 10000) / 100;
-                                let seconds = time_int % 100;
-                                let time_str = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
-                                obj.insert("时间".to_string(), serde_json::Value::String(time_str));
-                            }
-                        } else {
-                            if let Some(time_str) = time_value.as_str() {
-                                if let Ok(dt) = Local.datetime_from_str(time_str, "%Y%m%d") {
-                                    obj.insert("时间".to_string(), serde_json::Value::String(dt.format("%Y-%m-%d").to_string()));
-                                }
+    pub fn stock_market_data(&self, ths_code: &str) -> Result<Response, THSError> {
+        self.stock_market_data_with(ths_code, &STOCK_QUOTE_DATA_TYPES)
+    }
+
+    /// 获取行情快照并解析为 [`Quote`]，`data_types` 为空时使用默认的字段列表
+    ///
+    /// 服务器没有返回涨跌停价时，按证券所在板块的涨跌幅限制（北交所为 30%）根据昨收价计算。
+    pub fn quotes(&self, ths_code: &str, data_types: Option<&[i32]>) -> Result<Vec<Quote>, THSError> {
+        let codes = code::parse_list(ths_code)?;
+        let response = self.stock_market_data_with(ths_code, data_types.unwrap_or(&STOCK_QUOTE_DATA_TYPES))?;
+        let mut quotes = Self::parse_quotes(response)?;
+
+        for quote in &mut quotes {
+            if let Some(code) = codes.iter().find(|c| c.matches(&quote.market.code)) {
+                quote.fill_price_limits(code);
+            }
+        }
+        Ok(quotes)
+    }
+
+    /// 批量获取行情快照，代码可以来自不同市场，数量不限
+    ///
+    /// 见 [`THS::batch_quotes_with`]。
+    pub fn batch_quotes<I>(&self, codes: I) -> BatchQuotes
+    where I: IntoIterator, I::Item: IntoSecurityCode {
+        self.batch_quotes_with(codes, &BatchOptions::default())
+    }
+
+    /// 批量获取行情快照
+    ///
+    /// 代码按市场分组、去重后按 `chunk_size` 拆分成多个请求，结果按传入的顺序返回。
+    /// 单个代码或单个请求的失败只记录在对应的位置上，不会中断其余的请求。
+    pub fn batch_quotes_with<I>(&self, codes: I, options: &BatchOptions) -> BatchQuotes
+    where I: IntoIterator, I::Item: IntoSecurityCode {
+        let mut results: Vec<Option<Result<Quote, Arc<THSError>>>> = Vec::new();
+        let mut parsed = Vec::new();
+        for (i, code) in codes.into_iter().enumerate() {
+            match code.into_security_code() {
+                Ok(code) => {
+                    results.push(None);
+                    parsed.push((i, code));
+                }
+                Err(e) => results.push(Some(Err(Arc::new(e)))),
+            }
+        }
+
+        // 市场 -> 去重后的代码 -> 在传入列表中的位置
+        let mut groups: BTreeMap<&str, BTreeMap<&SecurityCode, Vec<usize>>> = BTreeMap::new();
+        for (i, code) in &parsed {
+            groups.entry(code.market()).or_default().entry(code).or_default().push(*i);
+        }
+
+        let chunk_size = options.chunk_size.max(1);
+        for (market, group) in &groups {
+            let entries: Vec<_> = group.iter().collect();
+            for chunk in entries.chunks(chunk_size) {
+                let short_codes = chunk.iter().map(|(c, _)| c.short_code()).collect::<Vec<_>>();
+                debug!(market, count = chunk.len(), "批量行情请求");
+
+                let quotes = self.quote_request(market, &short_codes, &options.data_types)
+                    .and_then(Self::parse_quotes);
+                let mut quotes = match quotes {
+                    Ok(quotes) => quotes,
+                    Err(e) => {
+                        warn!(market, count = chunk.len(), error = %e, "批量行情请求失败");
+                        let e = Arc::new(e);
+                        for (_, positions) in chunk {
+                            for &i in positions.iter() {
+                                results[i] = Some(Err(Arc::clone(&e)));
                             }
                         }
+                        continue;
+                    }
+                };
+
+                for (code, positions) in chunk {
+                    let found = quotes.iter().position(|q| code.matches(&q.market.code));
+                    let result = match found {
+                        Some(index) => {
+                            let mut quote = quotes.swap_remove(index);
+                            quote.fill_price_limits(code);
+                            Ok(quote)
+                        }
+                        None => Err(Arc::new(THSError::NoData(code.ths_code()))),
+                    };
+                    for &i in positions.iter() {
+                        results[i] = Some(result.clone());
                     }
                 }
             }
         }
 
-        Ok(response)
+        let results = results.into_iter()
+            .enumerate()
+            .map(|(i, r)| r.unwrap_or_else(|| Err(Arc::new(THSError::NoData(format!("第 {} 个代码没有结果", i + 1))))))
+            .collect();
+        BatchQuotes { results }
     }
 
-    pub fn stock_market_data(&mut self, ths_code: &str) -> Result<Response, THSError> {
-        let codes = if ths_code.contains(',') {
-            ths_code.split(',').collect::<Vec<_>>()
-        } else {
-            vec![ths_code]
-        };
+    fn parse_quotes(response: Response) -> Result<Vec<Quote>, THSError> {
+        match response.payload.result {
+            Some(serde_json::Value::Array(arr)) => arr.iter().map(Quote::from_row).collect(),
+            None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+            Some(other) => Err(THSError::decode("行情数据格式错误", format!("应为数组: {}", other))),
+        }
+    }
 
-        for code in &codes {
-            let code = code.to_uppercase();
-            if code.len() != 10 || !MARKETS.iter().any(|&m| code.starts_with(m)) {
-                return Err(THSError::InvalidCode(
-                    "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-                ));
-            }
+    /// 获取行情快照，`data_types` 为请求的 datatype 列表，可以是数字 id 或 [`Field`](crate::fields::Field)
+    pub fn stock_market_data_with<T: Copy + Into<i32>>(&self, ths_code: &str, data_types: &[T]) -> Result<Response, THSError> {
+        let codes = code::parse_list(ths_code)?;
+        if codes.is_empty() {
+            return Err(THSError::InvalidCode("必须提供证券代码".into()));
         }
 
-        let markets: std::collections::HashSet<_> = codes.iter().map(|c| &c[..4]).collect();
+        let markets: std::collections::HashSet<_> = codes.iter().map(|c| c.market()).collect();
         if markets.len() > 1 {
             return Err(THSError::ApiError("一次性查询多支股票必须市场代码相同".into()));
         }
 
-        let market = markets.into_iter().next().unwrap();
-        let short_codes = codes.iter().map(|c| &c[4..]).collect::<Vec<_>>().join(",");
-        
-        let data_type = "5,6,8,9,10,12,13,402,19,407,24,30,48,49,69,70,3250,920371,55,199112,264648,1968584,461256,1771976,3475914,3541450,526792,3153,592888,592890";
-        
-        let req = format!(
-            "\"id=200&instance={}&zipversion={}&codelist={}&market={}&datatype={}\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_codes,
-            market,
-            data_type
-        );
-
-        self.cmd_query_data(req, "fu", 1024 * 1024 * 2, 5)
-    }
-
-    pub fn get_block_data(&mut self, block_id: i32) -> Result<Response, THSError> {
-        let req = format!(
-            "\"id=7&instance={}&zipversion={}&sortbegin=0&sortcount=0&sortorder=D&sortid=55\
-            &blockid={:x}&reqflag=blockserve\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            block_id
-        );
-        self.cmd_query_data(req, "bk", 1024 * 1024 * 2, 5)
-    }
-
-    pub fn get_block_components(&mut self, link_code: &str) -> Result<Response, THSError> {
+        let short_codes = codes.iter().map(|c| c.short_code()).collect::<Vec<_>>();
+        self.quote_request(codes[0].market(), &short_codes, data_types)
+    }
+
+    /// 发送 `id=200` 行情请求，`short_codes` 为同一市场的6位代码
+    fn quote_request<T: Copy + Into<i32>>(&self, market: &str, short_codes: &[&str], data_types: &[T]) -> Result<Response, THSError> {
+        if data_types.is_empty() {
+            return Err(THSError::ApiError("必须指定至少一个数据类型".into()));
+        }
+
+        let req = QueryRequest::new(200)
+            .codelist(market, short_codes)
+            .datatypes(data_types);
+        self.query_raw(&req)
+    }
+
+    pub fn get_block_data(&self, block_id: i32) -> Result<Response, THSError> {
+        let req = QueryRequest::new(7)
+            .sort(55, SortOrder::Desc, 0, 0)
+            .param("blockid", format!("{:x}", block_id))
+            .param("reqflag", "blockserve");
+        self.query_raw(&req)
+    }
+
+    pub fn get_block_components(&self, link_code: &str) -> Result<Response, THSError> {
         if link_code.is_empty() {
             return Err(THSError::ApiError("必须提供板块代码".into()));
         }
 
-        let req = format!(
-            "\"id=7&instance={}&zipversion={}&sortbegin=0&sortcount=0&sortorder=D&sortid=55&linkcode={}\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            link_code
-        );
-        self.cmd_query_data(req, "bk", 1024 * 1024 * 2, 5)
+        let req = QueryRequest::new(7)
+            .sort(55, SortOrder::Desc, 0, 0)
+            .param("linkcode", link_code);
+        self.query_raw(&req)
     }
 
-    pub fn block_market_data(&mut self, block_code: &str) -> Result<Response, THSError> {
+    pub fn block_market_data(&self, block_code: &str) -> Result<Response, THSError> {
         let codes = if block_code.contains(',') {
             block_code.split(',').collect::<Vec<_>>()
         } else {
@@ -456,228 +1264,159 @@ impl THS {
         }
 
         let market = markets.into_iter().next().unwrap();
-        let short_codes = codes.iter().map(|c| &c[4..]).collect::<Vec<_>>().join(",");
-        
-        let data_type = "55,38,39,13,19,92,90,5,275,276,277";
-        
-        let req = format!(
-            "\"id=200&instance={}&zipversion={}&codelist={}&market={}&datatype={}\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_codes,
-            market,
-            data_type
-        );
+        let short_codes = codes.iter().map(|c| &c[4..]).collect::<Vec<_>>();
 
-        self.cmd_query_data(req, "fu", 1024 * 1024 * 2, 5)
+        let req = QueryRequest::new(200)
+            .codelist(market, &short_codes)
+            .datatypes(&[55, 38, 39, 13, 19, 92, 90, 5, 275, 276, 277]);
+        self.query_raw(&req)
     }
 
-    pub fn query_ths_industry(&mut self) -> Result<Response, THSError> {
+    pub fn query_ths_industry(&self) -> Result<Response, THSError> {
         self.get_block_data(0xCE5F)
     }
 
-    pub fn query_ths_concept(&mut self) -> Result<Response, THSError> {
+    pub fn query_ths_concept(&self) -> Result<Response, THSError> {
         self.get_block_data(0xCE5E)
     }
 
-    pub fn query_ths_index(&mut self) -> Result<Response, THSError> {
+    pub fn query_ths_index(&self) -> Result<Response, THSError> {
         self.get_block_data(0xD2)
     }
 
-    pub fn stock_zh_lists(&mut self) -> Result<Response, THSError> {
+    pub fn stock_zh_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xE)
     }
 
-    pub fn stock_us_lists(&mut self) -> Result<Response, THSError> {
+    pub fn stock_us_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xDC47)
     }
 
-    pub fn stock_hk_lists(&mut self) -> Result<Response, THSError> {
+    pub fn stock_hk_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xB)
     }
 
-    pub fn stock_zh_b_lists(&mut self) -> Result<Response, THSError> {
+    pub fn stock_zh_b_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xF)
     }
 
-    pub fn cbond_lists(&mut self) -> Result<Response, THSError> {
+    pub fn cbond_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xCE14)
     }
 
-    pub fn fund_etf_lists(&mut self) -> Result<Response, THSError> {
+    pub fn fund_etf_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xCFF3)
     }
 
-    pub fn fund_etf_t0_lists(&mut self) -> Result<Response, THSError> {
+    pub fn fund_etf_t0_lists(&self) -> Result<Response, THSError> {
         self.get_block_data(0xD90C)
     }
 
-    pub fn get_transaction_data(&mut self, ths_code: &str, start: i64, end: i64) -> Result<Response, THSError> {
-        let ths_code = ths_code.to_uppercase();
-        if ths_code.len() != 10 || !MARKETS.iter().any(|&m| ths_code.starts_with(m)) {
-            return Err(THSError::InvalidCode(
-                "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-            ));
-        }
+    pub fn get_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
         if start >= end {
             return Err(THSError::ApiError("开始时间戳必须小于结束时间戳".into()));
         }
 
-        let data_type = "1,5,10,12,18,49";
-        let market = &ths_code[..4];
-        let short_code = &ths_code[4..];
-
-        let req = format!(
-            "\"id=205&instance={}&zipversion={}&code={}&market={}&start={}&end={}&datatype={}&TraceDetail=0\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_code,
-            market,
-            start,
-            end,
-            data_type
-        );
-
-        self.cmd_query_data(req, "zhu", 1024 * 1024 * 2, 5)
+        let req = QueryRequest::new(205)
+            .code(&code)
+            .range(start, end)
+            .datatypes(&[1, 5, 10, 12, 18, 49])
+            .param("TraceDetail", 0);
+        self.query_raw(&req)
     }
 
-    pub fn get_super_transaction_data(&mut self, ths_code: &str, start: i64, end: i64) -> Result<Response, THSError> {
-        let ths_code = ths_code.to_uppercase();
-        if ths_code.len() != 10 || !MARKETS.iter().any(|&m| ths_code.starts_with(m)) {
-            return Err(THSError::InvalidCode(
-                "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-            ));
-        }
+    pub fn get_super_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
         if start >= end {
             return Err(THSError::ApiError("开始时间戳必须小于结束时间戳".into()));
         }
 
-        let data_type = concat!(
-            "1,5,7,10,12,13,14,18,19,20,21,25,26,27,28,29,31,32,33,34,35,49,",
-            "69,70,92,123,125,150,151,152,153,154,155,156,157,45,66,661,102,103,",
-            "104,105,106,107,108,109,110,111,112,113,114,115,116,117,118,119,120,121,123,125"
-        );
-
-        let market = &ths_code[..4];
-        let short_code = &ths_code[4..];
-
-        let req = format!(
-            "\"id=205&instance={}&zipversion={}&code={}&market={}&start={}&end={}&datatype={}&TraceDetail=0\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_code,
-            market,
-            start,
-            end,
-            data_type
-        );
-
-        self.cmd_query_data(req, "zhu", 1024 * 1024 * 2, 5)
+        let data_types = [
+            1, 5, 7, 10, 12, 13, 14, 18, 19, 20, 21, 25, 26, 27, 28, 29, 31, 32, 33, 34, 35, 49,
+            69, 70, 92, 123, 125, 150, 151, 152, 153, 154, 155, 156, 157, 45, 66, 661, 102, 103,
+            104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 123, 125,
+        ];
+
+        let req = QueryRequest::new(205)
+            .code(&code)
+            .range(start, end)
+            .datatypes(&data_types)
+            .param("TraceDetail", 0);
+        self.query_raw(&req)
     }
 
-    pub fn get_l2_transaction_data(&mut self, ths_code: &str, start: i64, end: i64) -> Result<Response, THSError> {
-        let ths_code = ths_code.to_uppercase();
-        if ths_code.len() != 10 || !MARKETS.iter().any(|&m| ths_code.starts_with(m)) {
-            return Err(THSError::InvalidCode(
-                "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-            ));
-        }
+    pub fn get_l2_transaction_data(&self, ths_code: impl IntoSecurityCode, start: i64, end: i64) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
         if start >= end {
             return Err(THSError::ApiError("开始时间戳必须小于结束时间戳".into()));
         }
 
-        let data_type = "5,10,12,13";
-        let market = &ths_code[..4];
-        let short_code = &ths_code[4..];
-
-        let req = format!(
-            "\"id=220&instance={}&zipversion={}&code={}&market={}&start={}&end={}&datatype={}\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_code,
-            market,
-            start,
-            end,
-            data_type
-        );
-
-        self.cmd_query_data(req, "zhu", 1024 * 1024 * 2, 5)
+        let req = QueryRequest::new(220)
+            .code(&code)
+            .range(start, end)
+            .datatypes(&[5, 10, 12, 13]);
+        self.query_raw(&req)
     }
     
 
-    pub fn wencai_base(&mut self, condition: &str) -> Result<Response, THSError> {
-        self.call::<Response>(
+    pub fn wencai_base(&self, condition: &str) -> Result<Response, THSError> {
+        self.request(
             "wencai_base",
             Some(condition.to_string()),
             1024 * 1024,
         )
     }
 
-    pub fn wencai_nlp(&mut self, condition: &str) -> Result<Response, THSError> {
-        self.call::<Response>(
+    pub fn wencai_nlp(&self, condition: &str) -> Result<Response, THSError> {
+        self.request(
             "wencai_nlp",
             Some(condition.to_string()),
             1024 * 1024 * 8,
         )
     }
 
-    pub fn order_book_ask(&mut self, ths_code: &str) -> Result<Response, THSError> {
-        self.call::<Response>(
+    pub fn order_book_ask(&self, ths_code: impl IntoSecurityCode) -> Result<Response, THSError> {
+        self.request(
             "order_book_ask",
-            Some("\"".to_owned() + ths_code +"\""),
+            Some(format!("\"{}\"", ths_code.into_security_code()?)),
             1024 * 1024 * 8,
         )
     }
 
-    pub fn order_book_bid(&mut self, ths_code: &str) -> Result<Response, THSError> {
-        self.call::<Response>(
+    pub fn order_book_bid(&self, ths_code: impl IntoSecurityCode) -> Result<Response, THSError> {
+        self.request(
             "order_book_bid",
-            Some("\"".to_owned() + ths_code +"\""),
+            Some(format!("\"{}\"", ths_code.into_security_code()?)),
             1024 * 1024 * 8,
         )
     }
 
-    pub fn ipo_today(&mut self) -> Result<Response, THSError> {
-        self.call::<Response>("ipo_today", None, 1024)
+    pub fn ipo_today(&self) -> Result<Response, THSError> {
+        self.request("ipo_today", None, 1024)
     }
 
-    pub fn ipo_wait(&mut self) -> Result<Response, THSError> {
-        self.call::<Response>("ipo_wait", None, 1024)
+    pub fn ipo_wait(&self) -> Result<Response, THSError> {
+        self.request("ipo_wait", None, 1024)
     }
 
-    pub fn history_minute_time_data(&mut self, ths_code: &str, date: &str, fields: Option<Vec<&str>>) -> Result<Response, THSError> {
-        let ths_code = ths_code.to_uppercase();
-        if ths_code.len() != 10 || !MARKETS.iter().any(|&m| ths_code.starts_with(m)) {
-            return Err(THSError::InvalidCode(
-                "证券代码必须为10个字符，且以 'USHA' 或 'USZA' 开头".into(),
-            ));
-        }
-
-        let data_type = "1,10,13,19,40";
-        let market = &ths_code[..4];
-        let short_code = &ths_code[4..];
+    pub fn history_minute_time_data(&self, ths_code: impl IntoSecurityCode, date: &str, fields: Option<Vec<&str>>) -> Result<Response, THSError> {
+        let code = ths_code.into_security_code()?;
 
-        let req = format!(
-            "\"id=207&instance={}&zipversion={}&code={}&market={}&datatype={}&date={}\"",
-            self.next_share_instance_id(),
-            self.zip_version(),
-            short_code,
-            market,
-            data_type,
-            date
-        );
-
-        let mut response = self.cmd_query_data(req, "zhu", 1024 * 1024 * 2, 5)?;
+        let req = QueryRequest::new(207)
+            .code(&code)
+            .datatypes(&[1, 10, 13, 19, 40])
+            .date(date);
+        let mut response = self.query_raw(&req)?;
 
         // 处理返回数据中的时间字段和字段过滤
         if let Some(serde_json::Value::Array(arr)) = response.payload.result.as_mut() {
             let mut filtered_arr = Vec::new();
             for item in arr {
                 if let Some(obj) = item.as_object() {
-                    if let Some(fields) = &fields {
-                        if !fields.iter().all(|&field| obj.contains_key(field)) {
-                            continue;
-                        }
+                    if let Some(fields) = &fields
+                        && !fields.iter().all(|&field| obj.contains_key(field)) {
+                        continue;
                     }
                     filtered_arr.push(item.clone());
                 }
@@ -691,8 +1430,492 @@ impl THS {
 
 impl Drop for THS {
     fn drop(&mut self) {
-        if self.login {
+        if self.is_logged_in() {
             let _ = self.disconnect();
         }
     }
-} 
\ No newline at end of file
+} 
+// THS 需要能放在 Arc 中由多个线程共享
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<THS>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    const OK: &str = r#"{"err_info":"","payload":{"result":null}}"#;
+
+    fn options(retry: RetryPolicy) -> ThsOption {
+        ThsOption { retry, ..ThsOption::default() }
+    }
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(40),
+            retry_err_info: vec!["繁忙".into()],
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn connected(ops: ThsOption, mock: &MockTransport) -> THS {
+        let ths = THS::with_transport(Some(ops), mock.clone().on_ok("connect", OK));
+        ths.connect().unwrap();
+        ths
+    }
+
+    #[test]
+    fn buffer_too_small_doubles_buffer() {
+        let mock = MockTransport::new()
+            .on("klines", CallStatus::BufferTooSmall)
+            .on("klines", CallStatus::BufferTooSmall)
+            .on_ok("klines", OK);
+        let ths = connected(options(fast_retry()), &mock);
+
+        let bars = ths.klines_typed("USHA600000", None, None, Adjust::None, Interval::Day, 10).unwrap();
+        assert!(bars.is_empty());
+        let sizes: Vec<_> = mock.calls_to("klines").iter().map(|c| c.buffer_size).collect();
+        assert_eq!(sizes, [1024 * 1024, 2 * 1024 * 1024, 4 * 1024 * 1024]);
+    }
+
+    #[test]
+    fn err_info_maps_to_server_error() {
+        let mock = MockTransport::new().on_ok("help", r#"{"err_info":"代码不存在","payload":{}}"#);
+        let ths = connected(options(RetryPolicy::default()), &mock);
+
+        match ths.help("x") {
+            Err(THSError::Server { err_info }) => assert_eq!(err_info, "代码不存在"),
+            other => panic!("应为 Server 错误: {:?}", other),
+        }
+        // 服务器错误默认不重试
+        assert_eq!(mock.calls_to("help").len(), 1);
+    }
+
+    #[test]
+    fn retryable_server_error_backs_off() {
+        let mock = MockTransport::new().on_ok("help", r#"{"err_info":"服务器繁忙","payload":{}}"#);
+        let ths = connected(options(fast_retry()), &mock);
+
+        let started = Instant::now();
+        let err = ths.help("x").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Server);
+        assert_eq!(mock.calls_to("help").len(), 3);
+        // 两次等待：20ms 和 40ms
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn retry_succeeds_after_transient_error() {
+        let mock = MockTransport::new()
+            .on_ok("help", r#"{"err_info":"服务器繁忙","payload":{}}"#)
+            .on_ok("help", r#"{"err_info":"","payload":{"result":"ok"}}"#);
+        let ths = connected(options(fast_retry()), &mock);
+
+        assert_eq!(ths.help("x").unwrap(), "ok");
+        assert_eq!(mock.calls_to("help").len(), 2);
+    }
+
+    #[test]
+    fn connect_backoff() {
+        let mock = MockTransport::new()
+            .on_ok("connect", r#"{"err_info":"服务器繁忙","payload":{}}"#)
+            .on_ok("connect", r#"{"err_info":"服务器繁忙","payload":{}}"#)
+            .on_ok("connect", OK);
+        let ths = THS::with_transport(Some(options(fast_retry())), mock.clone());
+
+        let started = Instant::now();
+        ths.connect().unwrap();
+        assert!(ths.is_logged_in());
+        assert_eq!(mock.calls_to("connect").len(), 3);
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn connect_does_not_retry_bad_credentials() {
+        let mock = MockTransport::new().on_ok("connect", r#"{"err_info":"密码错误","payload":{}}"#);
+        let ths = THS::with_transport(Some(options(fast_retry())), mock.clone());
+
+        assert_eq!(ths.connect().unwrap_err().kind(), ErrorKind::Server);
+        assert!(!ths.is_logged_in());
+        assert_eq!(mock.calls_to("connect").len(), 1);
+    }
+
+    fn push_options() -> ThsOption {
+        ThsOption { experimental_push: true, ..options(fast_retry()) }
+    }
+
+    fn stock_frame(code: &str, price: f64) -> String {
+        serde_json::json!({"data_class": DataClass::Stock.id(), "code": code, "data": {"price": price}}).to_string()
+    }
+
+    #[test]
+    fn subscribe_push_unsubscribe() {
+        let mock = MockTransport::new().on_ok("subscribe", OK);
+        let ths = connected(push_options(), &mock);
+
+        let rx = ths.subscribe(DataClass::Stock, &["600000", "USZA000001"]).unwrap();
+        assert!(mock.push(&stock_frame("USHA600000", 10.5)));
+        mock.push(&stock_frame("USHA600001", 1.0));
+        mock.push(&serde_json::json!({"data_class": DataClass::Index.id(), "code": "USHA600000"}).to_string());
+        mock.push("不是 JSON");
+
+        let update = rx.try_recv().unwrap();
+        assert_eq!(update.code, "USHA600000");
+        assert_eq!(update.data["price"], 10.5);
+        assert!(rx.try_recv().is_err());
+
+        ths.unsubscribe(DataClass::Stock, &["USHA600000"]).unwrap();
+        mock.push(&stock_frame("USHA600000", 10.6));
+        mock.push(&stock_frame("USZA000001", 9.0));
+        assert_eq!(rx.try_recv().unwrap().code, "USZA000001");
+        assert!(rx.try_recv().is_err());
+
+        let calls = mock.calls_to("subscribe");
+        assert_eq!(calls.len(), 2);
+        let unsubscribe: Value = serde_json::from_str(calls[1].params.as_deref().unwrap()).unwrap();
+        assert_eq!(unsubscribe["codes"], "USHA600000");
+    }
+
+    #[test]
+    fn resubscribe_after_reconnect() {
+        let mock = MockTransport::new()
+            .on_ok("subscribe", OK)
+            .on_ok("help", r#"{"err_info":"未登录","payload":{}}"#)
+            .on_ok("help", r#"{"err_info":"","payload":{"result":"ok"}}"#);
+        let ths = connected(push_options(), &mock);
+        let rx = ths.subscribe(DataClass::Stock, &["USHA600000"]).unwrap();
+
+        // 会话失效，重连后重放请求并恢复订阅
+        assert_eq!(ths.help("x").unwrap(), "ok");
+        assert_eq!(mock.calls_to("connect").len(), 2);
+        let calls = mock.calls_to("subscribe");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].params, calls[1].params);
+
+        mock.push(&stock_frame("USHA600000", 10.5));
+        assert_eq!(rx.try_recv().unwrap().code, "USHA600000");
+    }
+
+    #[test]
+    fn lost_session_reconnects_and_replays() {
+        let mock = MockTransport::new()
+            .on_ok("help", r#"{"err_info":"登录失效","payload":{}}"#)
+            .on_ok("help", r#"{"err_info":"","payload":{"result":"ok"}}"#);
+        let ths = connected(options(RetryPolicy::none()), &mock);
+
+        assert_eq!(ths.help("x").unwrap(), "ok");
+        assert_eq!(mock.calls_to("connect").len(), 2);
+        assert_eq!(mock.calls_to("help").len(), 2);
+    }
+
+    #[test]
+    fn lost_session_without_auto_reconnect() {
+        let mock = MockTransport::new().on_ok("help", r#"{"err_info":"登录失效","payload":{}}"#);
+        let mut ops = options(RetryPolicy::none());
+        ops.reconnect.auto_reconnect = false;
+        let ths = connected(ops, &mock);
+
+        let err = ths.help("x").unwrap_err();
+        assert!(matches!(err, THSError::Server { .. }));
+        assert_eq!(mock.calls_to("connect").len(), 1);
+    }
+
+    #[test]
+    fn heartbeat_reconnects_lost_session() {
+        let mock = MockTransport::new()
+            .on_ok("cmd.query_data.fu", r#"{"err_info":"未登录","payload":{}}"#)
+            .on_ok("cmd.query_data.fu", r#"{"err_info":"","payload":{"result":[]}}"#);
+        let ops = ThsOption { zip_version: Some(2), ..options(RetryPolicy::none()) };
+        let ths = Arc::new(connected(ops, &mock));
+        let heartbeat = ths.start_heartbeat(Duration::from_millis(10)).unwrap();
+
+        let started = Instant::now();
+        while mock.calls_to("connect").len() < 2 {
+            assert!(started.elapsed() < Duration::from_secs(5), "心跳没有重连");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        heartbeat.stop();
+
+        let calls = mock.calls_to("cmd.query_data.fu").len();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(mock.calls_to("cmd.query_data.fu").len(), calls);
+    }
+
+    #[test]
+    fn heartbeat_idles_without_session_and_exits_with_client() {
+        let mock = MockTransport::new();
+        let ths = Arc::new(THS::with_transport(Some(options(RetryPolicy::none())), mock.clone()));
+        let heartbeat = ths.start_heartbeat(Duration::from_millis(10)).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(mock.calls().is_empty());
+
+        // 客户端释放后线程自行退出，stop 不会阻塞
+        drop(ths);
+        std::thread::sleep(Duration::from_millis(30));
+        heartbeat.stop();
+    }
+
+    #[test]
+    fn failed_subscribe_does_not_leak_receiver() {
+        let mock = MockTransport::new().on_ok("subscribe", OK);
+        let ths = THS::with_transport(Some(push_options()), mock.clone().on_ok("connect", OK));
+        let count = Arc::new(AtomicU64::new(0));
+
+        let counter = Arc::clone(&count);
+        let err = ths.subscribe_with(DataClass::Stock, &["USHA600000"], move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotLoggedIn);
+
+        ths.connect().unwrap();
+        let counter = Arc::clone(&count);
+        ths.subscribe_with(DataClass::Stock, &["USHA600000"], move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }).unwrap();
+        mock.push(&stock_frame("USHA600000", 10.5));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn push_requires_opt_in() {
+        let mock = MockTransport::new().on_ok("subscribe", OK);
+        let ths = connected(options(fast_retry()), &mock);
+        assert!(matches!(ths.subscribe(DataClass::Stock, &["USHA600000"]), Err(THSError::ApiError(_))));
+        assert!(mock.calls_to("subscribe").is_empty());
+    }
+
+    #[test]
+    fn callback_can_change_subscriptions() {
+        let mock = MockTransport::new().on_ok("subscribe", OK);
+        let ths = Arc::new(connected(push_options(), &mock));
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // 回调在推送线程中取消自己的订阅并订阅另一个代码
+        let weak = Arc::downgrade(&ths);
+        ths.subscribe_with(DataClass::Stock, &["USHA600000"], move |_| {
+            let ths = weak.upgrade().unwrap();
+            ths.unsubscribe(DataClass::Stock, &["USHA600000"]).unwrap();
+            let tx = tx.clone();
+            ths.subscribe_with(DataClass::Stock, &["USHA600001"], move |u| tx.send(u.code).unwrap()).unwrap();
+        }).unwrap();
+
+        let pusher = mock.clone();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            pusher.push(&stock_frame("USHA600000", 10.5));
+            pusher.push(&stock_frame("USHA600000", 10.6));
+            pusher.push(&stock_frame("USHA600001", 1.0));
+            done_tx.send(()).unwrap();
+        });
+        done_rx.recv_timeout(Duration::from_secs(5)).expect("推送线程死锁");
+        assert_eq!(rx.try_recv().unwrap(), "USHA600001");
+        assert!(rx.try_recv().is_err());
+        assert_eq!(mock.calls_to("subscribe").len(), 3);
+    }
+
+    /// 行情服务：USZA 请求返回错误，不返回 600002，600001 以带市场前缀的形式返回，
+    /// 每个请求还会多返回一个只有后缀相同的代码
+    fn quote_server() -> MockTransport {
+        MockTransport::new().with_handler(|method, params, _| {
+            if !method.starts_with("cmd.query_data.") {
+                return CallStatus::Ok(OK.into());
+            }
+            let params: String = serde_json::from_str(params.unwrap()).unwrap();
+            let field = |key: &str| {
+                params.split('&').find_map(|kv| kv.strip_prefix(key)?.strip_prefix('=')).unwrap_or("").to_string()
+            };
+            let market = field("market");
+            if market == "USZA" {
+                return CallStatus::Ok(r#"{"err_info":"深市繁忙","payload":{}}"#.into());
+            }
+            let mut rows: Vec<Value> = field("codelist").split(',')
+                .filter(|code| *code != "600002")
+                .map(|code| {
+                    let returned = if code == "600001" { format!("{}{}", market, code) } else { code.to_string() };
+                    serde_json::json!({"代码": returned, "价格": code.parse::<f64>().unwrap(), "昨收价": 10.0})
+                })
+                .collect();
+            rows.insert(0, serde_json::json!({"代码": "1600000", "价格": -1.0, "昨收价": 10.0}));
+            CallStatus::Ok(serde_json::json!({"err_info": "", "payload": {"result": rows}}).to_string())
+        })
+    }
+
+    #[test]
+    fn batch_quotes_group_chunk_and_keep_order() {
+        let mock = quote_server();
+        let ops = ThsOption { zip_version: Some(2), ..options(RetryPolicy { max_attempts: 1, ..fast_retry() }) };
+        let ths = connected(ops, &mock);
+        let options = BatchOptions { chunk_size: 2, data_types: vec![10] };
+        let codes = ["USHA600003", "000001.SZ", "600000", "不是代码", "USHA600001", "USHA600002", "USHA600000", "USHA600004"];
+        let batch = ths.batch_quotes_with(codes, &options);
+
+        // USHA 的 5 个不同代码分成 3 个请求，USZA 1 个请求
+        let requests: Vec<String> = mock.calls()
+            .iter()
+            .filter(|call| call.method.starts_with("cmd.query_data."))
+            .map(|call| serde_json::from_str(call.params.as_deref().unwrap()).unwrap())
+            .collect();
+        let usha: Vec<&String> = requests.iter().filter(|req| req.contains("market=USHA")).collect();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(usha.len(), 3);
+        assert!(usha[0].contains("codelist=600000,600001&"));
+        assert!(requests.iter().any(|req| req.contains("codelist=000001&market=USZA")));
+
+        assert_eq!(batch.len(), codes.len());
+        let price = |i: usize| batch.results[i].as_ref().map(|q| q.market.price).map_err(|e| e.kind());
+        assert_eq!(price(0), Ok(600003.0));
+        assert_eq!(price(1), Err(ErrorKind::Server));
+        assert_eq!(price(2), Ok(600000.0));
+        assert_eq!(price(3), Err(ErrorKind::InvalidInput));
+        assert_eq!(price(4), Ok(600001.0));
+        assert_eq!(price(5), Err(ErrorKind::NoData));
+        assert_eq!(price(6), Ok(600000.0));
+        assert_eq!(price(7), Ok(600004.0));
+        assert_eq!(batch.errors().map(|(i, _)| i).collect::<Vec<_>>(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn quotes_match_exact_codes() {
+        let mock = quote_server();
+        let ops = ThsOption { zip_version: Some(2), ..options(fast_retry()) };
+        let ths = connected(ops, &mock);
+        let quotes = ths.quotes("USHA600000,USHA600001", Some(&[10])).unwrap();
+        assert_eq!(quotes.len(), 3);
+        // 只有后缀相同的 1600000 不会按 600000 补全涨跌停价
+        let limits = |code: &str| quotes.iter().find(|q| q.market.code == code).map(|q| q.limit_up);
+        assert_eq!(limits("600000"), Some(Some(11.0)));
+        assert_eq!(limits("USHA600001"), Some(Some(11.0)));
+        assert_eq!(limits("1600000"), Some(None));
+    }
+
+    #[test]
+    fn negotiates_zip_version_after_connect() {
+        let mock = MockTransport::new().on_ok("connect", OK).with_handler(|method, params, _| {
+            if method != "cmd.query_data.fu" {
+                CallStatus::Ok(OK.into())
+            } else if params.unwrap_or("").contains("zipversion=2") {
+                CallStatus::Ok("\u{1f}压缩数据".into())
+            } else {
+                CallStatus::Ok(r#"{"err_info":"","payload":{"result":[]}}"#.into())
+            }
+        });
+        let ths = THS::with_transport(None, mock.clone());
+        assert_eq!(ths.zip_version(), DEFAULT_ZIP_VERSION);
+
+        ths.connect().unwrap();
+        assert_eq!(ths.zip_version(), 1);
+        assert_eq!(mock.calls_to("cmd.query_data.fu").len(), 2);
+
+        // 已经协商过，重连时不再尝试
+        ths.reconnect(ths.session_gen.load(Ordering::SeqCst), None).unwrap();
+        assert_eq!(mock.calls_to("connect").len(), 2);
+        assert_eq!(mock.calls_to("cmd.query_data.fu").len(), 2);
+    }
+
+    #[test]
+    fn configured_zip_version_is_not_negotiated() {
+        let mock = MockTransport::new().on_ok("connect", OK);
+        let ops = ThsOption { zip_version: Some(0), ..ThsOption::default() };
+        let ths = THS::with_transport(Some(ops), mock.clone());
+
+        ths.connect().unwrap();
+        assert_eq!(ths.zip_version(), 0);
+        assert!(mock.calls_to("cmd.query_data.fu").is_empty());
+    }
+
+    #[test]
+    fn only_library_failures_fall_back() {
+        assert!(THS::is_library_failure(&THSError::LibraryError("加载失败".into())));
+        assert!(THS::is_library_failure(&THSError::MethodNotFound { code: -2, method: "connect".into() }));
+        assert!(THS::is_library_failure(&THSError::decode("JSON解析失败", "格式错误")));
+        assert!(!THS::is_library_failure(&THSError::Server { err_info: "密码错误".into() }));
+        assert!(!THS::is_library_failure(&THSError::Timeout { method: "connect".into(), timeout: Duration::from_secs(1) }));
+        assert!(!THS::is_library_failure(&THSError::IoError(std::io::ErrorKind::ConnectionReset.into())));
+        assert!(!THS::is_library_failure(&THSError::LibraryBusy { method: "connect".into() }));
+    }
+
+    #[test]
+    fn klines_pass_adjust_through_for_every_security_type() {
+        let mock = MockTransport::new().on_ok("klines", OK);
+        let ths = connected(options(fast_retry()), &mock);
+
+        for code in ["USHA600000", "USHI1A0001", "USHD113050"] {
+            ths.klines_typed(code, None, None, Adjust::Forward, Interval::Min1, 10).unwrap();
+        }
+        for call in mock.calls_to("klines") {
+            let params: Value = serde_json::from_str(call.params.as_deref().unwrap()).unwrap();
+            assert_eq!(params["adjust"], "forward");
+            assert_eq!(params["interval"], "1m");
+            assert_eq!(params["count"], 10);
+        }
+        assert_eq!(mock.calls_to("klines").len(), 3);
+    }
+
+    #[test]
+    fn adjust_and_interval_strings() {
+        for adjust in Adjust::ALL {
+            assert_eq!(adjust.as_str().parse::<Adjust>().unwrap(), adjust);
+            assert_eq!(serde_json::to_string(&adjust).unwrap(), format!("\"{}\"", adjust));
+        }
+        assert_eq!("none".parse::<Adjust>().unwrap(), Adjust::None);
+        assert!("qfq".parse::<Adjust>().is_err());
+
+        for interval in Interval::ALL {
+            assert_eq!(interval.as_str().parse::<Interval>().unwrap(), interval);
+            assert_eq!(serde_json::from_str::<Interval>(&format!("\"{}\"", interval)).unwrap(), interval);
+        }
+        assert!("2m".parse::<Interval>().is_err());
+        assert_eq!(Interval::Min120.minutes(), Some(120));
+        assert!(!Interval::Day.is_minute());
+    }
+
+    /// 返回 2024 年 1 月和 7 月每天的日K线，中间停牌五个月
+    fn suspended_klines() -> MockTransport {
+        use chrono::Datelike;
+        MockTransport::new().on_ok("connect", OK).with_handler(|method, params, _| {
+            if method != "klines" {
+                return CallStatus::Error(-2);
+            }
+            let params: Value = serde_json::from_str(params.unwrap()).unwrap();
+            let time = |key: &str| {
+                chrono::NaiveDateTime::parse_from_str(params[key].as_str().unwrap(), "%Y-%m-%d %H:%M:%S").unwrap()
+            };
+            let (start, end) = (time("start_time"), time("end_time"));
+            let rows: Vec<Value> = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+                .iter_days()
+                .take_while(|day| day.month() <= 7)
+                .filter(|day| day.month() == 1 || day.month() == 7)
+                .filter(|day| day.and_time(chrono::NaiveTime::MIN) >= start && day.and_time(chrono::NaiveTime::MIN) <= end)
+                .map(|day| serde_json::json!({
+                    "时间": day.format("%Y%m%d").to_string(),
+                    "开盘价": 10.0, "最高价": 10.0, "最低价": 10.0, "收盘价": 10.0, "成交量": 100, "总金额": 1000.0,
+                }))
+                .collect();
+            CallStatus::Ok(serde_json::json!({"err_info": "", "payload": {"result": rows}}).to_string())
+        })
+    }
+
+    #[test]
+    fn klines_range_keeps_history_before_long_suspension() {
+        use chrono::TimeZone;
+        let mock = suspended_klines();
+        let ths = THS::with_transport(Some(options(fast_retry())), mock.clone());
+        ths.connect().unwrap();
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let end = Local.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap();
+
+        let small_chunks = RangeOptions { chunk_bars: 10, ..RangeOptions::default() };
+        let bars = ths.klines_range_with("USHA600000", start, end, Adjust::None, Interval::Day, &small_chunks).unwrap();
+        assert_eq!(bars.len(), 62);
+        assert!(bars.windows(2).all(|w| w[0].time < w[1].time));
+        assert_eq!(bars[0].time, start);
+
+        let stop_early = RangeOptions { chunk_bars: 10, max_empty_chunks: Some(3) };
+        let bars = ths.klines_range_with("USHA600000", start, end, Adjust::None, Interval::Day, &stop_early).unwrap();
+        assert_eq!(bars.len(), 31);
+    }
+}
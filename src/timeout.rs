@@ -0,0 +1,88 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::THSError;
+
+/// 超时设置，`None` 表示不限时间
+///
+/// 超时是整个操作的时间上限，包括重试的等待、会话失效后的重连和每一次动态库调用。
+/// 单次调用可以通过 [`THS::with_timeout`](crate::ths::THS::with_timeout) 覆盖。
+///
+/// 进程内加载的动态库无法中断，超时的调用返回前同一个动态库的其余调用返回 `THSError::LibraryBusy`；
+/// 需要中断卡死的调用时设置 `ThsOption::worker`。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutPolicy {
+    /// `connect` 的超时
+    pub connect: Option<Duration>,
+    /// 查询、订阅等其余调用的超时
+    pub query: Option<Duration>,
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(60)),
+            query: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+impl TimeoutPolicy {
+    /// 不限时间
+    pub fn none() -> Self {
+        Self { connect: None, query: None }
+    }
+}
+
+/// 一次操作的截止时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Deadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    pub(crate) fn after(timeout: Duration) -> Self {
+        Self { at: Instant::now() + timeout, timeout }
+    }
+
+    /// 剩余时间，已经超时返回 `None`
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.at.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.remaining().is_none()
+    }
+
+    pub(crate) fn error(&self, method: &str) -> THSError {
+        THSError::Timeout { method: method.to_string(), timeout: self.timeout }
+    }
+}
+
+thread_local! {
+    /// 当前线程中 `with_timeout` 设置的截止时间
+    static SCOPED: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+/// 当前线程中生效的截止时间
+pub(crate) fn scoped() -> Option<Deadline> {
+    SCOPED.with(Cell::get)
+}
+
+/// 在 `deadline` 内执行 `f`，结束后恢复原来的设置
+pub(crate) fn with_scoped<R>(deadline: Deadline, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Deadline>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED.with(|s| s.set(self.0));
+        }
+    }
+
+    let _restore = Restore(SCOPED.with(|s| s.replace(Some(deadline))));
+    f()
+}
@@ -0,0 +1,570 @@
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::error::THSError;
+
+/// 动态库导出的 `Call` 函数签名
+type CallFn = unsafe extern "C" fn(*const c_char, *mut c_char, c_int, *const c_void) -> c_int;
+
+/// 已加载的动态库，按路径和版本共享
+///
+/// 动态库加载后可能在内部启动线程，卸载不安全，因此加载后一直保留到进程退出。
+static LIBRARIES: Mutex<Vec<Arc<LoadedLibrary>>> = Mutex::new(Vec::new());
+
+/// 动态库推送数据时回调的处理函数，按 [`PushSlot`] 区分
+///
+/// 回调不带上下文参数，无法区分推送属于哪个客户端，因此每一帧都交给全部处理函数，
+/// 由各个客户端按自己的订阅过滤。
+static PUSH_HANDLERS: RwLock<Vec<(u64, PushHandler)>> = RwLock::new(Vec::new());
+static NEXT_PUSH_SLOT: AtomicU64 = AtomicU64::new(0);
+
+/// 推送数据的处理函数，参数为推送的 JSON 字符串
+pub type PushHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// 一次底层调用的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallStatus {
+    /// 调用成功，内容为返回的 JSON 字符串（可能为空）
+    Ok(String),
+    /// 输出缓冲区大小不足，对应动态库返回的 -1
+    BufferTooSmall,
+    /// 其它返回码，一般表示方法不存在
+    Error(i32),
+}
+
+impl CallStatus {
+    /// 动态库的返回码
+    pub fn code(&self) -> i32 {
+        match self {
+            CallStatus::Ok(_) => 0,
+            CallStatus::BufferTooSmall => -1,
+            CallStatus::Error(code) => *code,
+        }
+    }
+}
+
+/// 底层调用通道
+///
+/// `THS` 只通过该 trait 与行情服务交互，默认实现为加载 hq 动态库的 [`LibTransport`]，
+/// 单元测试中可以替换为 [`MockTransport`]。
+pub trait Transport: Send + Sync {
+    /// 调用 `method`，`params` 为原样拼接到请求中的参数字符串，`buffer_size` 为输出缓冲区大小
+    fn call(&self, method: &str, params: Option<&str>, buffer_size: usize) -> Result<CallStatus, THSError>;
+
+    /// 在 `timeout` 内完成调用，超时返回 `THSError::Timeout`
+    ///
+    /// 默认在单独的线程中调用 [`Transport::call`]，超时后不再等待，调用线程在后台继续运行直到返回。
+    /// [`LibTransport`] 使用每个动态库一个的常驻线程，见其文档。
+    fn call_timeout(
+        self: Arc<Self>,
+        method: &str,
+        params: Option<&str>,
+        buffer_size: usize,
+        timeout: Duration,
+    ) -> Result<CallStatus, THSError>
+    where Self: 'static {
+        let (tx, rx) = mpsc::channel();
+        let (thread_method, params) = (method.to_string(), params.map(str::to_string));
+        std::thread::Builder::new()
+            .name("rusths-call".into())
+            .spawn(move || {
+                let _ = tx.send(self.call(&thread_method, params.as_deref(), buffer_size));
+            })
+            .map_err(THSError::IoError)?;
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(THSError::Timeout { method: method.to_string(), timeout }),
+            Err(RecvTimeoutError::Disconnected) => Err(THSError::ApiError(format!("调用线程异常退出: {}", method))),
+        }
+    }
+
+    /// 设置推送数据的处理函数，不支持推送的通道返回错误
+    fn set_push_handler(&self, _handler: PushHandler) -> Result<(), THSError> {
+        Err(THSError::ApiError("当前调用通道不支持推送".into()))
+    }
+}
+
+/// 一个已加载的动态库
+struct LoadedLibrary {
+    path: PathBuf,
+    version: String,
+    call_fn: CallFn,
+    /// 动态库不保证线程安全，同一个库的调用都通过该锁串行执行
+    call_lock: Mutex<()>,
+    /// 超时后仍未返回的调用的方法名，返回前其余调用直接失败
+    stuck: Mutex<Option<String>>,
+    /// 带超时的调用在该线程中执行，首次使用时启动
+    executor: OnceLock<Sender<Job>>,
+    _lib: Library,
+}
+
+/// `Job::state` 的取值
+const JOB_QUEUED: u8 = 0;
+const JOB_RUNNING: u8 = 1;
+/// 调用方超时时还在排队，不再执行
+const JOB_ABANDONED: u8 = 2;
+/// 调用方超时时已经开始执行
+const JOB_STUCK: u8 = 3;
+const JOB_DONE: u8 = 4;
+
+/// 交给执行线程的一次调用
+struct Job {
+    method: String,
+    params: Option<String>,
+    buffer_size: usize,
+    state: Arc<AtomicU8>,
+    reply: Sender<Result<CallStatus, THSError>>,
+}
+
+impl LoadedLibrary {
+    fn open(path: &Path, version: &str) -> Result<Self, THSError> {
+        if !path.is_file() {
+            return Err(THSError::LibraryError(format!("动态库不存在: {}", path.display())));
+        }
+
+        let lib = unsafe { Library::new(path) }
+            .map_err(|e| THSError::LibraryError(format!("加载动态库失败: {}: {}", path.display(), e)))?;
+        let call_fn = unsafe {
+            *lib.get::<CallFn>(b"Call")
+                .map_err(|e| THSError::LibraryError(format!("动态库缺少 Call 方法: {}: {}", path.display(), e)))?
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            version: version.to_string(),
+            call_fn,
+            call_lock: Mutex::new(()),
+            stuck: Mutex::new(None),
+            executor: OnceLock::new(),
+            _lib: lib,
+        })
+    }
+
+    /// 有超时后仍未返回的调用时返回 `LibraryBusy`
+    fn check_stuck(&self) -> Result<(), THSError> {
+        match &*self.stuck.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(method) => Err(THSError::LibraryBusy { method: method.clone() }),
+            None => Ok(()),
+        }
+    }
+
+    fn call(&self, method: &str, params: Option<&str>, buffer_size: usize) -> Result<CallStatus, THSError> {
+        let input = format!(
+            r#"{{"method":"{}","params":{}}}"#,
+            method,
+            params.unwrap_or("")
+        );
+
+        let input_str = CString::new(input).map_err(|e| THSError::ApiError(format!("无效的输入参数: {}", e)))?;
+
+        let mut output_buffer = vec![0u8; buffer_size];
+        let output_ptr = output_buffer.as_mut_ptr() as *mut c_char;
+
+        // 有客户端启用推送（`ThsOption::experimental_push`）时，把回调一并交给动态库
+        let callback = match PUSH_HANDLERS.read() {
+            Ok(guard) if !guard.is_empty() => push_trampoline as *const c_void,
+            _ => std::ptr::null(),
+        };
+
+        let _guard = self.call_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        unsafe {
+            let result = (self.call_fn)(input_str.as_ptr(), output_ptr, buffer_size as c_int, callback);
+
+            match result {
+                0 => {
+                    let output = CStr::from_ptr(output_ptr).to_str().map_err(|e| THSError::decode("输出解码失败", e))?;
+                    Ok(CallStatus::Ok(output.to_string()))
+                },
+                -1 => Ok(CallStatus::BufferTooSmall),
+                code => Ok(CallStatus::Error(code)),
+            }
+        }
+    }
+
+    /// 执行线程的发送端，线程在第一次使用时启动
+    fn executor(self: &Arc<Self>) -> Result<&Sender<Job>, THSError> {
+        if let Some(executor) = self.executor.get() {
+            return Ok(executor);
+        }
+        let (tx, rx) = mpsc::channel::<Job>();
+        let lib = Arc::clone(self);
+        std::thread::Builder::new()
+            .name("rusths-call".into())
+            .spawn(move || {
+                for job in rx {
+                    if job.state.compare_exchange(JOB_QUEUED, JOB_RUNNING, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+                        continue;
+                    }
+                    let result = lib.call(&job.method, job.params.as_deref(), job.buffer_size);
+                    if job.state.swap(JOB_DONE, Ordering::SeqCst) == JOB_STUCK {
+                        lib.stuck.lock().unwrap_or_else(|e| e.into_inner()).take();
+                        warn!(method = %job.method, "超时的动态库调用已经返回，恢复调用");
+                    }
+                    let _ = job.reply.send(result);
+                }
+            })
+            .map_err(THSError::IoError)?;
+        // 并发初始化时只保留一个发送端，其余线程在发送端释放后退出
+        Ok(self.executor.get_or_init(|| tx))
+    }
+
+    /// 在执行线程中调用，超时后正在执行的调用被标记为卡住
+    fn call_timeout(self: &Arc<Self>, method: &str, params: Option<&str>, buffer_size: usize, timeout: Duration) -> Result<CallStatus, THSError> {
+        let (reply, rx) = mpsc::channel();
+        let state = Arc::new(AtomicU8::new(JOB_QUEUED));
+        self.executor()?
+            .send(Job {
+                method: method.to_string(),
+                params: params.map(str::to_string),
+                buffer_size,
+                state: Arc::clone(&state),
+                reply,
+            })
+            .map_err(|_| THSError::ApiError(format!("动态库执行线程已退出: {}", method)))?;
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                let mut stuck = self.stuck.lock().unwrap_or_else(|e| e.into_inner());
+                if state.compare_exchange(JOB_RUNNING, JOB_STUCK, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                    warn!(method, timeout = ?timeout, "动态库调用超时，返回前其余调用直接失败");
+                    *stuck = Some(method.to_string());
+                } else if state.compare_exchange(JOB_QUEUED, JOB_ABANDONED, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+                    // 超时的同时已经执行完成
+                    drop(stuck);
+                    if let Ok(result) = rx.try_recv() {
+                        return result;
+                    }
+                }
+                Err(THSError::Timeout { method: method.to_string(), timeout })
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(THSError::ApiError(format!("调用线程异常退出: {}", method))),
+        }
+    }
+}
+
+/// 基于 hq 动态库的调用通道
+///
+/// 路径和版本相同的实例共享同一个已加载的动态库，不同版本的动态库可以同时使用。
+/// 每次 [`LibTransport::load`] 得到的实例有各自的推送处理函数，多个客户端可以同时订阅。
+///
+/// 带超时的调用在每个动态库一个的常驻线程中执行。进程内的动态库调用无法中断：超时后调用仍然占用动态库，
+/// 在它返回之前，同一个动态库的其余调用直接返回 `THSError::LibraryBusy`。
+/// 需要中断卡死的调用时使用 `ThsOption::worker`，在子进程中加载动态库。
+#[derive(Clone)]
+pub struct LibTransport {
+    lib: Arc<LoadedLibrary>,
+    push_slot: Arc<PushSlot>,
+}
+
+/// 一个 `LibTransport` 在 `PUSH_HANDLERS` 中的位置，最后一个克隆释放时移除对应的处理函数
+struct PushSlot(u64);
+
+impl PushSlot {
+    fn new() -> Self {
+        Self(NEXT_PUSH_SLOT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn set(&self, handler: PushHandler) {
+        let mut handlers = PUSH_HANDLERS.write().unwrap_or_else(|e| e.into_inner());
+        match handlers.iter_mut().find(|(id, _)| *id == self.0) {
+            Some(entry) => entry.1 = handler,
+            None => handlers.push((self.0, handler)),
+        }
+    }
+}
+
+impl Drop for PushSlot {
+    fn drop(&mut self) {
+        PUSH_HANDLERS.write().unwrap_or_else(|e| e.into_inner()).retain(|(id, _)| *id != self.0);
+    }
+}
+
+/// 把一帧推送数据交给全部处理函数，处理函数在锁外调用
+fn dispatch_push(data: &str) {
+    let handlers: Vec<PushHandler> = PUSH_HANDLERS.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, handler)| Arc::clone(handler))
+        .collect();
+    for handler in handlers {
+        handler(data);
+    }
+}
+
+impl LibTransport {
+    /// 加载 `lib_path` 处的动态库，`version` 为动态库的版本号，与路径一起用于区分已加载的库
+    pub fn load(lib_path: impl AsRef<Path>, version: &str) -> Result<Self, THSError> {
+        let lib_path = lib_path.as_ref();
+        let path = lib_path.canonicalize()
+            .map_err(|e| THSError::LibraryError(format!("动态库不存在: {}: {}", lib_path.display(), e)))?;
+
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(lib) = libraries.iter().find(|l| l.path == path && l.version == version) {
+            return Ok(Self { lib: Arc::clone(lib), push_slot: Arc::new(PushSlot::new()) });
+        }
+
+        let lib = Arc::new(LoadedLibrary::open(&path, version)?);
+        info!(path = %path.display(), version, "加载动态库");
+        libraries.push(Arc::clone(&lib));
+        Ok(Self { lib, push_slot: Arc::new(PushSlot::new()) })
+    }
+
+    /// 动态库的路径
+    pub fn path(&self) -> &Path {
+        &self.lib.path
+    }
+
+    /// 动态库的版本号
+    pub fn version(&self) -> &str {
+        &self.lib.version
+    }
+}
+
+impl std::fmt::Debug for LibTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LibTransport")
+            .field("path", &self.lib.path)
+            .field("version", &self.lib.version)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 交给动态库的推送回调，转发到 `PUSH_HANDLERS`
+extern "C" fn push_trampoline(data: *const c_char) {
+    if data.is_null() {
+        return;
+    }
+    let data = unsafe { CStr::from_ptr(data) }.to_string_lossy();
+    dispatch_push(&data);
+}
+
+impl Transport for LibTransport {
+    fn call(&self, method: &str, params: Option<&str>, buffer_size: usize) -> Result<CallStatus, THSError> {
+        self.lib.check_stuck()?;
+        self.lib.call(method, params, buffer_size)
+    }
+
+    fn call_timeout(
+        self: Arc<Self>,
+        method: &str,
+        params: Option<&str>,
+        buffer_size: usize,
+        timeout: Duration,
+    ) -> Result<CallStatus, THSError> {
+        self.lib.check_stuck()?;
+        self.lib.call_timeout(method, params, buffer_size, timeout)
+    }
+
+    fn set_push_handler(&self, handler: PushHandler) -> Result<(), THSError> {
+        self.push_slot.set(handler);
+        Ok(())
+    }
+}
+
+/// 记录在 [`MockTransport`] 中的一次调用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockCall {
+    pub method: String,
+    pub params: Option<String>,
+    pub buffer_size: usize,
+}
+
+type MockHandler = dyn Fn(&str, Option<&str>, usize) -> CallStatus + Send + Sync;
+
+#[derive(Default)]
+struct MockState {
+    replies: HashMap<String, VecDeque<CallStatus>>,
+    calls: Vec<MockCall>,
+    push_handler: Option<PushHandler>,
+}
+
+/// 可编排的内存调用通道，用于离线测试
+///
+/// 通过 [`MockTransport::on`] 为每个方法排队返回结果，按顺序依次返回，最后一个结果会被重复使用；
+/// 没有排队结果的方法交给 [`MockTransport::with_handler`] 设置的处理函数，否则返回 `CallStatus::Error(-2)`。
+/// 推送数据通过 [`MockTransport::push`] 模拟。
+/// 克隆出来的实例共享同一份状态，便于在交给 `THS` 之后继续检查调用记录。
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+    handler: Option<Arc<MockHandler>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为 `method` 追加一个返回结果
+    pub fn on(self, method: &str, status: CallStatus) -> Self {
+        self.state.lock().unwrap()
+            .replies
+            .entry(method.to_string())
+            .or_default()
+            .push_back(status);
+        self
+    }
+
+    /// 为 `method` 追加一个成功结果，`json` 为返回的 JSON 字符串
+    pub fn on_ok(self, method: &str, json: impl Into<String>) -> Self {
+        self.on(method, CallStatus::Ok(json.into()))
+    }
+
+    /// 设置兜底的处理函数
+    pub fn with_handler<F>(mut self, handler: F) -> Self
+    where F: Fn(&str, Option<&str>, usize) -> CallStatus + Send + Sync + 'static {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// 已发生的全部调用
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// 指定方法的调用记录
+    pub fn calls_to(&self, method: &str) -> Vec<MockCall> {
+        self.calls().into_iter().filter(|c| c.method == method).collect()
+    }
+
+    /// 模拟一次推送，返回是否有处理函数接收
+    pub fn push(&self, frame: &str) -> bool {
+        let handler = self.state.lock().unwrap().push_handler.clone();
+        match handler {
+            Some(handler) => {
+                handler(frame);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockTransport").finish_non_exhaustive()
+    }
+}
+
+impl Transport for MockTransport {
+    fn call(&self, method: &str, params: Option<&str>, buffer_size: usize) -> Result<CallStatus, THSError> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(MockCall {
+            method: method.to_string(),
+            params: params.map(str::to_string),
+            buffer_size,
+        });
+
+        if let Some(queue) = state.replies.get_mut(method) {
+            let status = if queue.len() > 1 { queue.pop_front() } else { queue.front().cloned() };
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+        drop(state);
+
+        match &self.handler {
+            Some(handler) => Ok(handler(method, params, buffer_size)),
+            None => Ok(CallStatus::Error(-2)),
+        }
+    }
+
+    fn set_push_handler(&self, handler: PushHandler) -> Result<(), THSError> {
+        self.state.lock().unwrap().push_handler = Some(handler);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_reaches_every_client() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handler = |name: &'static str| -> PushHandler {
+            let received = Arc::clone(&received);
+            Arc::new(move |frame: &str| received.lock().unwrap().push(format!("{}:{}", name, frame)))
+        };
+
+        let first = PushSlot::new();
+        let second = PushSlot::new();
+        first.set(handler("a"));
+        second.set(handler("b"));
+        // 重新设置不会重复注册
+        second.set(handler("b"));
+        dispatch_push("1");
+
+        drop(first);
+        dispatch_push("2");
+        drop(second);
+        dispatch_push("3");
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, ["a:1", "b:1", "b:2"]);
+    }
+
+    /// 输入中包含 `slow` 时阻塞 300ms
+    #[cfg(unix)]
+    unsafe extern "C" fn fake_call(input: *const c_char, output: *mut c_char, _size: c_int, _callback: *const c_void) -> c_int {
+        let input = unsafe { CStr::from_ptr(input) }.to_string_lossy();
+        if input.contains("slow") {
+            std::thread::sleep(Duration::from_millis(300));
+        }
+        let reply = CString::new("{}").unwrap();
+        unsafe { std::ptr::copy_nonoverlapping(reply.as_ptr(), output, 3) };
+        0
+    }
+
+    #[cfg(unix)]
+    fn fake_library() -> Arc<LibTransport> {
+        let lib = LoadedLibrary {
+            path: PathBuf::from("fake"),
+            version: "test".into(),
+            call_fn: fake_call,
+            call_lock: Mutex::new(()),
+            stuck: Mutex::new(None),
+            executor: OnceLock::new(),
+            _lib: libloading::os::unix::Library::this().into(),
+        };
+        Arc::new(LibTransport { lib: Arc::new(lib), push_slot: Arc::new(PushSlot::new()) })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stuck_call_fails_fast_until_it_returns() {
+        let transport = fake_library();
+        let ok = CallStatus::Ok("{}".into());
+        assert_eq!(Arc::clone(&transport).call_timeout("fast", None, 64, Duration::from_secs(1)).unwrap(), ok);
+
+        let err = Arc::clone(&transport).call_timeout("slow", None, 64, Duration::from_millis(50)).unwrap_err();
+        assert!(matches!(err, THSError::Timeout { .. }), "{:?}", err);
+        match transport.call("fast", None, 64) {
+            Err(THSError::LibraryBusy { method }) => assert_eq!(method, "slow"),
+            other => panic!("应为 LibraryBusy: {:?}", other),
+        }
+        assert!(matches!(
+            Arc::clone(&transport).call_timeout("fast", None, 64, Duration::from_secs(1)),
+            Err(THSError::LibraryBusy { .. })
+        ));
+
+        // 卡住的调用返回后恢复
+        std::thread::sleep(Duration::from_millis(400));
+        assert_eq!(Arc::clone(&transport).call_timeout("fast", None, 64, Duration::from_secs(1)).unwrap(), ok);
+        assert_eq!(transport.call("fast", None, 64).unwrap(), ok);
+    }
+}
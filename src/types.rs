@@ -1,7 +1,13 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::code::SecurityCode;
+use crate::error::THSError;
+use crate::fields::Field;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MarketData {
     pub code: String,
     pub name: String,
@@ -22,6 +28,217 @@ pub struct KLineData {
     pub amount: f64,
 }
 
+impl KLineData {
+    /// 从 `klines` 返回的一行原始数据解析
+    ///
+    /// `minute` 为 true 时，"时间" 字段是分钟K线的时间（`HHMM` 或 `HHMMSS`），需要和 "日期" 字段合并，缺少日期时返回错误；
+    /// 服务器直接返回 `YYYYMMDDHHMM` 形式的完整时间时也能识别。
+    pub fn from_row(row: &Value, minute: bool) -> Result<Self, THSError> {
+        let obj = row.as_object()
+            .ok_or_else(|| THSError::decode("K线数据格式错误", format!("应为对象: {}", row)))?;
+
+        let time = if minute {
+            parse_minute_time(obj)?
+        } else {
+            let date = parse_date(field(obj, "时间")?)?;
+            local_datetime(date.and_time(NaiveTime::MIN))?
+        };
+
+        Ok(Self {
+            time,
+            open: field_f64(obj, "开盘价")?,
+            high: field_f64(obj, "最高价")?,
+            low: field_f64(obj, "最低价")?,
+            close: field_f64(obj, "收盘价")?,
+            volume: field_f64(obj, "成交量")? as i64,
+            amount: field_f64(obj, "总金额")?,
+        })
+    }
+}
+
+fn field<'a>(obj: &'a Map<String, Value>, key: &str) -> Result<&'a Value, THSError> {
+    match obj.get(key) {
+        Some(Value::Null) | None => Err(THSError::decode("K线数据缺少字段", key.to_string())),
+        Some(v) => Ok(v),
+    }
+}
+
+fn field_f64(obj: &Map<String, Value>, key: &str) -> Result<f64, THSError> {
+    let value = field(obj, key)?;
+    value_f64(value).ok_or_else(|| THSError::decode("K线数据字段格式错误", format!("{}={}", key, value)))
+}
+
+pub(crate) fn value_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// 把数字或字符串形式的时间统一成纯数字字符串
+fn digits(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(n) => n.as_i64().map(|v| v.to_string()),
+        Value::String(s) => Some(s.chars().filter(|c| c.is_ascii_digit()).collect()),
+        _ => None,
+    }
+}
+
+fn parse_date(value: &Value) -> Result<NaiveDate, THSError> {
+    digits(value)
+        .and_then(|s| NaiveDate::parse_from_str(&s, "%Y%m%d").ok())
+        .ok_or_else(|| THSError::decode("K线日期格式错误", value.to_string()))
+}
+
+fn parse_minute_time(obj: &Map<String, Value>) -> Result<DateTime<Local>, THSError> {
+    let value = field(obj, "时间")?;
+    let s = digits(value).ok_or_else(|| THSError::decode("K线时间格式错误", value.to_string()))?;
+
+    let naive = match s.len() {
+        12 => NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M").ok(),
+        14 => NaiveDateTime::parse_from_str(&s, "%Y%m%d%H%M%S").ok(),
+        3..=6 => {
+            // 只有时间时由 "日期" 字段确定是哪一天，缺少日期无法得到正确的时间
+            let date = match obj.get("日期") {
+                Some(date) if !date.is_null() => parse_date(date)?,
+                _ => return Err(THSError::decode("分钟K线缺少日期", format!("时间={}", value))),
+            };
+            let time = if s.len() <= 4 {
+                NaiveTime::parse_from_str(&format!("{:0>4}", s), "%H%M")
+            } else {
+                NaiveTime::parse_from_str(&format!("{:0>6}", s), "%H%M%S")
+            };
+            time.ok().map(|t| date.and_time(t))
+        }
+        _ => None,
+    };
+
+    let naive = naive.ok_or_else(|| THSError::decode("K线时间格式错误", value.to_string()))?;
+    local_datetime(naive)
+}
+
+pub(crate) fn local_datetime(naive: NaiveDateTime) -> Result<DateTime<Local>, THSError> {
+    Local.from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| THSError::InvalidDate(format!("本地时间不存在: {}", naive)))
+}
+
+/// 个股行情快照，对应 `stock_market_data` 请求的字段
+///
+/// 没有请求的字段为 `None`，`market` 中没有请求的字段为默认值；
+/// 其余不在下列字段中的数据按 [`Field`] 的中文名称放在 `extra` 里。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Quote {
+    #[serde(flatten)]
+    pub market: MarketData,
+    pub pre_close: Option<f64>,
+    pub open: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub speed: Option<f64>,
+    pub amplitude: Option<f64>,
+    pub open_change_pct: Option<f64>,
+    pub change_5d: Option<f64>,
+    pub turnover_rate: Option<f64>,
+    pub volume_ratio: Option<f64>,
+    pub commission_ratio: Option<f64>,
+    pub current_volume: Option<i64>,
+    pub bid1: Option<f64>,
+    pub ask1: Option<f64>,
+    pub limit_up: Option<f64>,
+    pub limit_down: Option<f64>,
+    pub total_shares: Option<f64>,
+    pub float_shares: Option<f64>,
+    pub total_market_value: Option<f64>,
+    pub float_market_value: Option<f64>,
+    pub pe_ttm: Option<f64>,
+    pub main_net_volume: Option<f64>,
+    pub main_net_inflow: Option<f64>,
+    pub extra: HashMap<String, Value>,
+}
+
+impl Quote {
+    /// 从 `stock_market_data` 返回的一行原始数据解析
+    ///
+    /// 字段名既可以是中文名称，也可以是数字形式的 datatype，后者通过 [`Field::from_id`] 转换。
+    pub fn from_row(row: &Value) -> Result<Self, THSError> {
+        let obj = row.as_object()
+            .ok_or_else(|| THSError::decode("行情数据格式错误", format!("应为对象: {}", row)))?;
+
+        let mut quote = Quote::default();
+        for (key, value) in obj {
+            let name = key.parse::<i32>().ok()
+                .and_then(Field::from_id)
+                .map(Field::name)
+                .unwrap_or(key.as_str());
+            let number = value_f64(value);
+
+            match name {
+                "代码" => quote.market.code = value_string(value),
+                "名称" => quote.market.name = value_string(value),
+                "价格" => quote.market.price = number.unwrap_or_default(),
+                "涨跌" => quote.market.change = number.unwrap_or_default(),
+                "成交量" => quote.market.volume = number.unwrap_or_default() as i64,
+                "总金额" => quote.market.amount = number.unwrap_or_default(),
+                "昨收价" => quote.pre_close = number,
+                "开盘价" => quote.open = number,
+                "最高价" => quote.high = number,
+                "最低价" => quote.low = number,
+                "涨幅" => quote.change_pct = number,
+                "涨速" => quote.speed = number,
+                "振幅" => quote.amplitude = number,
+                "开盘涨幅" => quote.open_change_pct = number,
+                "5日涨幅" => quote.change_5d = number,
+                "换手率" => quote.turnover_rate = number,
+                "量比" => quote.volume_ratio = number,
+                "委比" => quote.commission_ratio = number,
+                "当前量(手)" => quote.current_volume = number.map(|v| v as i64),
+                "买1价" => quote.bid1 = number,
+                "卖1价" => quote.ask1 = number,
+                "涨停价" => quote.limit_up = number,
+                "跌停价" => quote.limit_down = number,
+                "总股本" => quote.total_shares = number,
+                "流通股本" => quote.float_shares = number,
+                "总市值" => quote.total_market_value = number,
+                "流通市值" => quote.float_market_value = number,
+                "市盈率TTM" => quote.pe_ttm = number,
+                "主力净量" => quote.main_net_volume = number,
+                "主力净流入" => quote.main_net_inflow = number,
+                _ => {
+                    quote.extra.insert(name.to_string(), value.clone());
+                }
+            }
+        }
+
+        if quote.market.code.is_empty() {
+            return Err(THSError::decode("行情数据缺少字段", "代码"));
+        }
+        Ok(quote)
+    }
+
+    /// 服务器没有返回涨跌停价时，按证券的涨跌幅限制和昨收价补全
+    pub fn fill_price_limits(&mut self, code: &SecurityCode) {
+        if self.limit_up.is_some() && self.limit_down.is_some() {
+            return;
+        }
+        if let Some(pre_close) = self.pre_close
+            && let Some((up, down)) = code.price_limits(pre_close) {
+            self.limit_up.get_or_insert(up);
+            self.limit_down.get_or_insert(down);
+        }
+    }
+}
+
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockData {
     pub code: String,
@@ -54,4 +271,100 @@ pub struct IpoData {
     pub pe: f64,
     pub shares: i64,
     pub date: String,
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn naive(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn kline(time: Value) -> Value {
+        json!({"时间": time, "开盘价": "10.1", "最高价": 10.5, "最低价": 9.9, "收盘价": "10.2", "成交量": 12345.0, "总金额": 1.5e6})
+    }
+
+    #[test]
+    fn daily_kline_row() {
+        let k = KLineData::from_row(&kline(json!(20240105)), false).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 00:00:00"));
+        assert_eq!((k.open, k.high, k.low, k.close), (10.1, 10.5, 9.9, 10.2));
+        assert_eq!(k.volume, 12345);
+        assert_eq!(k.amount, 1.5e6);
+
+        let k = KLineData::from_row(&kline(json!("2024-01-05")), false).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 00:00:00"));
+    }
+
+    #[test]
+    fn minute_kline_row() {
+        let k = KLineData::from_row(&kline(json!(202401050931i64)), true).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 09:31:00"));
+
+        let k = KLineData::from_row(&kline(json!("20240105093130")), true).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 09:31:30"));
+
+        let mut row = kline(json!(931));
+        row["日期"] = json!("20240105");
+        let k = KLineData::from_row(&row, true).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 09:31:00"));
+
+        row["时间"] = json!("93130");
+        let k = KLineData::from_row(&row, true).unwrap();
+        assert_eq!(k.time.naive_local(), naive("2024-01-05 09:31:30"));
+    }
+
+    #[test]
+    fn minute_kline_without_date_fails() {
+        let err = KLineData::from_row(&kline(json!(931)), true).unwrap_err();
+        assert!(matches!(err, THSError::Decode { .. }), "{}", err);
+
+        let mut row = kline(json!(931));
+        row["日期"] = Value::Null;
+        assert!(KLineData::from_row(&row, true).is_err());
+    }
+
+    #[test]
+    fn bad_kline_rows() {
+        assert!(KLineData::from_row(&json!([1, 2]), false).is_err());
+        assert!(KLineData::from_row(&kline(json!("2024")), false).is_err());
+        assert!(KLineData::from_row(&kline(json!(12)), true).is_err());
+
+        let mut row = kline(json!(20240105));
+        row.as_object_mut().unwrap().remove("开盘价");
+        assert!(KLineData::from_row(&row, false).is_err());
+        row["开盘价"] = json!("--");
+        assert!(KLineData::from_row(&row, false).is_err());
+    }
+
+    #[test]
+    fn quote_row_by_name_and_id() {
+        let row = json!({
+            "代码": "600000",
+            "10": "10.5",
+            "199112": 1.25,
+            "成交量": 1000.0,
+            "461256": "-12.5",
+            "53": 3.0,
+            "自定义": "x",
+        });
+        let quote = Quote::from_row(&row).unwrap();
+        assert_eq!(quote.market.code, "600000");
+        assert_eq!(quote.market.price, 10.5);
+        assert_eq!(quote.market.volume, 1000);
+        assert_eq!(quote.change_pct, Some(1.25));
+        assert_eq!(quote.commission_ratio, Some(-12.5));
+        assert_eq!(quote.open, None);
+        assert_eq!(quote.extra.get("委比(53)"), Some(&json!(3.0)));
+        assert_eq!(quote.extra.get("自定义"), Some(&json!("x")));
+    }
+
+    #[test]
+    fn bad_quote_rows() {
+        assert!(Quote::from_row(&json!("600000")).is_err());
+        assert!(Quote::from_row(&json!({"价格": 10.5})).is_err());
+    }
+}
\ No newline at end of file
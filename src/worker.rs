@@ -0,0 +1,690 @@
+//! 进程外调用：由 `rusths-worker` 子进程加载动态库，父进程通过管道发送调用
+//!
+//! 动态库是闭源的，其中的段错误或死锁会直接拖垮所在进程。开启 `ThsOption::worker` 后，
+//! 动态库只在子进程中加载，子进程崩溃时父进程的调用返回错误，下一次调用自动重启子进程。
+//!
+//! 子进程一次只执行一个调用，其余调用在父进程中排队，超时从请求写入子进程时开始计算：
+//! 执行超过限定时间时子进程被结束，该调用返回 `THSError::Timeout`；排队超时的调用同样返回
+//! `THSError::Timeout`，但不影响子进程和正在执行的调用。子进程中的错误按 [`ErrorKind`] 还原。
+//!
+//! 协议：父进程向子进程的标准输入逐行写入 [`WorkerRequest`]，子进程向标准输出逐行写入 [`WorkerReply`]，
+//! 每行都以 [`FRAME_MARKER`] 开头，动态库自己打印到标准输出的内容会被忽略。
+//!
+//! 子进程重启后没有登录状态，在重新 `connect` 之前，除 `connect`、`disconnect`、`help` 外的调用都返回
+//! `THSError::NotLoggedIn`，交给 `ReconnectPolicy` 重连并重放请求。
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::error::{ErrorKind, THSError};
+use crate::transport::{CallStatus, LibTransport, PushHandler, Transport};
+
+/// 子进程可执行文件路径的环境变量
+pub const WORKER_ENV: &str = "RUSTHS_WORKER";
+
+/// 子进程可执行文件的默认名称
+pub const WORKER_NAME: &str = "rusths-worker";
+
+/// 协议帧的起始标记，用于和动态库自己的输出区分
+pub const FRAME_MARKER: char = '\u{1e}';
+
+/// 不需要登录就可以调用的方法
+const SESSIONLESS_METHODS: [&str; 3] = ["connect", "disconnect", "help"];
+
+/// 进程外调用的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerOption {
+    /// `rusths-worker` 的路径，未设置时依次使用环境变量 `RUSTHS_WORKER`、
+    /// 当前可执行文件所在目录中的 `rusths-worker` 和 `PATH` 中的 `rusths-worker`
+    pub path: Option<PathBuf>,
+    /// 单次调用在子进程中执行的最长时间，不含排队时间，超过后结束子进程
+    pub call_timeout: Duration,
+    /// 子进程启动并加载动态库的最长时间
+    pub startup_timeout: Duration,
+}
+
+impl Default for WorkerOption {
+    fn default() -> Self {
+        Self {
+            path: None,
+            call_timeout: Duration::from_secs(30),
+            startup_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WorkerOption {
+    /// 子进程可执行文件的路径
+    pub fn program(&self) -> PathBuf {
+        if let Some(path) = &self.path {
+            return path.clone();
+        }
+        if let Some(path) = std::env::var_os(WORKER_ENV).filter(|p| !p.is_empty()) {
+            return PathBuf::from(path);
+        }
+        let name = format!("{}{}", WORKER_NAME, std::env::consts::EXE_SUFFIX);
+        std::env::current_exe().ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(&name)))
+            .filter(|path| path.is_file())
+            .unwrap_or_else(|| PathBuf::from(name))
+    }
+}
+
+/// 父进程发给子进程的请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerRequest {
+    Call {
+        id: u64,
+        method: String,
+        params: Option<String>,
+        buffer_size: usize,
+    },
+    /// 开始转发推送数据
+    EnablePush,
+}
+
+/// 子进程发给父进程的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerReply {
+    /// 动态库加载成功
+    Ready { version: String },
+    /// 动态库加载失败，子进程随后退出
+    Failed { message: String },
+    Result { id: u64, status: CallStatus },
+    Error { id: u64, kind: ErrorKind, message: String },
+    Push { frame: String },
+}
+
+/// 错误的描述，不带 `THSError` 的类别前缀，由父进程重新包装
+fn error_message(err: &THSError) -> String {
+    match err {
+        THSError::LibraryError(message)
+        | THSError::ApiError(message)
+        | THSError::InvalidCode(message)
+        | THSError::InvalidDate(message)
+        | THSError::UnknownField(message)
+        | THSError::NoData(message)
+        | THSError::UnsupportedPlatform(message)
+        | THSError::Server { err_info: message } => message.clone(),
+        THSError::IoError(e) => e.to_string(),
+        err => err.to_string(),
+    }
+}
+
+/// 按类别还原子进程返回的错误
+///
+/// 子进程中的错误只带类别和描述，需要其它信息的变体用本次调用的参数补全。
+fn remote_error(kind: ErrorKind, message: String, method: &str, buffer_size: usize, timeout: Duration) -> THSError {
+    match kind {
+        ErrorKind::Io => THSError::IoError(std::io::Error::other(message)),
+        ErrorKind::Library => THSError::LibraryError(message),
+        ErrorKind::InvalidInput => THSError::InvalidCode(message),
+        ErrorKind::NoData => THSError::NoData(message),
+        ErrorKind::UnsupportedPlatform => THSError::UnsupportedPlatform(message),
+        ErrorKind::BufferTooSmall => THSError::BufferTooSmall { size: buffer_size },
+        ErrorKind::MethodNotFound => THSError::MethodNotFound { code: -2, method: method.to_string() },
+        ErrorKind::NotLoggedIn => THSError::NotLoggedIn,
+        ErrorKind::Server => THSError::Server { err_info: message },
+        ErrorKind::Decode => THSError::decode("工作进程调用失败", message),
+        ErrorKind::Timeout => THSError::Timeout { method: method.to_string(), timeout },
+        ErrorKind::Other => THSError::ApiError(message),
+    }
+}
+
+/// `connect` 的返回结果是否表示登录成功，`err_info` 不为空时登录失败
+fn login_succeeded(status: &CallStatus) -> bool {
+    #[derive(Deserialize)]
+    struct Login {
+        #[serde(default)]
+        err_info: String,
+    }
+
+    match status {
+        CallStatus::Ok(output) => serde_json::from_str::<Login>(output).is_ok_and(|login| login.err_info.is_empty()),
+        _ => false,
+    }
+}
+
+fn write_frame<W: Write, T: Serialize>(out: &mut W, value: &T) -> std::io::Result<()> {
+    let line = serde_json::to_string(value).map_err(std::io::Error::other)?;
+    writeln!(out, "{}{}", FRAME_MARKER, line)?;
+    out.flush()
+}
+
+fn read_frame<T: serde::de::DeserializeOwned>(line: &str) -> Option<T> {
+    let start = line.find(FRAME_MARKER)?;
+    serde_json::from_str(&line[start + FRAME_MARKER.len_utf8()..]).ok()
+}
+
+/// 子进程的主循环：加载动态库，逐行处理标准输入中的请求，直到标准输入关闭
+pub fn serve(lib_path: &Path, version: &str) -> Result<(), THSError> {
+    let stdout = Arc::new(Mutex::new(std::io::stdout()));
+    let send = move |reply: &WorkerReply| {
+        let mut out = stdout.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = write_frame(&mut *out, reply);
+    };
+
+    let transport = match LibTransport::load(lib_path, version) {
+        Ok(transport) => transport,
+        Err(e) => {
+            send(&WorkerReply::Failed { message: error_message(&e) });
+            return Err(e);
+        }
+    };
+    serve_transport(&transport, version, std::io::stdin().lock(), send)
+}
+
+/// 用已加载的调用通道处理请求
+fn serve_transport<T, R, S>(transport: &T, version: &str, input: R, send: S) -> Result<(), THSError>
+where
+    T: Transport,
+    R: BufRead,
+    S: Fn(&WorkerReply) + Clone + Send + Sync + 'static,
+{
+    send(&WorkerReply::Ready { version: version.to_string() });
+
+    for line in input.lines() {
+        let line = line?;
+        let Some(request) = read_frame::<WorkerRequest>(&line) else {
+            continue;
+        };
+        match request {
+            WorkerRequest::Call { id, method, params, buffer_size } => {
+                let reply = match transport.call(&method, params.as_deref(), buffer_size) {
+                    Ok(status) => WorkerReply::Result { id, status },
+                    Err(e) => WorkerReply::Error { id, kind: e.kind(), message: error_message(&e) },
+                };
+                send(&reply);
+            }
+            WorkerRequest::EnablePush => {
+                let send = send.clone();
+                let handler: PushHandler = Arc::new(move |frame: &str| {
+                    send(&WorkerReply::Push { frame: frame.to_string() });
+                });
+                transport.set_push_handler(handler)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+type Pending = Arc<Mutex<HashMap<u64, Sender<WorkerReply>>>>;
+
+/// 一个运行中的子进程
+struct Worker {
+    /// 每启动一个子进程加一，用于判断调用结束时子进程是否已经被替换
+    serial: u64,
+    child: Child,
+    stdin: ChildStdin,
+    pending: Pending,
+    /// 是否已经在该子进程中登录
+    session: bool,
+}
+
+impl Worker {
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 通过 `rusths-worker` 子进程调用动态库的调用通道
+pub struct WorkerTransport {
+    option: WorkerOption,
+    lib_path: PathBuf,
+    version: String,
+    worker: Mutex<Option<Worker>>,
+    push_handler: Arc<RwLock<Option<PushHandler>>>,
+    next_id: AtomicU64,
+    serial: AtomicU64,
+    /// 子进程正在执行调用，其余调用等待 `idle`
+    busy: Mutex<bool>,
+    idle: Condvar,
+}
+
+/// 执行调用的权利，释放时唤醒下一个排队的调用
+struct Turn<'a>(&'a WorkerTransport);
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        *self.0.busy.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        self.0.idle.notify_one();
+    }
+}
+
+impl WorkerTransport {
+    /// 启动子进程并加载 `lib_path` 处的动态库，加载失败时返回错误
+    pub fn spawn(option: WorkerOption, lib_path: impl AsRef<Path>, version: &str) -> Result<Self, THSError> {
+        let transport = Self {
+            option,
+            lib_path: lib_path.as_ref().to_path_buf(),
+            version: version.to_string(),
+            worker: Mutex::new(None),
+            push_handler: Arc::new(RwLock::new(None)),
+            next_id: AtomicU64::new(1),
+            serial: AtomicU64::new(0),
+            busy: Mutex::new(false),
+            idle: Condvar::new(),
+        };
+        let worker = transport.start()?;
+        *transport.worker.lock().unwrap() = Some(worker);
+        Ok(transport)
+    }
+
+    /// 动态库的路径
+    pub fn lib_path(&self) -> &Path {
+        &self.lib_path
+    }
+
+    fn start(&self) -> Result<Worker, THSError> {
+        let program = self.option.program();
+        let mut child = Command::new(&program)
+            .arg(&self.lib_path)
+            .arg(&self.version)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| THSError::LibraryError(format!("启动工作进程失败: {}: {}", program.display(), e)))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let serial = self.serial.fetch_add(1, Ordering::SeqCst) + 1;
+        let pending: Pending = Arc::default();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let reader_pending = Arc::clone(&pending);
+        let push_handler = Arc::clone(&self.push_handler);
+        std::thread::Builder::new()
+            .name("rusths-worker-reader".into())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    let Some(reply) = read_frame::<WorkerReply>(&line) else {
+                        debug!(output = %line, "工作进程输出");
+                        continue;
+                    };
+                    match reply {
+                        WorkerReply::Result { id, .. } | WorkerReply::Error { id, .. } => {
+                            if let Some(tx) = reader_pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id) {
+                                let _ = tx.send(reply);
+                            }
+                        }
+                        WorkerReply::Push { frame } => {
+                            let handler = push_handler.read().unwrap_or_else(|e| e.into_inner()).clone();
+                            if let Some(handler) = handler {
+                                handler(&frame);
+                            }
+                        }
+                        WorkerReply::Ready { .. } | WorkerReply::Failed { .. } => {
+                            let _ = ready_tx.send(reply);
+                        }
+                    }
+                }
+                // 子进程退出，丢弃等待中的调用，调用方收到断开的错误
+                reader_pending.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                debug!(serial, "工作进程输出结束");
+            })
+            .map_err(THSError::IoError)?;
+
+        let mut worker = Worker { serial, child, stdin, pending, session: false };
+        match ready_rx.recv_timeout(self.option.startup_timeout) {
+            Ok(WorkerReply::Ready { .. }) => {}
+            Ok(WorkerReply::Failed { message }) => {
+                worker.kill();
+                return Err(THSError::LibraryError(message));
+            }
+            _ => {
+                worker.kill();
+                return Err(THSError::LibraryError(format!("工作进程启动失败: {}", program.display())));
+            }
+        }
+
+        if self.push_handler.read().unwrap_or_else(|e| e.into_inner()).is_some() {
+            write_frame(&mut worker.stdin, &WorkerRequest::EnablePush)?;
+        }
+        info!(serial, pid = worker.child.id(), path = %self.lib_path.display(), "工作进程已启动");
+        Ok(worker)
+    }
+
+    /// 结束编号为 `serial` 的子进程，已经被替换时什么都不做
+    fn discard(&self, serial: u64) {
+        let mut guard = self.worker.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(worker) = guard.as_mut()
+            && worker.serial == serial {
+            worker.kill();
+            *guard = None;
+        }
+    }
+}
+
+impl std::fmt::Debug for WorkerTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerTransport")
+            .field("lib_path", &self.lib_path)
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkerTransport {
+    /// 等待子进程空闲，等待超过 `timeout` 时返回 `Timeout`
+    fn wait_turn(&self, method: &str, timeout: Duration) -> Result<Turn<'_>, THSError> {
+        let busy = self.busy.lock().unwrap_or_else(|e| e.into_inner());
+        let (mut busy, _) = self.idle.wait_timeout_while(busy, timeout, |busy| *busy)
+            .unwrap_or_else(|e| e.into_inner());
+        if *busy {
+            debug!(method, timeout = ?timeout, "等待工作进程空闲超时");
+            return Err(THSError::Timeout { method: method.to_string(), timeout });
+        }
+        *busy = true;
+        Ok(Turn(self))
+    }
+
+    fn call_within(&self, method: &str, params: Option<&str>, buffer_size: usize, timeout: Duration) -> Result<CallStatus, THSError> {
+        let _turn = self.wait_turn(method, timeout)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+
+        let serial = {
+            let mut guard = self.worker.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(worker) = guard.as_mut()
+                && !worker.is_alive() {
+                warn!(serial = worker.serial, "工作进程已退出，重新启动");
+                worker.kill();
+                *guard = None;
+            }
+            if guard.is_none() {
+                *guard = Some(self.start()?);
+            }
+            let worker = guard.as_mut().expect("worker started");
+
+            if !worker.session && !SESSIONLESS_METHODS.contains(&method) {
+                return Err(THSError::NotLoggedIn);
+            }
+
+            worker.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(id, tx);
+            let request = WorkerRequest::Call {
+                id,
+                method: method.to_string(),
+                params: params.map(str::to_string),
+                buffer_size,
+            };
+            if let Err(e) = write_frame(&mut worker.stdin, &request) {
+                worker.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+                return Err(THSError::IoError(e));
+            }
+            worker.serial
+        };
+
+        match rx.recv_timeout(timeout) {
+            Ok(WorkerReply::Result { status, .. }) => {
+                let session = match method {
+                    "connect" if login_succeeded(&status) => Some(true),
+                    "disconnect" if matches!(status, CallStatus::Ok(_)) => Some(false),
+                    _ => None,
+                };
+                if let Some(session) = session {
+                    let mut guard = self.worker.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(worker) = guard.as_mut()
+                        && worker.serial == serial {
+                        worker.session = session;
+                    }
+                }
+                Ok(status)
+            }
+            Ok(WorkerReply::Error { kind, message, .. }) => Err(remote_error(kind, message, method, buffer_size, timeout)),
+            Ok(other) => Err(THSError::decode("工作进程返回了意外的消息", format!("{:?}", other))),
+            Err(RecvTimeoutError::Timeout) => {
+                warn!(method, serial, timeout = ?timeout, "工作进程调用超时，结束工作进程");
+                self.discard(serial);
+                Err(THSError::Timeout { method: method.to_string(), timeout })
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!(method, serial, "工作进程异常退出");
+                self.discard(serial);
+                Err(THSError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("工作进程异常退出: {}", method),
+                )))
+            }
+        }
+    }
+}
+
+impl Transport for WorkerTransport {
+    fn call(&self, method: &str, params: Option<&str>, buffer_size: usize) -> Result<CallStatus, THSError> {
+        self.call_within(method, params, buffer_size, self.option.call_timeout)
+    }
+
+    /// 超时时间不超过 `call_timeout`，执行超时后结束子进程
+    fn call_timeout(
+        self: Arc<Self>,
+        method: &str,
+        params: Option<&str>,
+        buffer_size: usize,
+        timeout: Duration,
+    ) -> Result<CallStatus, THSError> {
+        self.call_within(method, params, buffer_size, timeout.min(self.option.call_timeout))
+    }
+
+    fn set_push_handler(&self, handler: PushHandler) -> Result<(), THSError> {
+        *self.push_handler.write().unwrap_or_else(|e| e.into_inner()) = Some(handler);
+        let mut guard = self.worker.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(worker) = guard.as_mut() {
+            write_frame(&mut worker.stdin, &WorkerRequest::EnablePush)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WorkerTransport {
+    fn drop(&mut self) {
+        if let Some(mut worker) = self.worker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            worker.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+
+    /// 测试程序以这两个参数启动时只运行 [`fake_worker`]，充当工作进程
+    const FAKE_WORKER: &str = "worker::tests::fake_worker";
+    const FAKE_VERSION: &str = "rusths-fake-worker";
+
+    const OK: &str = r#"{"err_info":"","payload":{"result":null}}"#;
+
+    /// 假的动态库：`sleep` 等待 `params` 毫秒，`crash` 结束进程，`fail` 返回动态库错误，
+    /// `connect` 的参数为 `bad` 时登录失败
+    struct FakeLibrary;
+
+    impl Transport for FakeLibrary {
+        fn call(&self, method: &str, params: Option<&str>, _buffer_size: usize) -> Result<CallStatus, THSError> {
+            match method {
+                "connect" if params == Some("bad") => Ok(CallStatus::Ok(r#"{"err_info":"密码错误","payload":{}}"#.into())),
+                "sleep" => {
+                    thread::sleep(Duration::from_millis(params.unwrap_or("0").parse().unwrap()));
+                    Ok(CallStatus::Ok(OK.into()))
+                }
+                "crash" => std::process::exit(3),
+                "fail" => Err(THSError::LibraryError("模拟失败".into())),
+                _ => Ok(CallStatus::Ok(OK.into())),
+            }
+        }
+    }
+
+    #[test]
+    fn fake_worker() {
+        if !std::env::args().any(|arg| arg == FAKE_VERSION) {
+            return;
+        }
+        let stdout = Arc::new(Mutex::new(std::io::stdout()));
+        let send = move |reply: &WorkerReply| {
+            let _ = write_frame(&mut *stdout.lock().unwrap(), reply);
+        };
+        let _ = serve_transport(&FakeLibrary, FAKE_VERSION, std::io::stdin().lock(), send);
+        std::process::exit(0);
+    }
+
+    fn spawn() -> Arc<WorkerTransport> {
+        let option = WorkerOption {
+            path: Some(std::env::current_exe().unwrap()),
+            call_timeout: Duration::from_secs(10),
+            ..WorkerOption::default()
+        };
+        Arc::new(WorkerTransport::spawn(option, FAKE_WORKER, FAKE_VERSION).unwrap())
+    }
+
+    fn serial(worker: &WorkerTransport) -> Option<u64> {
+        worker.worker.lock().unwrap().as_ref().map(|w| w.serial)
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let mut out = Vec::new();
+        let request = WorkerRequest::Call {
+            id: 7,
+            method: "klines".into(),
+            params: Some("{\"code\":\"换行\\n\"}".into()),
+            buffer_size: 1024,
+        };
+        write_frame(&mut out, &request).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with(FRAME_MARKER));
+        assert_eq!(line.find('\n'), Some(line.len() - 1));
+
+        let Some(WorkerRequest::Call { id, method, params, buffer_size }) = read_frame(line.trim_end()) else {
+            panic!("无法解析: {}", line);
+        };
+        assert_eq!((id, method.as_str(), buffer_size), (7, "klines", 1024));
+        assert_eq!(params.as_deref(), Some("{\"code\":\"换行\\n\"}"));
+
+        // 动态库自己的输出没有起始标记，输出在同一行时跳过标记前的内容
+        assert!(read_frame::<WorkerReply>("动态库日志 {\"type\":\"ready\"}").is_none());
+        let line = format!("动态库日志{}{}", FRAME_MARKER, r#"{"type":"error","id":3,"kind":"Library","message":"x"}"#);
+        assert!(matches!(
+            read_frame::<WorkerReply>(&line),
+            Some(WorkerReply::Error { id: 3, kind: ErrorKind::Library, .. })
+        ));
+    }
+
+    #[test]
+    fn errors_keep_their_kind() {
+        let kinds = [
+            ErrorKind::Io,
+            ErrorKind::Library,
+            ErrorKind::InvalidInput,
+            ErrorKind::NoData,
+            ErrorKind::UnsupportedPlatform,
+            ErrorKind::BufferTooSmall,
+            ErrorKind::MethodNotFound,
+            ErrorKind::NotLoggedIn,
+            ErrorKind::Server,
+            ErrorKind::Decode,
+            ErrorKind::Timeout,
+            ErrorKind::Other,
+        ];
+        for kind in kinds {
+            assert_eq!(remote_error(kind, "x".into(), "help", 1024, Duration::ZERO).kind(), kind);
+        }
+        assert_eq!(error_message(&THSError::Server { err_info: "繁忙".into() }), "繁忙");
+
+        let worker = spawn();
+        worker.call("connect", None, 1024).unwrap();
+        let err = worker.call("fail", None, 1024).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Library);
+        assert_eq!(err.to_string(), "动态库错误: 模拟失败");
+    }
+
+    #[test]
+    fn session_follows_login_result() {
+        let worker = spawn();
+        assert!(matches!(worker.call("help", None, 1024), Ok(CallStatus::Ok(_))));
+        assert!(matches!(worker.call("quote", None, 1024), Err(THSError::NotLoggedIn)));
+
+        worker.call("connect", Some("bad"), 1024).unwrap();
+        assert!(matches!(worker.call("quote", None, 1024), Err(THSError::NotLoggedIn)));
+
+        worker.call("connect", None, 1024).unwrap();
+        assert!(worker.call("quote", None, 1024).is_ok());
+
+        worker.call("disconnect", None, 1024).unwrap();
+        assert!(matches!(worker.call("quote", None, 1024), Err(THSError::NotLoggedIn)));
+    }
+
+    #[test]
+    fn restarts_after_crash() {
+        let worker = spawn();
+        worker.call("connect", None, 1024).unwrap();
+        let first = serial(&worker);
+
+        assert_eq!(worker.call("crash", None, 1024).unwrap_err().kind(), ErrorKind::Io);
+        assert!(worker.call("help", None, 1024).is_ok());
+        assert_ne!(serial(&worker), first);
+
+        // 新的子进程没有登录
+        assert!(matches!(worker.call("quote", None, 1024), Err(THSError::NotLoggedIn)));
+        worker.call("connect", None, 1024).unwrap();
+        assert!(worker.call("quote", None, 1024).is_ok());
+    }
+
+    #[test]
+    fn timeout_fails_only_the_running_call() {
+        let worker = spawn();
+        worker.call("connect", None, 1024).unwrap();
+        let first = serial(&worker);
+
+        let running = Arc::clone(&worker);
+        let sleeper = thread::spawn(move || running.call_timeout("sleep", Some("5000"), 1024, Duration::from_millis(300)));
+        thread::sleep(Duration::from_millis(50));
+
+        // 排在卡住的调用之后，等它超时后在新的子进程中执行
+        let queued = Arc::clone(&worker).call_timeout("help", None, 1024, Duration::from_secs(5));
+        assert!(matches!(sleeper.join().unwrap(), Err(THSError::Timeout { .. })));
+        assert!(matches!(queued, Ok(CallStatus::Ok(_))));
+        assert_ne!(serial(&worker), first);
+    }
+
+    #[test]
+    fn queued_timeout_leaves_worker_running() {
+        let worker = spawn();
+        worker.call("connect", None, 1024).unwrap();
+        let first = serial(&worker);
+
+        let running = Arc::clone(&worker);
+        let sleeper = thread::spawn(move || running.call_timeout("sleep", Some("500"), 1024, Duration::from_secs(5)));
+        thread::sleep(Duration::from_millis(50));
+
+        let started = Instant::now();
+        let queued = Arc::clone(&worker).call_timeout("quote", None, 1024, Duration::from_millis(100));
+        assert!(matches!(queued, Err(THSError::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_millis(400));
+
+        assert!(sleeper.join().unwrap().is_ok());
+        assert_eq!(serial(&worker), first);
+        assert!(worker.call("quote", None, 1024).is_ok());
+    }
+}